@@ -1,32 +1,334 @@
+use std::ops::RangeInclusive;
 use std::path;
-use clap::Parser;
+use std::time::Duration;
+use clap::{Parser, Subcommand};
+use crate::config::{AppTraceType, LogLevel, WallClock};
+use crate::dlt::chaos::Chaos;
+use crate::dlt::InvalidUtf8;
+use crate::dlt::format::InputFormat;
 
 #[derive(Parser,Debug)]
 #[command(author, version, about, long_about = None)]
+#[command(subcommand_negates_reqs = true)]
 pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     /// configuration file
-    #[arg(short, long, value_name = "FILE")]
+    #[arg(short, long, value_name = "FILE", global = true)]
     pub config: Option<path::PathBuf>,
 
-    /// input files
-    #[arg(short, long, value_name = "INPUT", required = true)]
+    /// input files, DLT traces, raw DLT messages or pcap/pcapng captures of DLT on port 3490
+    #[arg(short, long, value_name = "INPUT", required_unless_present = "manifest")]
     input: Vec<path::PathBuf>,
 
-    /// ECU id for filtering
-    #[arg(long = "ecu")]
-    ecu_id: Option<String>,
+    /// CSV or JSON file listing the input files with metadata about each one, written in the
+    /// output columns named like its keys
+    #[arg(long, value_name = "FILE", conflicts_with = "input")]
+    manifest: Option<path::PathBuf>,
+
+    /// format of the input files, detected per file if not given
+    #[arg(long, value_enum, global = true)]
+    format: Option<InputFormat>,
 
-    /// APP id for filtering
-    #[arg(long = "app")]
-    app_id: Option<String>,
+    /// ECU ids for filtering, comma separated, may contain the wildcards '*' and '?'
+    #[arg(long = "ecu", value_delimiter = ',')]
+    ecu_id: Vec<String>,
 
-    /// CONTEXT id for filtering
-    #[arg(long = "ctx")]
-    context_id: Option<String>,
+    /// APP ids for filtering, comma separated, may contain the wildcards '*' and '?'
+    #[arg(long = "app", value_delimiter = ',')]
+    app_id: Vec<String>,
+
+    /// CONTEXT ids for filtering, comma separated, may contain the wildcards '*' and '?'
+    #[arg(long = "ctx", value_delimiter = ',')]
+    context_id: Vec<String>,
 
     /// patterns used for filtering
     #[arg(short, long)]
     patterns: Vec<String>,
+
+    /// only output the buffer overflow notifications of the DLT daemon
+    #[arg(long)]
+    buffer_overflow: bool,
+
+    /// only output app-trace messages of the given type
+    #[arg(long, value_enum)]
+    app_trace: Option<AppTraceType>,
+
+    /// drop log messages less severe than the given level, e.g. warn drops info, debug and verbose
+    #[arg(long, value_enum)]
+    level: Option<LogLevel>,
+
+    /// drop messages stored before this time, as seconds since the epoch or RFC 3339 time
+    #[arg(long, value_name = "TIME", value_parser = WallClock::parse)]
+    time_from: Option<Duration>,
+
+    /// drop messages stored after this time, as seconds since the epoch or RFC 3339 time
+    #[arg(long, value_name = "TIME", value_parser = WallClock::parse)]
+    time_to: Option<Duration>,
+
+    /// drop messages logged earlier than this many seconds after boot of the ECU
+    #[arg(long, value_name = "SECONDS", value_parser = parse_uptime)]
+    uptime_from: Option<Duration>,
+
+    /// drop messages logged later than this many seconds after boot of the ECU
+    #[arg(long, value_name = "SECONDS", value_parser = parse_uptime)]
+    uptime_to: Option<Duration>,
+
+    /// skip the messages before this index, counting from 0 in each input file like the index
+    /// field
+    #[arg(long, value_name = "INDEX")]
+    from_index: Option<usize>,
+
+    /// stop after the message of this index, counting from 0 in each input file
+    #[arg(long, value_name = "INDEX")]
+    to_index: Option<usize>,
+
+    /// also output this many messages before each match, overriding the config
+    #[arg(short = 'B', long, value_name = "N")]
+    before: Option<usize>,
+
+    /// also output this many messages after each match, overriding the config
+    #[arg(short = 'A', long, value_name = "N")]
+    after: Option<usize>,
+
+    /// only output messages matching the expression, e.g. 'app == "NAV" && level <= warn'
+    #[arg(long = "where", value_name = "EXPRESSION")]
+    query: Option<String>,
+
+    /// require ids of 4 uppercase letters or digits and warn about ids in the trace that only
+    /// differ from the filtered ones by padding
+    #[arg(long)]
+    strict_ids: bool,
+
+    /// flush the output after each match instead of when the buffer is full
+    #[arg(long)]
+    line_buffered: bool,
+
+    /// stamp outputs with tool version, command line and hashes of config and input files
+    #[arg(long)]
+    provenance: bool,
+
+    /// how string arguments that are not valid UTF-8 are written
+    #[arg(long, value_enum, default_value_t = InvalidUtf8::Replace, global = true)]
+    invalid_utf8: InvalidUtf8,
+
+    /// DBC file describing the CAN frames of network trace messages
+    #[arg(long, value_name = "FILE", global = true)]
+    dbc: Option<path::PathBuf>,
+
+    /// directory the outputs of the config are confined to, below a directory named after the
+    /// project of the config; for servers shared by several teams
+    #[arg(long, value_name = "DIR", global = true)]
+    output_root: Option<path::PathBuf>,
+
+    /// write warnings about the input, e.g. skipped corrupt data, to this file instead of stderr
+    #[arg(long, value_name = "FILE", global = true)]
+    warnings: Option<path::PathBuf>,
+
+    /// inject a fault into the files the outputs write, for integration tests; random network
+    /// errors are deferred until an output sends to the network
+    #[arg(long, value_enum, value_name = "FAULT", hide = true)]
+    chaos: Option<Chaos>,
+}
+
+#[derive(Subcommand,Debug)]
+pub enum Command {
+    /// work with configuration files
+    Config {
+        #[command(subcommand)]
+        action: ConfigCommand,
+    },
+    /// compile a config with the FIBEX files of its filters into a file that loads faster, for
+    /// short-lived runs, e.g. in CI; it is passed with --config like the config itself
+    Compile {
+        // not named config, which would take the value of the global --config
+        /// config file to compile
+        #[arg(value_name = "CONFIG")]
+        source: path::PathBuf,
+
+        /// file the compiled config is written to
+        #[arg(short, long, value_name = "FILE")]
+        output: path::PathBuf,
+    },
+    /// print every message of the input files using the default format, no configuration needed
+    Cat {
+        /// input files
+        #[arg(value_name = "INPUT", required = true)]
+        input: Vec<path::PathBuf>,
+
+        /// only print the first N messages
+        #[arg(long, value_name = "N", conflicts_with = "tail")]
+        head: Option<usize>,
+
+        /// only print the last N messages
+        #[arg(long, value_name = "N")]
+        tail: Option<usize>,
+
+        /// index of the first message to print, counting from 0 over all input files
+        #[arg(long, value_name = "INDEX")]
+        from: Option<usize>,
+
+        /// index of the last message to print, counting from 0 over all input files
+        #[arg(long, value_name = "INDEX")]
+        to: Option<usize>,
+
+        /// align the fields in columns and cut the lines to the width of the terminal
+        #[arg(long)]
+        pretty: bool,
+    },
+    /// print statistics about the messages in the input files
+    Stats {
+        /// input files
+        #[arg(value_name = "INPUT", required = true)]
+        input: Vec<path::PathBuf>,
+
+        /// emit the statistics as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// only write the alerts of the config and periodic summaries as JSON lines, for long soak tests
+    Alert {
+        /// input file, e.g. the trace the logger writes to
+        #[arg(value_name = "INPUT")]
+        input: path::PathBuf,
+
+        /// keep reading as the input grows, like tail -f
+        #[arg(long)]
+        follow: bool,
+
+        /// look for changes of the input and the config by polling instead of file system
+        /// events, which network shares often do not deliver
+        #[arg(long, requires = "follow")]
+        poll: bool,
+
+        /// seconds between two summaries
+        #[arg(long, value_name = "SECONDS", default_value_t = 60)]
+        summary_interval: u64,
+
+        /// file the summaries are appended to as JSON lines, instead of being written to stdout
+        /// along the alerts
+        #[arg(long, value_name = "FILE")]
+        summary_output: Option<path::PathBuf>,
+    },
+    /// print the durations of the function calls traced with FunctionIn/FunctionOut messages
+    Calls {
+        /// input files
+        #[arg(value_name = "INPUT", required = true)]
+        input: Vec<path::PathBuf>,
+    },
+    /// infer the typical argument types of the verbose messages of each context and flag the
+    /// messages deviating from them
+    Schema {
+        /// input files
+        #[arg(value_name = "INPUT", required = true)]
+        input: Vec<path::PathBuf>,
+
+        /// emit the report as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// export spans and function calls as Chrome/Perfetto trace events
+    Timeline {
+        /// input files
+        #[arg(value_name = "INPUT", required = true)]
+        input: Vec<path::PathBuf>,
+
+        /// JSON file the trace events are written to
+        #[arg(short, long, value_name = "FILE")]
+        output: path::PathBuf,
+    },
+    /// copy the messages of a time window to a new trace without decoding them
+    Trim {
+        /// input file
+        #[arg(value_name = "INPUT")]
+        input: path::PathBuf,
+
+        /// start of the window as storage time in seconds since the epoch
+        #[arg(long, value_name = "SECONDS")]
+        from: Option<f64>,
+
+        /// end of the window as storage time in seconds since the epoch
+        #[arg(long, value_name = "SECONDS")]
+        to: Option<f64>,
+
+        /// trace file the messages are written to
+        #[arg(short, long, value_name = "FILE")]
+        output: path::PathBuf,
+    },
+    /// package a trimmed and redacted copy of the input with the config, its statistics and the
+    /// warnings about skipped corrupt data into a zip archive, e.g. for a supplier ticket
+    Bundle {
+        /// input file
+        #[arg(value_name = "INPUT")]
+        input: path::PathBuf,
+
+        /// start of the window as storage time in seconds since the epoch
+        #[arg(long, value_name = "SECONDS")]
+        from: Option<f64>,
+
+        /// end of the window as storage time in seconds since the epoch
+        #[arg(long, value_name = "SECONDS")]
+        to: Option<f64>,
+
+        /// overwrite the matches of the regular expression in the payloads with '*', e.g. a VIN;
+        /// may be given several times
+        #[arg(long, value_name = "REGEX")]
+        redact: Vec<String>,
+
+        /// zip archive the bundle is written to
+        #[arg(short, long, value_name = "FILE")]
+        output: path::PathBuf,
+    },
+    /// reorder the messages by device time, the uptime within each boot, spilling to temporary
+    /// files so traces larger than the memory can be sorted
+    Sort {
+        /// input files
+        #[arg(value_name = "INPUT", required = true)]
+        input: Vec<path::PathBuf>,
+
+        /// trace file the sorted messages are written to
+        #[arg(short, long, value_name = "FILE")]
+        output: path::PathBuf,
+
+        /// memory for buffering messages before they are spilled, in MiB
+        #[arg(long, value_name = "MIB", default_value_t = 1024)]
+        max_memory: usize,
+
+        /// directory of the spill files, the system temp directory by default
+        #[arg(long, value_name = "DIR")]
+        temp_dir: Option<path::PathBuf>,
+    },
+    /// extract the files sent via DLT file transfer (FLST/FLDA/FLFI)
+    ExtractFiles {
+        /// input files
+        #[arg(value_name = "INPUT", required = true)]
+        input: Vec<path::PathBuf>,
+
+        /// directory the extracted files are written to
+        #[arg(short, long, value_name = "DIR", default_value = ".")]
+        output_dir: path::PathBuf,
+    },
+    /// run the patterns of a filter on the given payload and print the captures and the output row, for trying out a config
+    Eval {
+        /// name of the filter in the configuration file
+        #[arg(long, value_name = "NAME")]
+        filter: String,
+
+        /// payload text the patterns are run on
+        #[arg(long)]
+        payload: String,
+    },
+    /// encode a synthetic trace covering all argument types and header flags and check that it reads back unchanged
+    Selftest,
+    /// serve JSON-RPC requests on stdin/stdout, reporting progress and accepting pause/cancel, for front-ends embedding the parser
+    Serve,
+}
+
+#[derive(Subcommand,Debug)]
+pub enum ConfigCommand {
+    /// run the tests declared in the filters of the configuration file
+    Test,
 }
 
 impl Cli {
@@ -38,4 +340,106 @@ impl Cli {
         &self.input
     }
 
-}
\ No newline at end of file
+    pub fn manifest(&self) -> &Option<path::PathBuf> {
+        &self.manifest
+    }
+
+    pub fn buffer_overflow(&self) -> bool {
+        self.buffer_overflow
+    }
+
+    pub fn app_trace(&self) -> Option<AppTraceType> {
+        self.app_trace
+    }
+
+    pub fn level(&self) -> Option<LogLevel> {
+        self.level
+    }
+
+    pub fn time_from(&self) -> Option<Duration> {
+        self.time_from
+    }
+
+    pub fn time_to(&self) -> Option<Duration> {
+        self.time_to
+    }
+
+    pub fn uptime_from(&self) -> Option<Duration> {
+        self.uptime_from
+    }
+
+    pub fn uptime_to(&self) -> Option<Duration> {
+        self.uptime_to
+    }
+
+    /// Indices of the messages to process in each input file.
+    pub fn index_range(&self) -> RangeInclusive<usize> {
+        self.from_index.unwrap_or(0)..=self.to_index.unwrap_or(usize::MAX)
+    }
+
+    pub fn before(&self) -> Option<usize> {
+        self.before
+    }
+
+    pub fn after(&self) -> Option<usize> {
+        self.after
+    }
+
+    pub fn query(&self) -> &Option<String> {
+        &self.query
+    }
+
+    pub fn ecu_id(&self) -> &[String] {
+        &self.ecu_id
+    }
+
+    pub fn app_id(&self) -> &[String] {
+        &self.app_id
+    }
+
+    pub fn context_id(&self) -> &[String] {
+        &self.context_id
+    }
+
+    pub fn strict_ids(&self) -> bool {
+        self.strict_ids
+    }
+
+    pub fn line_buffered(&self) -> bool {
+        self.line_buffered
+    }
+
+    pub fn provenance(&self) -> bool {
+        self.provenance
+    }
+
+    pub fn format(&self) -> Option<InputFormat> {
+        self.format
+    }
+
+    pub fn invalid_utf8(&self) -> InvalidUtf8 {
+        self.invalid_utf8
+    }
+
+    pub fn output_root(&self) -> &Option<path::PathBuf> {
+        &self.output_root
+    }
+
+    pub fn dbc(&self) -> &Option<path::PathBuf> {
+        &self.dbc
+    }
+
+    pub fn warnings(&self) -> &Option<path::PathBuf> {
+        &self.warnings
+    }
+
+    pub fn chaos(&self) -> Option<Chaos> {
+        self.chaos
+    }
+
+}
+
+fn parse_uptime(input: &str) -> Result<Duration, String> {
+    input.parse().ok().and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+        .ok_or_else(|| format!("invalid uptime '{input}', expected seconds after boot"))
+}
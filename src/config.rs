@@ -1,27 +1,153 @@
+use std::collections::{BTreeMap, HashMap};
 use std::error::Error;
 use std::{fs, path};
 use std::path::Path;
-use serde_derive::Deserialize;
-use std::process;
+use std::time::Duration;
+use chrono::DateTime;
+use clap::ValueEnum;
+use serde_derive::{Deserialize, Serialize};
+use regex::Regex;
+use crate::dlt::fibex::Fibex;
+use crate::dlt::filter::Pattern;
 
 #[derive(Deserialize,Debug)]
 pub struct Config {
+    /// name of the team or project the config belongs to, its outputs are kept in a directory
+    /// of that name below the output root
+    project: Option<String>,
     filters: Option<Vec<Filter>>,
+    callstacks: Option<Vec<Callstack>>,
+    spans: Option<Vec<Span>>,
+    profiles: Option<Vec<Profile>>,
+    alerts: Option<Vec<Alert>>,
 }
 
 impl Config {
+    /// The ECU, app and context ids of the filters that do not follow the naming convention,
+    /// with the filter they belong to.
+    pub fn unconventional_ids(&self) -> Vec<String> {
+        let mut unconventional = vec![];
+        for filter in self.filters.iter().flatten() {
+            let ids = [
+                ("ecu_id", &filter.ecu_id), ("app_id", &filter.app_id), ("context_id", &filter.context_id),
+                ("exclude_ecu_id", &filter.exclude_ecu_id), ("exclude_app_id", &filter.exclude_app_id), ("exclude_context_id", &filter.exclude_context_id),
+            ];
+            for (name, ids) in ids {
+                for id in ids.iter().flat_map(Ids::to_vec).filter(|id| !is_conventional_id(id)) {
+                    unconventional.push(format!("{name} {id:?} of filter '{}'", filter.name));
+                }
+            }
+        }
+        unconventional
+    }
+
+    pub fn project(&self) -> &Option<String> {
+        &self.project
+    }
+
+    /// Keeps the files the outputs write within the directory of the project below `root`, for
+    /// servers shared by several teams. Relative paths are taken as relative to that directory,
+    /// paths leading outside of it are refused. Returns the directory of the project.
+    pub fn confine_outputs(&mut self, root: &Path) -> Result<path::PathBuf, String> {
+        let Some(project) = &self.project else {
+            return Err("config lacks the project its outputs belong to".to_string());
+        };
+        if project.is_empty() || !project.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+            return Err(format!("invalid project '{project}', expected letters, digits, - and _"));
+        }
+        let root = root.canonicalize().map_err(|err| format!("invalid output root {root:?}: {err}"))?;
+        let project_dir = root.join(project);
+        fs::create_dir_all(&project_dir).map_err(|err| format!("error on creating {project_dir:?}: {err}"))?;
+
+        for output in self.filters.iter_mut().flatten().filter_map(|filter| filter.output.as_mut()) {
+            let csv = output.csv.as_mut().map(|csv| &mut csv.file_path);
+            let arrow = output.arrow.as_mut().and_then(|arrow| arrow.file_path.as_mut());
+            let sqlite = output.sqlite.as_mut().map(|sqlite| &mut sqlite.file_path);
+            let parquet = output.parquet.as_mut().map(|parquet| &mut parquet.file_path);
+            let (dlt, fibex) = match output.dlt.as_mut() {
+                Some(dlt) => (Some(&mut dlt.file_path), dlt.non_verbose.as_mut().map(|non_verbose| &mut non_verbose.fibex_path)),
+                None => (None, None),
+            };
+            for file_path in [csv, arrow, sqlite, parquet, dlt, fibex].into_iter().flatten() {
+                *file_path = confine(&project_dir, file_path)?;
+            }
+        }
+        Ok(project_dir)
+    }
 
     pub fn filters(&self) -> &Option<Vec<Filter>> {
         &self.filters
     }
 
+    pub fn callstacks(&self) -> &Option<Vec<Callstack>> {
+        &self.callstacks
+    }
+
+    pub fn spans(&self) -> &Option<Vec<Span>> {
+        &self.spans
+    }
+
+    pub fn profiles(&self) -> &Option<Vec<Profile>> {
+        &self.profiles
+    }
+
+    pub fn alerts(&self) -> &Option<Vec<Alert>> {
+        &self.alerts
+    }
+
+    /// What writes to stdout: the arrow outputs without file_path, and apart from them the
+    /// stdout outputs, the filters without sinks and the callstack dumps.
+    fn stdout_writers(&self) -> (Vec<String>, Vec<String>) {
+        let mut arrow_writers = vec![];
+        let mut other_writers = vec![];
+        for filter in self.filters.iter().flatten() {
+            let name = &filter.name;
+            match &filter.output {
+                Some(output) if output.has_sinks() => {
+                    if output.arrow.as_ref().is_some_and(|arrow| arrow.file_path.is_none()) {
+                        arrow_writers.push(format!("arrow output of filter '{name}'"));
+                    }
+                    if output.stdout.as_ref().is_some_and(|stdout| stdout.enabled) {
+                        other_writers.push(format!("stdout output of filter '{name}'"));
+                    }
+                },
+                _ => other_writers.push(format!("filter '{name}' without outputs")),
+            }
+        }
+        if self.callstacks.as_ref().is_some_and(|callstacks| !callstacks.is_empty()) {
+            other_writers.push("callstacks".to_string());
+        }
+        (arrow_writers, other_writers)
+    }
+
     fn is_valid(&self) -> Result<(), &'static str> {
         let is_filter_valid = match &self.filters {
             Some(filters) => filters.iter().all(|filter| filter.is_valid()),
             None => true,
         };
+        let is_callstack_valid = match &self.callstacks {
+            Some(callstacks) => callstacks.iter().all(|callstack| callstack.is_valid()),
+            None => true,
+        };
+        let is_span_valid = match &self.spans {
+            Some(spans) => spans.iter().all(|span| span.is_valid()),
+            None => true,
+        };
+
+        let is_profile_valid = match &self.profiles {
+            Some(profiles) => profiles.iter().all(|profile| profile.is_valid()),
+            None => true,
+        };
+
+        // an arrow stream on stdout breaks if anything else is written there
+        let (arrow_writers, other_writers) = self.stdout_writers();
+        let is_stdout_shared = !arrow_writers.is_empty() && arrow_writers.len() + other_writers.len() > 1;
+        if is_stdout_shared {
+            let writers: Vec<String> = arrow_writers.into_iter().chain(other_writers).collect();
+            eprintln!("an arrow output without file_path takes stdout, nothing else can write to it: {}", writers.join(", "));
+        }
 
-        if is_filter_valid {
+        if is_filter_valid && is_callstack_valid && is_span_valid && is_profile_valid && !is_stdout_shared {
             Ok(())
         } else {
             Err("config file invalid")
@@ -29,14 +155,107 @@ impl Config {
     }
 }
 
+/// A filter as defined in `[[filters]]` of the config, for building the engine without a config
+/// file, see [`crate::build_route`].
+pub type FilterSpec = Filter;
+
 #[derive(Deserialize,Debug)]
 pub struct Filter {
     name: String,
-    ecu_id: Option<String>,
-    app_id: Option<String>,
-    context_id: Option<String>,
+    ecu_id: Option<Ids>,
+    app_id: Option<Ids>,
+    context_id: Option<Ids>,
+    /// which ecu id of the message ecu_id and exclude_ecu_id are compared with
+    #[serde(default)]
+    ecu_source: EcuSource,
+    /// drop the messages of these ids, even if they are included above
+    exclude_ecu_id: Option<Ids>,
+    exclude_app_id: Option<Ids>,
+    exclude_context_id: Option<Ids>,
     patterns: Option<Vec<String>>,
+    /// drop the messages whose payload matches any of these patterns
+    exclude_patterns: Option<Vec<String>>,
+    /// only keep the messages of each context from one matching start_pattern to the next one
+    /// matching stop_pattern, both included
+    start_pattern: Option<String>,
+    stop_pattern: Option<String>,
+    /// match the patterns, exclude_patterns and start/stop patterns regardless of case
+    #[serde(default)]
+    ignore_case: bool,
+    /// only keep the messages without payload if true, drop them if false
+    header_only: Option<bool>,
+    /// only keep non-verbose messages with any of these message ids
+    message_ids: Option<Vec<u32>>,
+    #[serde(default)]
+    buffer_overflow: bool,
+    app_trace: Option<AppTraceType>,
+    /// least severe log level of the log messages to keep
+    level: Option<LogLevel>,
+    /// only keep messages stored at or after this time
+    time_from: Option<WallClock>,
+    /// only keep messages stored at or before this time
+    time_to: Option<WallClock>,
+    /// only keep messages logged at or after this many seconds after boot of the ECU
+    uptime_from: Option<f64>,
+    /// only keep messages logged at or before this many seconds after boot of the ECU
+    uptime_to: Option<f64>,
+    /// thin out the messages of each context to one per interval, e.g. "500ms", keeping the
+    /// first and the last
+    keep_one_per: Option<String>,
+    /// only keep the first matching message of each lifecycle, e.g. the software version
+    first_per: Option<FirstPer>,
+    /// drop messages repeating the payload of the message before in their context, counting them
+    #[serde(default)]
+    dedup: bool,
+    /// also keep this many messages before and after each match, like `grep -C`
+    before: Option<usize>,
+    after: Option<usize>,
+    non_verbose: Option<NonVerboseMatch>,
+    /// names of arguments sent with variable info (VARI) whose values are extracted, output as
+    /// `<name>` like the captures of the patterns
+    variables: Option<Vec<String>>,
+    wasm: Option<WasmMatch>,
     output: Option<Output>,
+    tests: Option<Vec<FilterTest>>,
+}
+
+/// Span a filter only keeps the first matching message of.
+#[derive(Deserialize,Debug,Clone,Copy,PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum FirstPer {
+    /// boot of the ECU, see `Lifecycles`
+    Lifecycle,
+}
+
+/// ECU id of a message the filters compare with. Multiplexed traces often store all messages
+/// under the ECU id of the logger, while the standard header of each message has the actual one.
+#[derive(Deserialize,Debug,Clone,Copy,Default)]
+#[serde(rename_all = "lowercase")]
+pub enum EcuSource {
+    /// the ECU id of the storage header
+    #[default]
+    Storage,
+    /// the ECU id of the standard header, messages without one do not match
+    Header,
+    /// either of them
+    Either,
+}
+
+/// A single id or a list of ids, a message matches if it has any of them.
+#[derive(Deserialize,Debug,Clone)]
+#[serde(untagged)]
+pub enum Ids {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Ids {
+    pub fn to_vec(&self) -> Vec<String> {
+        match self {
+            Ids::One(id) => vec![id.clone()],
+            Ids::Many(ids) => ids.clone(),
+        }
+    }
 }
 
 fn validate_id(name: &str, id: &Option<String>) -> bool {
@@ -50,60 +269,606 @@ fn validate_id(name: &str, id: &Option<String>) -> bool {
     }
 }
 
+/// Whether the id follows the naming convention of DLT: up to 4 uppercase letters or digits,
+/// shorter ids are padded with nul bytes on the wire. The wildcards `*` and `?` are allowed.
+pub fn is_conventional_id(id: &str) -> bool {
+    let fixed = id.replace('*', "");
+    (1..=4).contains(&fixed.len()) && fixed.bytes().all(|byte| byte.is_ascii_uppercase() || byte.is_ascii_digit() || byte == b'?')
+}
+
+/// Validates ids that may contain the wildcards `*` and `?`, the other characters have to fit
+/// an id.
+fn validate_ids(name: &str, ids: &Option<Ids>) -> bool {
+    match ids.as_ref().map(Ids::to_vec) {
+        Some(ids) if ids.is_empty() => {
+            println!("{name} is an empty list");
+            false
+        },
+        Some(ids) => ids.into_iter().all(|id| match id.replace('*', "") {
+            fixed if fixed.is_ascii() && fixed.len() <= 4 => true,
+            _ => {
+                println!("{name} non-ascii or too long (4 char max besides '*'): {id}");
+                false
+            },
+        }),
+        None => true,
+    }
+}
+
 impl Filter {
+    /// Reads a filter from JSON with the keys of a `[[filters]]` entry of the config. Output
+    /// presets are not resolved, they are defined by the config.
+    pub fn from_json(json: &str) -> Result<Filter, Box<dyn Error>> {
+        let filter: Filter = serde_json::from_str(json)?;
+        if !filter.is_valid() {
+            return Err(format!("filter {} invalid", filter.name).into());
+        }
+        Ok(filter)
+    }
+
 
     pub fn name(&self) -> &String {
         &self.name
     }
 
-    pub fn ecu_id(&self) -> &Option<String> {
+    pub fn ecu_id(&self) -> &Option<Ids> {
         &self.ecu_id
     }
 
-    pub fn app_id(&self) -> &Option<String> {
+    pub fn app_id(&self) -> &Option<Ids> {
         &self.app_id
     }
 
-    pub fn context_id(&self) -> &Option<String> {
+    pub fn context_id(&self) -> &Option<Ids> {
         &self.context_id
     }
 
+    pub fn ecu_source(&self) -> EcuSource {
+        self.ecu_source
+    }
+
+    pub fn exclude_ecu_id(&self) -> &Option<Ids> {
+        &self.exclude_ecu_id
+    }
+
+    pub fn exclude_app_id(&self) -> &Option<Ids> {
+        &self.exclude_app_id
+    }
+
+    pub fn exclude_context_id(&self) -> &Option<Ids> {
+        &self.exclude_context_id
+    }
+
     pub fn patterns(&self) -> &Option<Vec<String>> {
         &self.patterns
     }
 
+    pub fn exclude_patterns(&self) -> &Option<Vec<String>> {
+        &self.exclude_patterns
+    }
+
+    pub fn start_pattern(&self) -> &Option<String> {
+        &self.start_pattern
+    }
+
+    pub fn stop_pattern(&self) -> &Option<String> {
+        &self.stop_pattern
+    }
+
+    pub fn ignore_case(&self) -> bool {
+        self.ignore_case
+    }
+
+    pub fn header_only(&self) -> Option<bool> {
+        self.header_only
+    }
+
+    pub fn message_ids(&self) -> &Option<Vec<u32>> {
+        &self.message_ids
+    }
+
+    /// Only match the buffer overflow notifications of the DLT daemon.
+    pub fn buffer_overflow(&self) -> bool {
+        self.buffer_overflow
+    }
+
+    pub fn app_trace(&self) -> Option<AppTraceType> {
+        self.app_trace
+    }
+
+    pub fn level(&self) -> Option<LogLevel> {
+        self.level
+    }
+
+    pub fn time_from(&self) -> &Option<WallClock> {
+        &self.time_from
+    }
+
+    pub fn time_to(&self) -> &Option<WallClock> {
+        &self.time_to
+    }
+
+    pub fn uptime_from(&self) -> Option<Duration> {
+        self.uptime_from.and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+    }
+
+    pub fn uptime_to(&self) -> Option<Duration> {
+        self.uptime_to.and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+    }
+
+    pub fn first_per(&self) -> Option<FirstPer> {
+        self.first_per
+    }
+
+    pub fn dedup(&self) -> bool {
+        self.dedup
+    }
+
+    pub fn before(&self) -> Option<usize> {
+        self.before
+    }
+
+    pub fn after(&self) -> Option<usize> {
+        self.after
+    }
+
+    pub fn keep_one_per(&self) -> Option<Duration> {
+        self.keep_one_per.as_deref().and_then(|interval| parse_interval(interval).ok())
+    }
+
+    pub fn non_verbose(&self) -> &Option<NonVerboseMatch> {
+        &self.non_verbose
+    }
+
+    pub fn variables(&self) -> &Option<Vec<String>> {
+        &self.variables
+    }
+
+    pub fn wasm(&self) -> &Option<WasmMatch> {
+        &self.wasm
+    }
+
     pub fn output(&self) -> &Option<Output> {
         &self.output
     }
 
+    pub fn tests(&self) -> &Option<Vec<FilterTest>> {
+        &self.tests
+    }
+
     fn is_valid(&self) -> bool {
-        let is_ecu_id_valid = validate_id("ecu_id", &self.ecu_id);
-        let is_app_id_valid = validate_id("app_id", &self.app_id);
-        let is_context_id_valid = validate_id("context_id", &self.context_id);
-        // TODO: validate patterns!
+        let is_ecu_id_valid = validate_ids("ecu_id", &self.ecu_id);
+        let is_app_id_valid = validate_ids("app_id", &self.app_id);
+        let is_context_id_valid = validate_ids("context_id", &self.context_id);
+        let is_exclude_valid = validate_ids("exclude_ecu_id", &self.exclude_ecu_id)
+            & validate_ids("exclude_app_id", &self.exclude_app_id)
+            & validate_ids("exclude_context_id", &self.exclude_context_id);
+        let is_time_valid = [("time_from", &self.time_from), ("time_to", &self.time_to)].into_iter()
+            .all(|(name, time)| match time.as_ref().map(WallClock::since_epoch) {
+                Some(Err(err)) => {
+                    eprintln!("{name}: {err}");
+                    false
+                },
+                _ => true,
+            });
+        let is_uptime_valid = [("uptime_from", self.uptime_from), ("uptime_to", self.uptime_to)].into_iter()
+            .all(|(name, uptime)| match uptime {
+                Some(seconds) if Duration::try_from_secs_f64(seconds).is_err() => {
+                    eprintln!("{name}: invalid uptime {seconds}, expected seconds after boot");
+                    false
+                },
+                _ => true,
+            });
+        let is_message_ids_valid = match &self.message_ids {
+            Some(message_ids) if message_ids.is_empty() => {
+                eprintln!("message_ids: empty list, expected at least one message id");
+                false
+            },
+            _ => true,
+        };
+        let is_keep_one_per_valid = match self.keep_one_per.as_deref().map(parse_interval) {
+            Some(Err(err)) => {
+                eprintln!("keep_one_per: {err}");
+                false
+            },
+            _ => true,
+        };
+        let is_section_valid = match (&self.start_pattern, &self.stop_pattern) {
+            (Some(start), Some(stop)) => [("start_pattern", start), ("stop_pattern", stop)].into_iter()
+                .all(|(name, pattern)| match Regex::new(pattern) {
+                    Ok(_) => true,
+                    Err(err) => {
+                        eprintln!("{name}: {err}");
+                        false
+                    },
+                }),
+            (None, None) => true,
+            _ => {
+                eprintln!("start_pattern and stop_pattern have to be given together");
+                false
+            },
+        };
+        let is_patterns_valid = [("patterns", &self.patterns), ("exclude_patterns", &self.exclude_patterns)].into_iter()
+            .filter_map(|(name, patterns)| patterns.as_ref().map(|patterns| (name, patterns)))
+            .all(|(name, patterns)| patterns.iter().all(|pattern| match Regex::new(pattern) {
+                Ok(_) => true,
+                Err(err) => {
+                    eprintln!("{name}: {err}");
+                    false
+                },
+            }));
         let is_output_valid = match &self.output {
             Some(out) => out.is_valid(),
             None => true,
         };
-        is_ecu_id_valid && is_app_id_valid && is_context_id_valid && is_output_valid
+        is_ecu_id_valid && is_app_id_valid && is_context_id_valid && is_exclude_valid && is_time_valid && is_uptime_valid
+            && is_message_ids_valid && is_keep_one_per_valid && is_section_valid && is_patterns_valid && is_output_valid
+    }
+}
+
+/// Storage time given as seconds since the epoch or as RFC 3339 time, e.g.
+/// `2024-05-01T12:00:00Z`.
+#[derive(Deserialize,Debug,Clone)]
+#[serde(untagged)]
+pub enum WallClock {
+    Seconds(f64),
+    Text(String),
+}
+
+impl WallClock {
+    pub fn parse(input: &str) -> Result<Duration, String> {
+        match input.parse() {
+            Ok(seconds) => WallClock::Seconds(seconds).since_epoch(),
+            Err(_) => WallClock::Text(input.to_string()).since_epoch(),
+        }
+    }
+
+    pub fn since_epoch(&self) -> Result<Duration, String> {
+        match self {
+            WallClock::Seconds(seconds) => Duration::try_from_secs_f64(*seconds)
+                .map_err(|_| format!("invalid time {seconds}, expected seconds since the epoch")),
+            WallClock::Text(text) => {
+                let datetime = DateTime::parse_from_rfc3339(text).map_err(|err| format!("invalid time '{text}': {err}"))?;
+                let nanoseconds = datetime.timestamp_nanos_opt().and_then(|nanoseconds| u64::try_from(nanoseconds).ok())
+                    .ok_or_else(|| format!("time '{text}' is before the epoch"))?;
+                Ok(Duration::from_nanos(nanoseconds))
+            },
+        }
+    }
+}
+
+/// Parses an interval given as a number with the unit `us`, `ms`, `s`, `min` or `h`, e.g. "500ms".
+pub fn parse_interval(input: &str) -> Result<Duration, String> {
+    let unit_start = input.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(input.len());
+    let (value, unit) = input.split_at(unit_start);
+    let value: f64 = value.trim().parse().map_err(|_| format!("invalid interval '{input}', expected e.g. 500ms"))?;
+    let seconds = match unit {
+        "us" => value / 1_000_000.0,
+        "ms" => value / 1000.0,
+        "s" => value,
+        "min" => value * 60.0,
+        "h" => value * 3600.0,
+        _ => return Err(format!("invalid unit of interval '{input}', expected us, ms, s, min or h")),
+    };
+    match Duration::try_from_secs_f64(seconds) {
+        Ok(interval) if !interval.is_zero() => Ok(interval),
+        _ => Err(format!("invalid interval '{input}', expected a positive duration")),
+    }
+}
+
+/// Non-verbose messages a filter extracts besides the captures of its patterns. Their arguments
+/// are decoded with the FIBEX file and named after its PDUs, so they fill the same output columns
+/// as captures of the same name.
+#[derive(Deserialize,Debug)]
+pub struct NonVerboseMatch {
+    fibex_path: path::PathBuf,
+    message_ids: Vec<u32>,
+    /// the FIBEX file as parsed when the config was compiled
+    #[serde(skip)]
+    fibex: Option<Fibex>,
+}
+
+impl NonVerboseMatch {
+    pub fn fibex_path(&self) -> &path::PathBuf {
+        &self.fibex_path
+    }
+
+    /// Descriptions of the FIBEX file, taken from the compiled config if it is one.
+    pub fn fibex(&self) -> Result<Fibex, String> {
+        match &self.fibex {
+            Some(fibex) => Ok(fibex.clone()),
+            None => Fibex::read(&self.fibex_path),
+        }
+    }
+
+    pub fn message_ids(&self) -> &Vec<u32> {
+        &self.message_ids
+    }
+}
+
+/// WebAssembly module with custom filter logic, see `WasmModule` for what it exports. The names
+/// of the values its transform returns are listed as `fields`, so they can be output as captures.
+#[derive(Deserialize,Debug)]
+pub struct WasmMatch {
+    module_path: path::PathBuf,
+    #[serde(default)]
+    fields: Vec<String>,
+}
+
+impl WasmMatch {
+    pub fn module_path(&self) -> &path::PathBuf {
+        &self.module_path
+    }
+
+    pub fn fields(&self) -> &Vec<String> {
+        &self.fields
+    }
+}
+
+/// Crash reporter context whose hex callstack dumps are gathered into one readable block.
+#[derive(Deserialize,Debug,Clone)]
+pub struct Callstack {
+    app_id: Option<String>,
+    context_id: Option<String>,
+}
+
+impl Callstack {
+    pub fn app_id(&self) -> &Option<String> {
+        &self.app_id
+    }
+
+    pub fn context_id(&self) -> &Option<String> {
+        &self.context_id
+    }
+
+    fn is_valid(&self) -> bool {
+        let is_app_id_valid = validate_id("app_id", &self.app_id);
+        let is_context_id_valid = validate_id("context_id", &self.context_id);
+        is_app_id_valid && is_context_id_valid
+    }
+}
+
+/// Condition the alert mode reports, as expression like the ones of `--where`. Further matches
+/// within `cooldown` seconds of storage time after a report are only counted, so a flood of
+/// errors does not flood the output.
+#[derive(Deserialize,Debug,Clone)]
+pub struct Alert {
+    name: String,
+    condition: String,
+    #[serde(default)]
+    cooldown: f64,
+}
+
+impl Alert {
+    pub fn name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn condition(&self) -> &String {
+        &self.condition
+    }
+
+    pub fn cooldown(&self) -> f64 {
+        self.cooldown
+    }
+}
+
+/// Span of the timeline export, starting with a message matching `start_pattern` and ending
+/// with the next message of the same app/context matching `stop_pattern`.
+#[derive(Deserialize,Debug,Clone)]
+pub struct Span {
+    name: String,
+    app_id: Option<String>,
+    context_id: Option<String>,
+    start_pattern: String,
+    stop_pattern: String,
+}
+
+impl Span {
+    pub fn name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn app_id(&self) -> &Option<String> {
+        &self.app_id
+    }
+
+    pub fn context_id(&self) -> &Option<String> {
+        &self.context_id
+    }
+
+    pub fn start_pattern(&self) -> &String {
+        &self.start_pattern
+    }
+
+    pub fn stop_pattern(&self) -> &String {
+        &self.stop_pattern
+    }
+
+    fn is_valid(&self) -> bool {
+        let is_app_id_valid = validate_id("app_id", &self.app_id);
+        let is_context_id_valid = validate_id("context_id", &self.context_id);
+        is_app_id_valid && is_context_id_valid
+    }
+}
+
+/// Deviations from the DLT standard of the messages of one ECU, identified by the ECU id of the
+/// storage header.
+#[derive(Deserialize,Debug,Clone)]
+pub struct Profile {
+    ecu_id: String,
+    /// coding of all string arguments, regardless of their type info
+    string_coding: Option<StringCoding>,
+    /// seconds per timestamp tick, instead of 0.1 ms
+    timestamp_resolution: Option<f64>,
+    /// byte order of the payload, regardless of the standard header
+    byte_order: Option<ByteOrder>,
+    /// unit of the subsecond part of the storage time, in-house loggers write nanoseconds in
+    /// place of the microseconds of the standard
+    storage_subseconds: Option<Subseconds>,
+}
+
+impl Profile {
+    pub fn ecu_id(&self) -> &String {
+        &self.ecu_id
+    }
+
+    pub fn string_coding(&self) -> Option<StringCoding> {
+        self.string_coding
+    }
+
+    pub fn timestamp_resolution(&self) -> Option<f64> {
+        self.timestamp_resolution
+    }
+
+    pub fn byte_order(&self) -> Option<ByteOrder> {
+        self.byte_order
+    }
+
+    pub fn storage_subseconds(&self) -> Option<Subseconds> {
+        self.storage_subseconds
+    }
+
+    fn is_valid(&self) -> bool {
+        let is_ecu_id_valid = validate_id("ecu_id", &Some(self.ecu_id.clone()));
+        let is_resolution_valid = match self.timestamp_resolution {
+            Some(resolution) if resolution <= 0.0 => {
+                eprintln!("timestamp_resolution of profile {} must be positive: {resolution}", self.ecu_id);
+                false
+            },
+            _ => true,
+        };
+        is_ecu_id_valid && is_resolution_valid
+    }
+}
+
+#[derive(Deserialize,Debug,Clone,Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum StringCoding {
+    Utf16le,
+    Utf16be,
+}
+
+#[derive(Deserialize,Debug,Clone,Copy,PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ByteOrder {
+    Big,
+    Little,
+}
+
+#[derive(Deserialize,Debug,Clone,Copy,PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Subseconds {
+    Microseconds,
+    Nanoseconds,
+}
+
+#[derive(Deserialize,Debug)]
+pub struct FilterTest {
+    payload: String,
+    #[serde(default = "FilterTest::default_matches")]
+    matches: bool,
+    captures: Option<BTreeMap<String, String>>,
+}
+
+impl FilterTest {
+    fn default_matches() -> bool {
+        true
+    }
+
+    pub fn payload(&self) -> &String {
+        &self.payload
+    }
+
+    /// Runs the example payload against the given patterns and checks that the match result and
+    /// the named captures are as expected.
+    pub fn run(&self, pattern: &Pattern) -> Result<(), String> {
+        let captures = pattern.captures(&self.payload);
+        match (&captures, self.matches) {
+            (None, true) => return Err(format!("payload '{}' did not match", self.payload)),
+            (Some(_), false) => return Err(format!("payload '{}' matched unexpectedly", self.payload)),
+            _ => (),
+        }
+
+        let captures = captures.unwrap_or_default();
+        for (name, expected) in self.captures.iter().flatten() {
+            let actual = captures.iter().find_map(|capture| capture.name(name)).map(|value| value.as_str());
+            match actual {
+                Some(actual) if actual == expected => (),
+                Some(actual) => return Err(format!("capture '{name}': expected '{expected}', got '{actual}'")),
+                None => return Err(format!("capture '{name}': expected '{expected}', got nothing")),
+            }
+        }
+        Ok(())
     }
 }
 
+/// Sinks the matching messages of a filter are written to, each one given is written, e.g. brief
+/// lines on stdout while all fields go to a CSV file. The format of a table sink may be a
+/// template too, its placeholders become the columns.
 #[derive(Deserialize,Debug)]
 pub struct Output {
     csv: Option<Csv>,
     stdout: Option<Stdout>,
+    dlt: Option<Dlt>,
+    arrow: Option<Arrow>,
+    sqlite: Option<Sqlite>,
+    parquet: Option<Parquet>,
+    /// mark where messages were lost, according to the message counters, in stdout and csv
+    /// output
+    #[serde(default)]
+    lost_markers: bool,
+    /// CSV file of events, e.g. CAN triggers, the `ref_event` and `ref_delta` fields refer to
+    reference_events: Option<path::PathBuf>,
+    /// written for values a message lacks, e.g. the app id of a message without extended
+    /// header, "none" if not given
+    missing: Option<String>,
+    /// literal values written in every row, e.g. `test_id = "TC-1234"`, in front unless the
+    /// format names them
+    columns: Option<BTreeMap<String, String>>,
 }
 
 impl Output {
+    pub fn reference_events(&self) -> &Option<path::PathBuf> {
+        &self.reference_events
+    }
+
+    pub fn lost_markers(&self) -> bool {
+        self.lost_markers
+    }
+
+    pub fn missing(&self) -> &Option<String> {
+        &self.missing
+    }
+
+    pub fn columns(&self) -> &Option<BTreeMap<String, String>> {
+        &self.columns
+    }
+
     pub fn csv(&self) -> &Option<Csv> {
         &self.csv
     }
 
+    pub fn dlt(&self) -> &Option<Dlt> {
+        &self.dlt
+    }
+
     pub fn stdout(&self) -> &Option<Stdout> {
         &self.stdout
     }
 
+    pub fn arrow(&self) -> &Option<Arrow> {
+        &self.arrow
+    }
+
+    pub fn sqlite(&self) -> &Option<Sqlite> {
+        &self.sqlite
+    }
+
+    pub fn parquet(&self) -> &Option<Parquet> {
+        &self.parquet
+    }
+
     fn is_valid(&self) -> bool {
         let is_csv_valid = match &self.csv {
             Some(csv) => csv.is_valid(),
@@ -113,16 +878,100 @@ impl Output {
             Some(stdout) => stdout.is_valid(),
             None => true,
         };
-        is_csv_valid && is_stdout_valid
+        let is_dlt_valid = match &self.dlt {
+            Some(dlt) => dlt.is_valid(),
+            None => true,
+        };
+        is_csv_valid && is_stdout_valid && is_dlt_valid
+    }
+
+    /// Whether any sink is given, a filter without one writes its matches to stdout.
+    fn has_sinks(&self) -> bool {
+        self.stdout.as_ref().is_some_and(|stdout| stdout.enabled)
+            || self.csv.is_some() || self.dlt.is_some() || self.arrow.is_some() || self.sqlite.is_some() || self.parquet.is_some()
+    }
+}
+
+/// Type info of app-trace messages.
+#[derive(Deserialize,ValueEnum,Debug,Clone,Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum AppTraceType {
+    Variable,
+    FunctionIn,
+    FunctionOut,
+    State,
+    Vfb,
+}
+
+/// Log level of log messages, from the most to the least severe.
+#[derive(Deserialize,ValueEnum,Debug,Clone,Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Fatal,
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Verbose,
+}
+
+#[derive(Deserialize,Debug,Clone,Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum ShardKey {
+    Ecu,
+    App,
+    Ctx,
+}
+
+impl ShardKey {
+    /// Placeholder in the output path template that is replaced by the shard value.
+    pub fn placeholder(&self) -> &'static str {
+        match self {
+            ShardKey::Ecu => "{ecu}",
+            ShardKey::App => "{app}",
+            ShardKey::Ctx => "{ctx}",
+        }
+    }
+}
+
+/// Line ending of the rows written to csv files.
+#[derive(Deserialize,Debug,Clone,Copy,Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    /// as expected by some Windows tools, e.g. older Excel versions
+    Crlf,
+}
+
+impl LineEnding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::Crlf => "\r\n",
+        }
     }
 }
 
 #[derive(Deserialize,Debug)]
 pub struct Csv {
+    /// path of the file, on Windows with drive letter or as UNC path; use a literal string
+    /// ('C:\logs\out.csv') in the config to avoid escaping the backslashes
     file_path: path::PathBuf,
     #[serde(default = "Csv::default_delimiter")]
     delimiter: char,
+    /// fields or template, like the format of stdout
     format: Option<String>,
+    shard_by: Option<ShardKey>,
+    #[serde(default)]
+    line_ending: LineEnding,
+    /// split the output into files of this many rows, numbered `out.00000.csv`, ..., and list
+    /// them in `out.manifest.json`
+    chunk_rows: Option<usize>,
+    /// write a header row naming the columns, with the unit of arguments whose unit is known,
+    /// e.g. `speed [km/h]`, on by default
+    #[serde(default = "Csv::default_header")]
+    header: bool,
 }
 
 impl Csv {
@@ -130,9 +979,47 @@ impl Csv {
         ','
     }
 
+    fn default_header() -> bool {
+        true
+    }
+
+    pub fn file_path(&self) -> &path::PathBuf {
+        &self.file_path
+    }
+
+    pub fn delimiter(&self) -> char {
+        self.delimiter
+    }
+
+    pub fn format_string(&self) -> &Option<String> {
+        &self.format
+    }
+
+    pub fn shard_by(&self) -> Option<ShardKey> {
+        self.shard_by
+    }
+
+    pub fn line_ending(&self) -> LineEnding {
+        self.line_ending
+    }
+
+    pub fn chunk_rows(&self) -> Option<usize> {
+        self.chunk_rows
+    }
+
+    pub fn header(&self) -> bool {
+        self.header
+    }
+
     fn is_valid(&self) -> bool {
         // TODO: improve filename validation
-        let is_file_path_valid = true;
+        let is_file_path_valid = match self.shard_by {
+            Some(shard_by) if !self.file_path.to_string_lossy().contains(shard_by.placeholder()) => {
+                eprintln!("csv file_path {:?} is sharded but lacks the placeholder {}", self.file_path, shard_by.placeholder());
+                false
+            },
+            _ => true,
+        };
         let is_delimiter_valid = match &self.delimiter {
             ',' => true,
             ';' => true,
@@ -145,8 +1032,190 @@ impl Csv {
                 false
             },
         };
+        let is_chunk_rows_valid = match self.chunk_rows {
+            Some(0) => {
+                eprintln!("csv chunk_rows must be greater than 0");
+                false
+            },
+            _ => true,
+        };
         // TODO: check output format, or rather which fields should be output
-        is_file_path_valid && is_delimiter_valid
+        is_file_path_valid && is_delimiter_valid && is_chunk_rows_valid
+    }
+}
+
+/// Rows of the matching messages as Arrow IPC stream, e.g. for reading them with Polars or
+/// DuckDB. The fields of the format are separated by commas and become the columns, all of them
+/// strings.
+#[derive(Deserialize,Debug)]
+pub struct Arrow {
+    /// written to stdout if not given
+    file_path: Option<path::PathBuf>,
+    format: Option<String>,
+}
+
+impl Arrow {
+    pub fn file_path(&self) -> &Option<path::PathBuf> {
+        &self.file_path
+    }
+
+    pub fn format_string(&self) -> &Option<String> {
+        &self.format
+    }
+}
+
+/// Rows of the matching messages as table of a SQLite database, e.g. for querying a trace of
+/// several GB with SQL without parsing it again. The fields of the format are separated by commas
+/// and become the columns, by default the header fields, the payload and the captures.
+#[derive(Deserialize,Debug)]
+pub struct Sqlite {
+    file_path: path::PathBuf,
+    /// name of the filter if not given, a table of the name is replaced
+    table: Option<String>,
+    format: Option<String>,
+}
+
+impl Sqlite {
+    pub fn file_path(&self) -> &path::PathBuf {
+        &self.file_path
+    }
+
+    pub fn table(&self) -> &Option<String> {
+        &self.table
+    }
+
+    pub fn format_string(&self) -> &Option<String> {
+        &self.format
+    }
+}
+
+/// Rows of the matching messages as Parquet file, e.g. for loading them into pandas, Polars or
+/// Spark. The fields of the format are separated by commas and become typed columns, by default
+/// the header fields, the payload and the captures.
+#[derive(Deserialize,Debug)]
+pub struct Parquet {
+    file_path: path::PathBuf,
+    format: Option<String>,
+}
+
+impl Parquet {
+    pub fn file_path(&self) -> &path::PathBuf {
+        &self.file_path
+    }
+
+    pub fn format_string(&self) -> &Option<String> {
+        &self.format
+    }
+}
+
+/// Export of the matching messages as DLT, copied unchanged from the input.
+#[derive(Deserialize,Debug)]
+pub struct Dlt {
+    file_path: path::PathBuf,
+    annotation: Option<Annotation>,
+    non_verbose: Option<NonVerbose>,
+    /// hold back this many messages to write them in storage time order
+    #[serde(default)]
+    reorder_window: usize,
+}
+
+impl Dlt {
+    pub fn file_path(&self) -> &path::PathBuf {
+        &self.file_path
+    }
+
+    pub fn non_verbose(&self) -> &Option<NonVerbose> {
+        &self.non_verbose
+    }
+
+    pub fn annotation(&self) -> &Option<Annotation> {
+        &self.annotation
+    }
+
+    pub fn reorder_window(&self) -> usize {
+        self.reorder_window
+    }
+
+    fn is_valid(&self) -> bool {
+        match &self.annotation {
+            Some(annotation) => annotation.is_valid(),
+            None => true,
+        }
+    }
+}
+
+/// Re-encoding of the captures of the matching messages as compact non-verbose DLT messages,
+/// described by a generated FIBEX file.
+#[derive(Deserialize,Debug,Clone)]
+pub struct NonVerbose {
+    #[serde(default = "NonVerbose::default_message_id")]
+    message_id: u32,
+    fibex_path: path::PathBuf,
+}
+
+impl NonVerbose {
+    fn default_message_id() -> u32 {
+        1
+    }
+
+    pub fn message_id(&self) -> u32 {
+        self.message_id
+    }
+
+    pub fn fibex_path(&self) -> &path::PathBuf {
+        &self.fibex_path
+    }
+}
+
+/// Marker log message injected into the DLT export, after each match if `on_match` is set and
+/// before the first exported message at or after each of the storage times `at`, given in
+/// seconds since the epoch.
+#[derive(Deserialize,Debug,Clone)]
+pub struct Annotation {
+    #[serde(default = "Annotation::default_app_id")]
+    app_id: String,
+    #[serde(default = "Annotation::default_context_id")]
+    context_id: String,
+    payload: String,
+    #[serde(default)]
+    on_match: bool,
+    #[serde(default)]
+    at: Vec<f64>,
+}
+
+impl Annotation {
+    fn default_app_id() -> String {
+        "KRKN".to_string()
+    }
+
+    fn default_context_id() -> String {
+        "ANNO".to_string()
+    }
+
+    pub fn app_id(&self) -> &String {
+        &self.app_id
+    }
+
+    pub fn context_id(&self) -> &String {
+        &self.context_id
+    }
+
+    pub fn payload(&self) -> &String {
+        &self.payload
+    }
+
+    pub fn on_match(&self) -> bool {
+        self.on_match
+    }
+
+    pub fn at(&self) -> &Vec<f64> {
+        &self.at
+    }
+
+    fn is_valid(&self) -> bool {
+        let is_app_id_valid = validate_id("app_id", &Some(self.app_id.clone()));
+        let is_context_id_valid = validate_id("context_id", &Some(self.context_id.clone()));
+        is_app_id_valid && is_context_id_valid
     }
 }
 
@@ -155,7 +1224,12 @@ pub struct Stdout {
     #[serde(default = "Stdout::default_enabled")]
     enabled: bool,
     delimiter: char,
+    /// fields separated by the delimiter, e.g. `time,app,payload`, or a template with named
+    /// placeholders, e.g. `{time} [{app}.{ctx}] {level}: {payload}`
     format: String,
+    /// written before and after each line, e.g. a tag to tell the outputs of filters apart
+    prefix: Option<String>,
+    suffix: Option<String>,
 }
 
 impl Stdout {
@@ -175,6 +1249,14 @@ impl Stdout {
         &self.format
     }
 
+    pub fn prefix(&self) -> &Option<String> {
+        &self.prefix
+    }
+
+    pub fn suffix(&self) -> &Option<String> {
+        &self.suffix
+    }
+
     fn is_valid(&self) -> bool {
         if self.enabled {
             // TODO: check output format
@@ -185,12 +1267,149 @@ impl Stdout {
     }
 }
 
+/// Resolves the named output presets of `[presets.<name>]`, which filters reference with
+/// `output.preset = "<name>"`. Settings of the filter's output take precedence over the preset.
+fn apply_presets(config: &mut toml::Value) -> Result<(), String> {
+    let Some(table) = config.as_table_mut() else {
+        return Ok(());
+    };
+    let presets = table.remove("presets");
+    let filters = table.get_mut("filters").and_then(toml::Value::as_array_mut);
+    for filter in filters.into_iter().flatten() {
+        let Some(output) = filter.get_mut("output").and_then(toml::Value::as_table_mut) else {
+            continue;
+        };
+        let Some(name) = output.remove("preset") else {
+            continue;
+        };
+        let preset = name.as_str()
+            .and_then(|name| presets.as_ref()?.get(name))
+            .ok_or_else(|| format!("unknown output preset: {name}"))?;
+        let mut merged = preset.clone();
+        merge_toml(&mut merged, toml::Value::Table(std::mem::take(output)));
+        *output = match merged {
+            toml::Value::Table(merged) => merged,
+            _ => return Err(format!("output preset {name} is not a table")),
+        };
+    }
+    Ok(())
+}
+
+/// Merges the tables recursively, values of `overrides` replace the ones of `base`.
+fn merge_toml(base: &mut toml::Value, overrides: toml::Value) {
+    match (base, overrides) {
+        (toml::Value::Table(base), toml::Value::Table(overrides)) => {
+            for (key, value) in overrides {
+                match base.get_mut(&key) {
+                    Some(base_value) => merge_toml(base_value, value),
+                    None => {
+                        base.insert(key, value);
+                    },
+                }
+            }
+        },
+        (base, overrides) => *base = overrides,
+    }
+}
+
+/// Resolves the path relative to `dir`, failing if it leads outside of it, also by way of a
+/// symbolic link to an existing directory.
+fn confine(dir: &Path, file_path: &Path) -> Result<path::PathBuf, String> {
+    let mut confined = dir.to_path_buf();
+    for component in file_path.components() {
+        match component {
+            path::Component::ParentDir => {
+                confined.pop();
+            },
+            path::Component::CurDir => {},
+            path::Component::Prefix(prefix) => confined = path::PathBuf::from(prefix.as_os_str()),
+            // an absolute path replaces the directory
+            path::Component::RootDir | path::Component::Normal(_) => confined.push(component),
+        }
+    }
+    let parent = confined.parent().and_then(|parent| parent.canonicalize().ok());
+    if !confined.starts_with(dir) || parent.is_some_and(|parent| !parent.starts_with(dir)) {
+        return Err(format!("output {file_path:?} is outside of the project directory {dir:?}"));
+    }
+    Ok(confined)
+}
+
+/// Decodes the config file, which editors on Windows may save with a byte order mark or as
+/// UTF-16. UTF-16 without byte order mark is recognized by the zero bytes of the ASCII text it
+/// starts with.
+fn decode_config(bytes: &[u8]) -> Result<String, String> {
+    let utf16 = |bytes: &[u8], little_endian: bool, encoding: &str| {
+        if !bytes.len().is_multiple_of(2) {
+            return Err(format!("config is {encoding} with an odd number of bytes"));
+        }
+        let units = bytes.chunks_exact(2).map(|unit| match little_endian {
+            true => u16::from_le_bytes([unit[0], unit[1]]),
+            false => u16::from_be_bytes([unit[0], unit[1]]),
+        });
+        char::decode_utf16(units).collect::<Result<String, _>>().map_err(|err| format!("config is invalid {encoding}: {err}"))
+    };
+    let ascii_utf16 = |zeros_at: usize| bytes.len() >= 2
+        && bytes.chunks_exact(2).take(4).all(|unit| unit[zeros_at] == 0 && unit[1 - zeros_at] != 0);
+
+    match bytes {
+        [0xFF, 0xFE, 0, 0, ..] | [0, 0, 0xFE, 0xFF, ..] => Err("config is UTF-32, save it as UTF-8".to_string()),
+        [0xEF, 0xBB, 0xBF, rest @ ..] => String::from_utf8(rest.to_vec()).map_err(|err| format!("config is invalid UTF-8: {err}")),
+        [0xFF, 0xFE, rest @ ..] => utf16(rest, true, "UTF-16LE"),
+        [0xFE, 0xFF, rest @ ..] => utf16(rest, false, "UTF-16BE"),
+        _ if ascii_utf16(1) => utf16(bytes, true, "UTF-16LE"),
+        _ if ascii_utf16(0) => utf16(bytes, false, "UTF-16BE"),
+        _ => String::from_utf8(bytes.to_vec())
+            .map_err(|err| format!("config is not UTF-8, invalid byte at offset {}, save it as UTF-8", err.utf8_error().valid_up_to())),
+    }
+}
+
+/// First line of a compiled config, telling it from a TOML config.
+const COMPILED_CONFIG_MAGIC: &[u8] = b"dlt-kraken compiled config 1\n";
+
+/// Config written by `dlt-kraken compile`: the text of the config along with the FIBEX files of
+/// its filters, already parsed. Compiled regexes cannot be saved, they are compiled on loading
+/// as for a TOML config, which is fast compared to parsing large FIBEX files.
+#[derive(Serialize,Deserialize)]
+struct CompiledConfig {
+    config: String,
+    fibex: HashMap<path::PathBuf, Fibex>,
+}
+
+/// Compiles the config into a file that loads without reading its FIBEX files, for short-lived
+/// runs, e.g. in CI, where parsing them takes most of the time. It is passed as config file like
+/// the TOML config it was compiled from.
+pub fn compile_config(file_path: &Path, output_path: &Path) -> Result<(), Box<dyn Error>> {
+    let contents = decode_config(&fs::read(file_path)?)?;
+    let config = parse_config(&contents)?;
+    let mut fibex = HashMap::new();
+    for non_verbose in config.filters.iter().flatten().filter_map(|filter| filter.non_verbose.as_ref()) {
+        if !fibex.contains_key(&non_verbose.fibex_path) {
+            fibex.insert(non_verbose.fibex_path.clone(), Fibex::read(&non_verbose.fibex_path)?);
+        }
+    }
+    let mut compiled = COMPILED_CONFIG_MAGIC.to_vec();
+    serde_json::to_writer(&mut compiled, &CompiledConfig { config: contents, fibex })?;
+    fs::write(output_path, compiled)?;
+    Ok(())
+}
+
 pub fn read_config(file_path: &Path) -> Result<Config, Box<dyn Error>> {
-    let contents = fs::read_to_string(file_path)?;
-    let config: Config = toml::from_str(&contents).unwrap();
-    if let Err(err) = config.is_valid() {
-        eprintln!("{err}");
-        process::exit(1)
+    let bytes = fs::read(file_path)?;
+    let Some(compiled) = bytes.strip_prefix(COMPILED_CONFIG_MAGIC) else {
+        return parse_config(&decode_config(&bytes)?);
+    };
+    let CompiledConfig { config, fibex } = serde_json::from_slice(compiled)?;
+    let mut config = parse_config(&config)?;
+    for non_verbose in config.filters.iter_mut().flatten().filter_map(|filter| filter.non_verbose.as_mut()) {
+        non_verbose.fibex = fibex.get(&non_verbose.fibex_path).cloned();
     }
     Ok(config)
 }
+
+fn parse_config(contents: &str) -> Result<Config, Box<dyn Error>> {
+    let mut value: toml::Value = toml::from_str(contents)?;
+    apply_presets(&mut value)?;
+    let config: Config = value.try_into()?;
+    config.is_valid()?;
+    Ok(config)
+}
@@ -1,15 +1,160 @@
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::fs::File;
+use std::io::{self, BufWriter, ErrorKind, Read, Write as _};
+use std::ops::{Deref, RangeInclusive};
 use std::path::PathBuf;
-use std::fmt::Write;
-use memmap::MmapOptions;
-use crate::dlt::filter::{Filter};
-use crate::dlt::headers::{ExtendedHeader, read_extended_header, read_standard_header, read_storage_header, StandardHeader, StorageHeader};
+use std::fmt::{Debug, Formatter, Write};
+use std::process;
+use std::sync::{Mutex, OnceLock};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use clap::ValueEnum;
+use std::path::Path;
+use memmap::{Mmap, MmapOptions};
+use serde_derive::Serialize;
+use crate::config::{ByteOrder, Callstack, FirstPer, Profile, Span, Subseconds};
+use crate::dlt::apptrace::CallTracker;
+use crate::dlt::arrow::ArrowWriter;
+use crate::dlt::sqlite::SqliteWriter;
+use crate::dlt::parquet::ParquetWriter;
+use crate::dlt::callstack::CallstackCollector;
+use crate::dlt::can::{CanFrame, Dbc};
+use crate::dlt::console::Console;
+use crate::dlt::context::{ContextLines, Recent};
+use crate::dlt::control::ControlMessage;
+use crate::dlt::counters::CounterGaps;
+use crate::dlt::export::DltExport;
+use crate::dlt::filetransfer::{FileExtractor, FileTransfer};
+use crate::dlt::filter::{Extraction, Filter};
+use crate::dlt::format::InputFormat;
+use crate::dlt::lifecycle::Lifecycles;
+use crate::dlt::headers::{ExtendedHeader, read_extended_header, read_standard_header, read_storage_header, StandardHeader, StorageHeader, find_message_frame, peek_storage_header, verify_message_frame};
 use crate::dlt::payload::{Payload, Value};
-use crate::{Output, OutputField, OutputType};
+use crate::dlt::registry::Registry;
+use crate::dlt::schema::Schemas;
+use crate::dlt::section::Sections;
+use crate::dlt::someip::SomeIp;
+use crate::dlt::sort::ExternalSort;
+use crate::dlt::stats::Stats;
+use crate::dlt::timeline::Timeline;
+use crate::dlt::warning::{Warning, WarningKind};
+use crate::config::ShardKey;
+use crate::dlt::csv::CsvWriters;
+use crate::dlt::dedup::Deduplicator;
+use crate::dlt::downsample::Downsampler;
+use crate::dlt::reference::ReferenceEvents;
+use crate::{DEFAULT_MISSING, Output, OutputField, OutputType};
+use crate::manifest::Manifest;
+use crate::provenance::Provenance;
 
-mod headers;
-mod payload;
+pub(crate) mod headers;
+pub(crate) mod payload;
+pub use crate::dlt::headers::{MessageType, MessageTypeInfoLog};
+pub mod alert;
+pub mod apptrace;
+pub mod arrow;
+pub mod sqlite;
+pub mod bundle;
+mod parquet;
+pub mod can;
+pub mod callstack;
+pub mod chaos;
+mod console;
+pub mod context;
+pub mod control;
+pub mod counters;
+pub mod csv;
+pub mod dedup;
+pub mod downsample;
+pub mod eval;
+pub mod export;
+pub mod fibex;
+pub mod filetransfer;
 pub mod filter;
+pub mod format;
+pub mod lifecycle;
+pub mod pcap;
+pub mod query;
+pub mod reference;
+pub mod schema;
+pub mod section;
+pub mod registry;
+pub mod selftest;
+pub mod server;
+pub mod someip;
+pub mod sort;
+pub mod stats;
+pub mod timeline;
+pub mod wasm;
+pub mod warning;
+pub mod watch;
+
+/// Rendering of string arguments that are not valid UTF-8.
+#[derive(ValueEnum,Debug,Clone,Copy,PartialEq)]
+pub enum InvalidUtf8 {
+    /// substitute the invalid bytes with replacement characters
+    Replace,
+    /// write the whole string as hex, like raw data
+    Hex,
+}
+
+static INVALID_UTF8: OnceLock<InvalidUtf8> = OnceLock::new();
+
+/// Sets how invalid UTF-8 strings are decoded, before any trace is read.
+pub fn set_invalid_utf8(invalid_utf8: InvalidUtf8) {
+    let _ = INVALID_UTF8.set(invalid_utf8);
+}
+
+fn invalid_utf8() -> InvalidUtf8 {
+    *INVALID_UTF8.get().unwrap_or(&InvalidUtf8::Replace)
+}
+
+static INPUT_FORMAT: OnceLock<InputFormat> = OnceLock::new();
+
+/// Sets the format of all inputs instead of detecting it per file, before any trace is read.
+pub fn set_input_format(input_format: InputFormat) {
+    let _ = INPUT_FORMAT.set(input_format);
+}
+
+static PROFILES: OnceLock<HashMap<String, Profile>> = OnceLock::new();
+
+/// Sets the profiles of the ECUs that deviate from the standard, before any trace is read.
+pub fn set_profiles(profiles: &[Profile]) {
+    let profiles = profiles.iter().map(|profile| (profile.ecu_id().clone(), profile.clone())).collect();
+    let _ = PROFILES.set(profiles);
+}
+
+fn profile(ecu_id: &str) -> Option<&'static Profile> {
+    PROFILES.get()?.get(ecu_id)
+}
+
+/// Applies the resolution of the storage time the profile of the logger declares.
+fn apply_storage_profile(storage_header: &mut StorageHeader) {
+    if profile(storage_header.ecu_id()).and_then(Profile::storage_subseconds) == Some(Subseconds::Nanoseconds) {
+        storage_header.set_nanoseconds();
+    }
+}
+
+/// Storage time in seconds of the verified message at `index`, without parsing the message.
+/// Messages with a serial header have no storage time and report 0.
+fn peek_storage_time(data: &[u8], index: usize) -> f64 {
+    let Some(mut storage_header) = peek_storage_header(data, index) else {
+        return 0.0;
+    };
+    apply_storage_profile(&mut storage_header);
+    storage_header.time()
+}
+
+static DBC: OnceLock<Dbc> = OnceLock::new();
+
+/// Sets the description of the CAN frames in network trace messages, before any trace is read.
+pub fn set_dbc(dbc: Dbc) {
+    let _ = DBC.set(dbc);
+}
+
+fn dbc() -> Option<&'static Dbc> {
+    DBC.get()
+}
 
 pub struct TraceData<'d> {
     data : &'d [u8],
@@ -17,12 +162,13 @@ pub struct TraceData<'d> {
 }
 
 impl<'t,'d:'t> TraceData<'d> {
-    fn new(data: &'d [u8], index: usize) -> TraceData<'d> {
+    /// Trace starting at the given byte index of the data, e.g. of a mapped file.
+    pub fn new(data: &'d [u8], index: usize) -> TraceData<'d> {
         TraceData {data, index }
     }
 
     fn iter(&'t self) -> TraceDataIter<'d> {
-        TraceDataIter { data: self.data, index: self.index }
+        TraceDataIter { data: self.data, index: self.index, error: None, warnings: vec![] }
     }
 }
 
@@ -30,11 +176,25 @@ impl<'d> Iterator for TraceDataIter<'d> {
     type Item = Message<'d>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.index < self.data.len() {
-            Some(self.read_message())
-        } else {
-            None
+        if self.index >= self.data.len() {
+            return None;
+        }
+        if let Err(err) = verify_message_frame(self.data, self.index) {
+            match find_message_frame(self.data, self.index + 1) {
+                Some(next_index) => {
+                    let message = format!("{err}, skipped {} bytes", next_index - self.index);
+                    self.warnings.push(Warning { offset: self.index, kind: WarningKind::Resync, message });
+                    self.index = next_index;
+                },
+                None => {
+                    // no complete message follows, the data ends with a corrupt or truncated one
+                    self.error = Some(err);
+                    self.index = self.data.len();
+                    return None;
+                },
+            }
         }
+        Some(self.read_message())
     }
 }
 
@@ -47,23 +207,49 @@ impl<'a,'d:'a> IntoIterator for &'a TraceData<'d> {
     }
 }
 
+#[derive(Clone)]
 pub struct TraceDataIter<'d> {
     data: &'d [u8],
     index: usize,
+    error: Option<String>,
+    warnings: Vec<Warning>,
 }
 
 impl<'d> TraceDataIter<'d> {
+    /// The reason the iteration stopped before the end of the data.
+    pub fn error(&self) -> &Option<String> {
+        &self.error
+    }
+
+    /// Problems found so far that did not stop the iteration, e.g. skipped corrupt data.
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
     fn read_message(&mut self) -> Message<'d> {
-        let storage_header = read_storage_header(self);
+        let frame_index = self.index;
+        let mut storage_header = read_storage_header(self);
+        apply_storage_profile(&mut storage_header);
         let start_index = self.index;
+        let profile = profile(storage_header.ecu_id());
 
-        let standard_header = read_standard_header(self);
+        let mut standard_header = read_standard_header(self);
+        if let Some(resolution) = profile.and_then(Profile::timestamp_resolution) {
+            standard_header.rescale_timestamp(resolution);
+        }
+        let is_big_endian = match profile.and_then(Profile::byte_order) {
+            Some(byte_order) => byte_order == ByteOrder::Big,
+            None => standard_header.is_big_endian(),
+        };
 
         let mut message = Message {
             storage_header,
             standard_header,
             extended_header: None,
             payload: vec![],
+            malformed: false,
+            header_only: false,
+            bytes: &[],
         };
 
         if message.standard_header.has_extended_header() {
@@ -72,127 +258,1025 @@ impl<'d> TraceDataIter<'d> {
 
             let payload_size = message.standard_header.msg_len() - message.standard_header.len() - message.extended_header.as_ref().unwrap().len();
 
-            if message.extended_header.as_ref().unwrap().is_verbose() {
+            if payload_size == 0 {
+                message.header_only = true;
+            } else if message.extended_header.as_ref().unwrap().is_verbose() {
                 let payload = Payload::new_verbose(
                     self.data,
                     self.index,
                     payload_size,
-                    message.standard_header.is_big_endian(),
+                    is_big_endian,
                     message.extended_header.as_ref().unwrap().number_of_arguments(),
-                );
+                ).with_string_coding(profile.and_then(Profile::string_coding));
 
                 for arg in &payload {
                     message.payload.push(arg);
                 }
+                message.malformed = message.payload.len() < message.extended_header.as_ref().unwrap().number_of_arguments();
             } else {
                 let payload = Payload::new_non_verbose(
                     self.data,
                     self.index,
                     payload_size,
-                    message.standard_header.is_big_endian(),
+                    is_big_endian,
                 );
-                let value = payload.read_non_verbose();
-                message.payload.push(value);
+                match payload.read_non_verbose() {
+                    Some(value) => message.payload.push(value),
+                    None => message.malformed = true,
+                }
             }
         } else {
             let payload_size = message.standard_header.msg_len() - message.standard_header.len();
 
-            let payload = Payload::new_non_verbose(
-                self.data,
-                self.index,
-                payload_size,
-                message.standard_header.is_big_endian(),
-            );
-            let value = payload.read_non_verbose();
-            message.payload.push(value);
+            if payload_size == 0 {
+                message.header_only = true;
+            } else {
+                let payload = Payload::new_non_verbose(
+                    self.data,
+                    self.index,
+                    payload_size,
+                    is_big_endian,
+                );
+                match payload.read_non_verbose() {
+                    Some(value) => message.payload.push(value),
+                    None => message.malformed = true,
+                }
+            }
         }
         self.index = start_index + message.standard_header.msg_len();
+        message.bytes = &self.data[frame_index..self.index];
+        if message.malformed {
+            let message = "payload reaches beyond the message or has an unknown type".to_string();
+            self.warnings.push(Warning { offset: frame_index, kind: WarningKind::MalformedPayload, message });
+        }
         message
     }
 }
 
-#[derive(Debug)]
 pub struct Message<'d> {
     storage_header: StorageHeader,
     standard_header: StandardHeader,
     extended_header: Option<ExtendedHeader>,
     payload: Vec<Value<'d>>,
+    /// the payload could not be decoded completely, `payload` holds the arguments before the error
+    malformed: bool,
+    /// the message ends after its headers, it has no payload to decode
+    header_only: bool,
+    /// the message as stored in the trace, including its storage header
+    bytes: &'d [u8],
 }
 
-pub fn run_dlt(file_path: &PathBuf, filters: &Filter, output: &Option<Output>) {
-    println!("{file_path:?}");
-
-    let file= File::open(file_path).unwrap();
-    let mmap = unsafe { MmapOptions::new().map(&file).unwrap() };
-
-    let message = TraceData::new(&mmap, 0);
-
-    for msg in message.iter()
-        .filter(|msg| filters.filter_ecu_id(msg))
-        .filter(|msg| filters.filter_app_id(msg))
-        .filter(|msg| filters.filter_context_id(msg)) {
-        let captures = filters.find_patterns(&msg);
-            if captures.is_some() {
-                println!("cap {captures:?}");
-                println!("output: {output:?}");
-                let captures : Vec<_>= captures.iter().flatten().collect();
-                if let Some(out) = output {
-                    let delimiter = match out.output_type() {
-                        OutputType::Stdout(stdout) => stdout.delimiter,
-                        OutputType::Csv(csv) => csv.delimiter,
-                    };
-                    let mut out_string = String::new();
-
-                    for field in &out.fields {
-                        let default_str = "none";
-                        let result = match field {
-                            OutputField::Time => write!(&mut out_string, "T{delimiter}"),
-                            OutputField::Timestamp => write!(&mut out_string, "TS{delimiter}"),
-                            OutputField::App => write!(&mut out_string, "{}{delimiter}", msg.extended_header.as_ref().map_or_else(|| default_str, |header| header.app_id())),
-                            OutputField::Ctx => write!(&mut out_string, "{}{delimiter}", msg.extended_header.as_ref().map_or_else(|| default_str, |header| header.context_id())),
-                            OutputField::Ecu => write!(&mut out_string, "{}{delimiter}", msg.standard_header.ecu_id().as_ref().map_or_else(|| default_str, |value| value)),
-                            OutputField::Capture(name) => {
-                                let mut result = Ok(());
-                                for capture in &captures {
-
-                                    if let Some(capture) = capture.name(name).map(|captured| captured.as_str()) {
-                                        result = write!(&mut out_string, "{capture}{delimiter}");
-                                        if result.is_err() {
-                                            break;
-                                        }
-                                    }
+impl Debug for Message<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Message")
+            .field("storage_header", &self.storage_header)
+            .field("standard_header", &self.standard_header)
+            .field("extended_header", &self.extended_header)
+            .field("payload", &self.payload)
+            .field("malformed", &self.malformed)
+            .field("header_only", &self.header_only)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Information about a message beyond its own content, used for the output fields.
+struct FieldContext<'c> {
+    extraction: &'c Extraction<'c>,
+    /// position of the message in the input
+    index: usize,
+    /// boot of the ECU the message belongs to
+    lifecycle: usize,
+    registry: &'c Registry,
+    references: Option<&'c ReferenceEvents>,
+    /// metadata the manifest lists for the input file
+    metadata: &'c BTreeMap<String, String>,
+    /// written for values the message lacks
+    missing: &'c str,
+}
+
+impl Message<'_> {
+    /// Message time in microseconds, preferring the uptime of the ECU over the storage time.
+    pub fn time(&self) -> f64 {
+        match self.standard_header.timestamp() {
+            Some(timestamp) => timestamp as f64 * 100.0,
+            None => self.storage_header.time() * 1_000_000.0,
+        }
+    }
+
+    /// Session id of the standard header, if the message has one.
+    pub fn session_id(&self) -> Option<u32> {
+        self.standard_header.session_id()
+    }
+
+    /// Type of the message, `None` without extended header.
+    pub fn message_type(&self) -> Option<MessageType> {
+        self.extended_header.as_ref().map(ExtendedHeader::msg_type)
+    }
+
+    /// Log level of log messages.
+    pub fn log_level(&self) -> Option<MessageTypeInfoLog> {
+        self.extended_header.as_ref()
+            .filter(|header| header.msg_type() == MessageType::Log)
+            .and_then(ExtendedHeader::msg_type_info_log)
+    }
+
+    /// The decoded SOME/IP message or CAN frame of network trace messages.
+    pub fn network_trace(&self) -> Option<String> {
+        match (SomeIp::from_message(self), CanFrame::from_message(self)) {
+            (Some(someip), _) => Some(someip.to_string()),
+            (None, Some(can)) => Some(can.to_string()),
+            (None, None) => None,
+        }
+    }
+
+    /// The payload as text: the decoded network trace or the arguments separated by spaces.
+    pub fn payload_text(&self) -> String {
+        if let Some(network_trace) = self.network_trace() {
+            return network_trace;
+        }
+        let mut payload = String::new();
+        for (idx, value) in self.payload.iter().enumerate() {
+            let separator = if idx > 0 { " " } else { "" };
+            let _ = write!(payload, "{separator}{value}");
+        }
+        payload
+    }
+
+    /// Names and units of the arguments sent with variable info.
+    pub fn variables(&self) -> Vec<(&str, Option<&str>)> {
+        self.payload.iter().filter_map(|value| Some((value.name()?, value.unit()))).collect()
+    }
+
+    /// Size of the payload in bytes, from the message length of the standard header.
+    pub fn payload_len(&self) -> usize {
+        let ext_header_len = self.extended_header.as_ref().map_or(0, ExtendedHeader::len);
+        self.standard_header.msg_len().saturating_sub(self.standard_header.len() + ext_header_len)
+    }
+
+    /// Number of arguments of the extended header, `None` for messages without one.
+    pub fn number_of_arguments(&self) -> Option<usize> {
+        self.extended_header.as_ref().map(ExtendedHeader::number_of_arguments)
+    }
+
+    /// Whether the payload is malformed, e.g. an argument reaches beyond the end of the message.
+    pub fn is_malformed(&self) -> bool {
+        self.malformed
+    }
+
+    /// Whether the message consists of its headers only, without any payload.
+    pub fn is_header_only(&self) -> bool {
+        self.header_only
+    }
+
+    /// The message as stored in the trace, including its storage header.
+    pub fn bytes(&self) -> &[u8] {
+        self.bytes
+    }
+
+    /// ECU id of the standard header, falling back to the one of the storage header.
+    pub fn ecu_id(&self) -> &str {
+        self.standard_header.ecu_id().as_deref().unwrap_or(self.storage_header.ecu_id())
+    }
+
+    /// Formats the given fields of the message, each one followed by the delimiter.
+    fn format_fields(&self, fields: &[OutputField], delimiter: char, context: &FieldContext) -> String {
+        let mut out_string = String::new();
+        let FieldContext { extraction, index, lifecycle, registry, references, metadata, missing } = context;
+
+        for field in fields {
+            let default_str = *missing;
+            let result = match field {
+                OutputField::Time => match self.storage_header.rfc3339() {
+                    Some(time) => write!(&mut out_string, "{time}{delimiter}"),
+                    None => write!(&mut out_string, "{default_str}{delimiter}"),
+                },
+                OutputField::Timestamp => match self.standard_header.timestamp() {
+                    // the timestamp counts in 0.1 milliseconds
+                    Some(timestamp) => write!(&mut out_string, "{:.4}{delimiter}", timestamp as f64 / 10_000.0),
+                    None => write!(&mut out_string, "{default_str}{delimiter}"),
+                },
+                OutputField::Session => match self.session_id() {
+                    Some(session_id) => write!(&mut out_string, "{session_id}{delimiter}"),
+                    None => write!(&mut out_string, "{default_str}{delimiter}"),
+                },
+                OutputField::App => write!(&mut out_string, "{}{delimiter}", self.extended_header.as_ref().map_or_else(|| default_str, |header| header.app_id())),
+                OutputField::Ctx => write!(&mut out_string, "{}{delimiter}", self.extended_header.as_ref().map_or_else(|| default_str, |header| header.context_id())),
+                OutputField::Ecu => write!(&mut out_string, "{}{delimiter}", self.standard_header.ecu_id().as_deref().unwrap_or(default_str)),
+                OutputField::Static(_, value) => write!(&mut out_string, "{value}{delimiter}"),
+                OutputField::Metadata(key) => write!(&mut out_string, "{}{delimiter}", metadata.get(key).map_or(default_str, String::as_str)),
+                OutputField::Capture(name) => {
+                    let mut result = Ok(());
+                    for capture in extraction.values(name) {
+                        result = write!(&mut out_string, "{capture}{delimiter}");
+                        if result.is_err() {
+                            break;
+                        }
+                    }
+                    result
+                },
+                OutputField::Type => write!(&mut out_string, "{}{delimiter}", self.extended_header.as_ref().map_or_else(|| default_str.to_string(), |header| header.type_name())),
+                OutputField::Level => match self.log_level() {
+                    Some(level) => write!(&mut out_string, "{level}{delimiter}"),
+                    None => write!(&mut out_string, "{default_str}{delimiter}"),
+                },
+                OutputField::Payload => match (ControlMessage::from_message(self), self.network_trace()) {
+                    (Some(control), _) => write!(&mut out_string, "{control}{delimiter}"),
+                    (None, Some(network_trace)) => write!(&mut out_string, "{network_trace}{delimiter}"),
+                    (None, None) => self.payload.iter().try_for_each(|value| write!(&mut out_string, "{value}{delimiter}")),
+                },
+                OutputField::PayloadLen => write!(&mut out_string, "{}{delimiter}", self.payload_len()),
+                OutputField::Noar => match self.number_of_arguments() {
+                    Some(noar) => write!(&mut out_string, "{noar}{delimiter}"),
+                    None => write!(&mut out_string, "{default_str}{delimiter}"),
+                },
+                OutputField::RefEvent | OutputField::RefDelta => {
+                    let time = self.storage_header.time();
+                    match (field, references.and_then(|references| references.nearest(time))) {
+                        (OutputField::RefEvent, Some((name, _))) => write!(&mut out_string, "{name}{delimiter}"),
+                        (_, Some((_, delta))) => write!(&mut out_string, "{delta:+.6}{delimiter}"),
+                        (_, None) => write!(&mut out_string, "{default_str}{delimiter}"),
+                    }
+                },
+                OutputField::Index => write!(&mut out_string, "{index}{delimiter}"),
+                OutputField::Lifecycle => write!(&mut out_string, "{lifecycle}{delimiter}"),
+                OutputField::AppDescription => {
+                    let description = self.extended_header.as_ref()
+                        .and_then(|header| registry.app_description(self.ecu_id(), header.app_id()));
+                    write!(&mut out_string, "{}{delimiter}", description.unwrap_or(default_str))
+                },
+                OutputField::ContextDescription => {
+                    let description = self.extended_header.as_ref()
+                        .and_then(|header| registry.context_description(self.ecu_id(), header.app_id(), header.context_id()));
+                    write!(&mut out_string, "{}{delimiter}", description.unwrap_or(default_str))
+                },
+            };
+            if let Err(err) = result {
+                eprintln!("error on constructing output: {err}");
+            }
+        }
+        out_string
+    }
+
+    /// Line of the output, its fields joined by the delimiter or filled into its template. CSV
+    /// rows have one cell per field, so the columns stay in the order of the format and header.
+    fn format_line(&self, out: &Output, delimiter: char, context: &FieldContext) -> String {
+        match (out.template(), out.output_type()) {
+            (Some(template), _) => template.render(out.fields().iter().map(|field| self.format_field(field, context))),
+            (None, OutputType::Csv(_)) => {
+                let cells: Vec<String> = out.fields().iter()
+                    .map(|field| csv::escape(&self.format_field(field, context), delimiter).into_owned())
+                    .collect();
+                cells.join(&delimiter.to_string())
+            },
+            (None, _) => self.format_fields(out.fields(), delimiter, context).trim_end_matches(delimiter).to_string(),
+        }
+    }
+
+    /// Value of the field alone, values of captures and arguments separated by spaces.
+    fn format_field(&self, field: &OutputField, context: &FieldContext) -> String {
+        let mut value = self.format_fields(std::slice::from_ref(field), ' ', context);
+        value.pop();
+        value
+    }
+
+    fn shard_value(&self, key: ShardKey) -> &str {
+        match key {
+            ShardKey::Ecu => self.storage_header.ecu_id(),
+            ShardKey::App => self.extended_header.as_ref().map_or("none", |header| header.app_id()),
+            ShardKey::Ctx => self.extended_header.as_ref().map_or("none", |header| header.context_id()),
+        }
+    }
+}
+
+/// Content of an input file, mapped into memory or, if that is not possible, read.
+enum FileData {
+    Mapped(Mmap),
+    Read(Vec<u8>),
+}
+
+impl Deref for FileData {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            FileData::Mapped(mmap) => mmap,
+            FileData::Read(data) => data,
+        }
+    }
+}
+
+/// Maps the file into memory. Files that cannot be mapped, e.g. ones locked by the logger on
+/// Windows or pipes, are read instead. Unless set with `set_input_format`, the format is detected
+/// from the start of the file; pcap and pcapng captures and raw messages are converted to a trace
+/// of messages with storage header.
+fn map_file(file_path: &Path) -> Result<FileData, String> {
+    let mut file = File::open(file_path).map_err(|err| format!("error on opening: {err}"))?;
+    let data = match unsafe { MmapOptions::new().map(&file) } {
+        Ok(mmap) => FileData::Mapped(mmap),
+        Err(map_err) => {
+            let mut data = vec![];
+            file.read_to_end(&mut data).map_err(|err| format!("error on mapping: {map_err}, error on reading: {err}"))?;
+            FileData::Read(data)
+        },
+    };
+    let (trace, warnings) = match INPUT_FORMAT.get().copied().unwrap_or_else(|| InputFormat::detect(&data)) {
+        InputFormat::Storage | InputFormat::Serial => return Ok(data),
+        InputFormat::Raw => format::read_raw(&data),
+        InputFormat::Pcap => pcap::read_capture(&data)?,
+    };
+    for warning in warnings {
+        eprintln!("{file_path:?}: {warning}");
+    }
+    Ok(FileData::Read(trace))
+}
+
+/// Exit code of a batch run in which some of the input files failed.
+const EXIT_CODE_FILE_FAILURES: i32 = 2;
+
+/// Input file a batch run failed on.
+#[derive(Serialize, Debug)]
+pub struct FileFailure {
+    file: PathBuf,
+    error: String,
+}
+
+/// Hands the messages of each file to `process`. Corrupt data within a file is skipped and
+/// reported as warning. A file that cannot be read or ends in corrupt data does not stop the batch, the
+/// failure is reported and the next file is processed.
+fn for_each_file(file_paths: &[PathBuf], mut process: impl FnMut(&Path, &mut TraceDataIter)) -> Vec<FileFailure> {
+    let mut failures = vec![];
+    for file_path in file_paths {
+        let result = map_file(file_path).and_then(|mmap| {
+            let mut messages = TraceData::new(&mmap, 0).iter();
+            process(file_path, &mut messages);
+            warning::report(file_path, messages.warnings());
+            messages.error().clone().map_or(Ok(()), Err)
+        });
+        if let Err(error) = result {
+            eprintln!("{file_path:?}: {error}");
+            failures.push(FileFailure { file: file_path.clone(), error });
+        }
+    }
+    failures
+}
+
+fn exit_on_failures(failures: &[FileFailure], file_count: usize) {
+    if !failures.is_empty() {
+        eprintln!("{} of {file_count} input files failed", failures.len());
+        process::exit(EXIT_CODE_FILE_FAILURES);
+    }
+}
+
+/// Prints the messages of the given index range, optionally only the first or last ones of it.
+/// Prints the messages in the range, with `pretty` aligned in columns and cut to the width of the
+/// terminal.
+pub fn run_cat(file_paths: &[PathBuf], fields: &[OutputField], range: RangeInclusive<usize>, head: Option<usize>, tail: Option<usize>, pretty: bool) {
+    let console = pretty.then(Console::new);
+    let mut stdout = BufWriter::new(io::stdout().lock());
+    let mut last_lines = VecDeque::new();
+    let mut lifecycles = Lifecycles::new();
+    let registry = Registry::new();
+    let mut index = 0;
+    let mut printed = 0;
+
+    let mut done = false;
+
+    let failures = for_each_file(file_paths, |_, messages| {
+        if done {
+            return;
+        }
+        for msg in messages {
+            let msg_index = index;
+            index += 1;
+            let lifecycle = lifecycles.add(&msg);
+            if msg_index < *range.start() {
+                continue;
+            }
+            if msg_index > *range.end() || head.is_some_and(|head| printed >= head) {
+                done = true;
+                return;
+            }
+
+            let context = FieldContext { extraction: &Extraction::default(), index: msg_index, lifecycle, registry: &registry, references: None, metadata: &BTreeMap::new(), missing: DEFAULT_MISSING };
+            let line = match &console {
+                Some(console) => console.format(&msg, msg_index, fields, &context),
+                None => format!("{msg_index} {}", msg.format_fields(fields, ' ', &context).trim_end()),
+            };
+            match tail {
+                Some(tail) => {
+                    if last_lines.len() == tail {
+                        last_lines.pop_front();
+                    }
+                    if tail > 0 {
+                        last_lines.push_back(line);
+                    }
+                },
+                None => {
+                    exit_on_write_error(writeln!(stdout, "{line}"));
+                    printed += 1;
+                },
+            }
+        }
+    });
+
+    for line in last_lines {
+        exit_on_write_error(writeln!(stdout, "{line}"));
+    }
+    exit_on_write_error(stdout.flush());
+    exit_on_failures(&failures, file_paths.len());
+}
+
+pub fn run_stats(file_paths: &[PathBuf], json: bool) {
+    let mut stats = Stats::new();
+    let mut failures = vec![];
+
+    // the files are gathered in parallel and merged in input order, so drops between
+    // consecutive files are still counted
+    for (file_path, result) in file_paths.iter().zip(gather_stats(file_paths)) {
+        let result = result.and_then(|(file_stats, warnings, error)| {
+            stats.merge(file_stats);
+            warning::report(file_path, &warnings);
+            error.map_or(Ok(()), Err)
+        });
+        if let Err(error) = result {
+            eprintln!("{file_path:?}: {error}");
+            failures.push(FileFailure { file: file_path.clone(), error });
+        }
+    }
+
+    if json {
+        let summary = StatsSummary { stats: &stats, failures: &failures };
+        println!("{}", serde_json::to_string_pretty(&summary).unwrap());
+    } else {
+        print!("{stats}");
+    }
+    exit_on_failures(&failures, file_paths.len());
+}
+
+/// Statistics and warnings of a file, and the reason it was not read completely.
+type FileStats = Result<(Stats, Vec<Warning>, Option<String>), String>;
+
+/// Gathers the statistics of every file on a pool of worker threads.
+fn gather_stats(file_paths: &[PathBuf]) -> Vec<FileStats> {
+    let workers = thread::available_parallelism().map_or(1, usize::from).min(file_paths.len());
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new((0..file_paths.len()).map(|_| None).collect::<Vec<_>>());
+
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| {
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(file_path) = file_paths.get(index) else { break };
+                    let result = file_stats(file_path);
+                    results.lock().unwrap()[index] = Some(result);
+                }
+            });
+        }
+    });
+    results.into_inner().unwrap().into_iter().map(Option::unwrap).collect()
+}
+
+fn file_stats(file_path: &Path) -> FileStats {
+    let mut stats = Stats::new();
+    let mmap = map_file(file_path)?;
+    let mut messages = TraceData::new(&mmap, 0).iter();
+    for msg in &mut messages {
+        stats.add(&msg);
+    }
+    Ok((stats, messages.warnings().to_vec(), messages.error().clone()))
+}
+
+#[derive(Serialize)]
+struct StatsSummary<'s> {
+    #[serde(flatten)]
+    stats: &'s Stats,
+    failures: &'s [FileFailure],
+}
+
+/// Reassembles the files sent with the dlt-system file transfer protocol and writes them to the
+/// output directory.
+pub fn run_extract_files(file_paths: &[PathBuf], output_dir: &Path) {
+    let mut extractor = FileExtractor::new(output_dir);
+    let mut errors = 0;
+
+    let failures = for_each_file(file_paths, |_, messages| {
+        for msg in messages {
+            if let Some(transfer) = FileTransfer::from_message(&msg) {
+                if let Err(err) = extractor.add(transfer) {
+                    eprintln!("{err}");
+                    errors += 1;
+                }
+            }
+        }
+    });
+
+    for err in extractor.finish() {
+        eprintln!("{err}");
+        errors += 1;
+    }
+    exit_on_failures(&failures, file_paths.len());
+    if errors > 0 {
+        process::exit(1);
+    }
+}
+
+/// Prints each function call traced with FunctionIn/FunctionOut messages and its duration.
+pub fn run_calls(file_paths: &[PathBuf]) {
+    let mut calls = CallTracker::new();
+
+    let failures = for_each_file(file_paths, |_, messages| {
+        for msg in messages {
+            if let Some(call) = calls.add(&msg) {
+                println!("{call}");
+            }
+        }
+    });
+    exit_on_failures(&failures, file_paths.len());
+}
+
+/// Prints the typical argument signature of the verbose messages of each context and the
+/// messages deviating from it.
+pub fn run_schema(file_paths: &[PathBuf], json: bool) {
+    let mut schemas = Schemas::new();
+
+    let failures = for_each_file(file_paths, |file_path, messages| {
+        for (index, msg) in messages.enumerate() {
+            schemas.add(file_path, index, &msg);
+        }
+    });
+    let report = schemas.report();
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report).unwrap());
+    } else {
+        report.iter().for_each(|context| print!("{context}"));
+    }
+    exit_on_failures(&failures, file_paths.len());
+}
+
+/// Writes the configured spans and the app-trace function calls as Chrome/Perfetto trace events.
+pub fn run_timeline(file_paths: &[PathBuf], spans: &[Span], output_path: &Path) {
+    let mut timeline = Timeline::new(spans).unwrap_or_else(|err| {
+        eprintln!("{err}");
+        process::exit(1);
+    });
+
+    let failures = for_each_file(file_paths, |_, messages| {
+        for msg in messages {
+            timeline.add(&msg);
+        }
+    });
+
+    let result = File::create(output_path)
+        .map_err(|err| err.to_string())
+        .and_then(|file| serde_json::to_writer(BufWriter::new(file), &timeline.finish()).map_err(|err| err.to_string()));
+    if let Err(err) = result {
+        eprintln!("error on writing timeline {output_path:?}: {err}");
+        process::exit(1);
+    }
+    exit_on_failures(&failures, file_paths.len());
+}
+
+/// Copies the messages stored within the time window, given in seconds since the epoch, to the
+/// output file. The messages are copied byte by byte without being decoded.
+pub fn run_trim(file_path: &Path, from: Option<f64>, to: Option<f64>, output_path: &Path) {
+    let mmap = map_file(file_path).unwrap_or_else(|err| {
+        eprintln!("{file_path:?}: {err}");
+        process::exit(1);
+    });
+    let mut output = File::create(output_path).map(BufWriter::new).unwrap_or_else(|err| {
+        eprintln!("error on creating {output_path:?}: {err}");
+        process::exit(1);
+    });
+
+    let mut index = 0;
+    let mut copied = 0;
+    let mut warnings = vec![];
+    while index < mmap.len() {
+        let size = match verify_message_frame(&mmap, index) {
+            Ok(size) => size,
+            Err(err) => match find_message_frame(&mmap, index + 1) {
+                Some(next_index) => {
+                    let message = format!("{err}, skipped {} bytes", next_index - index);
+                    warnings.push(Warning { offset: index, kind: WarningKind::Resync, message });
+                    index = next_index;
+                    continue;
+                },
+                None => {
+                    eprintln!("{file_path:?}: {err}");
+                    break;
+                },
+            },
+        };
+        let time = peek_storage_time(&mmap, index);
+        if from.is_none_or(|from| time >= from) && to.is_none_or(|to| time <= to) {
+            if let Err(err) = output.write_all(&mmap[index..index + size]) {
+                eprintln!("error on writing {output_path:?}: {err}");
+                process::exit(1);
+            }
+            copied += 1;
+        }
+        index += size;
+    }
+
+    if let Err(err) = output.flush() {
+        eprintln!("error on writing {output_path:?}: {err}");
+        process::exit(1);
+    }
+    warning::report(file_path, &warnings);
+    eprintln!("copied {copied} messages to {output_path:?}");
+    if index < mmap.len() {
+        process::exit(EXIT_CODE_FILE_FAILURES);
+    }
+}
+
+/// Writes the messages of the inputs ordered by device time, see `ExternalSort`. At most
+/// `max_memory` bytes of messages are held in memory, the rest is spilled to `temp_dir`.
+pub fn run_sort(file_paths: &[PathBuf], output_path: &Path, max_memory: usize, temp_dir: &Path) {
+    let mut sort = ExternalSort::new(max_memory, temp_dir);
+    let total_bytes: u64 = file_paths.iter().filter_map(|file_path| file_path.metadata().ok()).map(|metadata| metadata.len()).sum();
+    let mut read_bytes = 0;
+    let mut reported = 0;
+
+    let failures = for_each_file(file_paths, |file_path, messages| {
+        let start = read_bytes;
+        while let Some(msg) = messages.next() {
+            if let Err(err) = sort.add(&msg) {
+                eprintln!("error on spilling to {temp_dir:?}: {err}");
+                process::exit(1);
+            }
+            read_bytes = start + messages.index as u64;
+            report_progress("read", read_bytes, total_bytes, &mut reported);
+        }
+        read_bytes = start + file_path.metadata().map_or(0, |metadata| metadata.len());
+    });
+    if !failures.is_empty() {
+        exit_on_failures(&failures, file_paths.len());
+    }
+    eprintln!("read {read_bytes} bytes, spilled {} runs", sort.runs());
+
+    let mut output = File::create(output_path).map(BufWriter::new).unwrap_or_else(|err| {
+        eprintln!("error on creating {output_path:?}: {err}");
+        process::exit(1);
+    });
+    let mut reported = 0;
+    let mut written = 0;
+    let result = sort.finish(&mut output, |count, total| {
+        written = count;
+        report_progress("written", count, total, &mut reported);
+    }).and_then(|_| output.flush());
+    if let Err(err) = result {
+        eprintln!("error on writing {output_path:?}: {err}");
+        process::exit(1);
+    }
+    eprintln!("wrote {written} messages to {output_path:?}");
+}
+
+/// Prints the progress to stderr in steps of 10 percent.
+fn report_progress(step: &str, done: u64, total: u64, reported: &mut u64) {
+    let percent = (done * 100).checked_div(total).unwrap_or(100);
+    if percent >= *reported + 10 {
+        *reported = percent - percent % 10;
+        eprintln!("{step} {percent}%");
+    }
+}
+
+fn exit_on_write_error(result: io::Result<()>) {
+    if let Err(err) = result {
+        // the reading end of a pipe went away, there is nobody left to write to
+        if err.kind() != ErrorKind::BrokenPipe {
+            eprintln!("error on writing output: {err}");
+            process::exit(1);
+        }
+        process::exit(0);
+    }
+}
+
+/// Filters the messages of the given files and writes those matching any of the filters to the
+/// output of each filter they match. Only the messages in `index_range` of each file are
+/// processed. Unless `line_buffered` is set, output is block buffered for
+/// throughput instead of being flushed after each match. Messages of the `callstacks` contexts
+/// are gathered and written as one block per dump. If a `provenance` is given, each csv file
+/// starts with it as comment lines.
+pub fn run_dlt(file_paths: &[PathBuf], routes: &[(Filter, Vec<Output>)], index_range: RangeInclusive<usize>, line_buffered: bool, callstacks: &[Callstack], provenance: &Option<Provenance>, manifest: &Option<Manifest>) {
+    let mut stdout = BufWriter::new(io::stdout().lock());
+    // the outputs of all routes, the writers of each one at its position
+    let sinks: Vec<&Output> = routes.iter().flat_map(|(_, outputs)| outputs).collect();
+    let first_sinks: Vec<usize> = routes.iter()
+        .scan(0, |first_sink, (_, outputs)| {
+            let first = *first_sink;
+            *first_sink += outputs.len();
+            Some(first)
+        })
+        .collect();
+    let mut csv_writers: Vec<Option<CsvWriters>> = sinks.iter()
+        .map(|out| match out.output_type() {
+            OutputType::Csv(csv) => Some(CsvWriters::new(csv, provenance)),
+            _ => None,
+        })
+        .collect();
+    let mut dlt_exports: Vec<Option<DltExport>> = sinks.iter()
+        .map(|out| match out.output_type() {
+            OutputType::Dlt(dlt) => Some(DltExport::create(dlt).unwrap_or_else(|err| {
+                eprintln!("error on creating dlt file {:?}: {err}", dlt.file_path);
+                process::exit(1);
+            })),
+            _ => None,
+        })
+        .collect();
+    let mut arrow_writers: Vec<Option<ArrowWriter>> = sinks.iter()
+        .map(|out| match (out.output_type(), &out.fields) {
+            (OutputType::Arrow(arrow), fields) => Some(ArrowWriter::create(arrow, fields).unwrap_or_else(|err| {
+                eprintln!("error on creating arrow output {:?}: {err}", arrow.file_path);
+                process::exit(1);
+            })),
+            _ => None,
+        })
+        .collect();
+    let mut databases = HashMap::new();
+    let mut sqlite_writers: Vec<Option<SqliteWriter>> = sinks.iter()
+        .map(|out| match (out.output_type(), &out.fields) {
+            (OutputType::Sqlite(sqlite), fields) => Some(SqliteWriter::create(sqlite, fields, &mut databases).unwrap_or_else(|err| {
+                eprintln!("error on creating sqlite output {:?}: {err}", sqlite.file_path);
+                process::exit(1);
+            })),
+            _ => None,
+        })
+        .collect();
+    let mut parquet_writers: Vec<Option<ParquetWriter>> = sinks.iter()
+        .map(|out| match (out.output_type(), &out.fields) {
+            (OutputType::Parquet(parquet), fields) => Some(ParquetWriter::create(parquet, fields).unwrap_or_else(|err| {
+                eprintln!("error on creating parquet output {:?}: {err}", parquet.file_path);
+                process::exit(1);
+            })),
+            _ => None,
+        })
+        .collect();
+    let mut callstack_collector = CallstackCollector::new(callstacks);
+
+    // stdout only carries the rows of the outputs, so the inputs are not announced
+    let failures = for_each_file(file_paths, |file_path, messages| {
+        let no_metadata = BTreeMap::new();
+        let metadata = manifest.as_ref().and_then(|manifest| manifest.metadata(file_path)).unwrap_or(&no_metadata);
+        let mut lifecycles = Lifecycles::new();
+        // counters are followed over all messages, before filtering, and the gaps reported along
+        // the next message written of their context
+        let lost_markers: Vec<bool> = routes.iter()
+            .map(|(_, outputs)| outputs.iter().any(Output::lost_markers))
+            .collect();
+        let mut counter_gaps: Vec<CounterGaps> = routes.iter().map(|_| CounterGaps::new()).collect();
+
+        // descriptions are looked up in a first pass, the GetLogInfo responses often come late
+        let mut registry = Registry::new();
+        let needs_registry = sinks.iter()
+            .flat_map(|out| out.fields.iter())
+            .any(|field| matches!(field, OutputField::AppDescription | OutputField::ContextDescription));
+        if needs_registry {
+            messages.clone().for_each(|msg| registry.add(&msg));
+        }
+
+        // the last match of each context is looked up in a first pass, it is kept when thinning out
+        let mut downsamplers: Vec<Option<Downsampler>> = routes.iter()
+            .map(|(filters, _)| filters.keep_one_per().map(Downsampler::new))
+            .collect();
+        if downsamplers.iter().any(Option::is_some) {
+            for (index, msg) in messages.clone().enumerate().skip(*index_range.start()).take_while(|(index, _)| index_range.contains(index)) {
+                for ((filters, _), downsampler) in routes.iter().zip(downsamplers.iter_mut()) {
+                    if let Some(downsampler) = downsampler.as_mut().filter(|_| filters.matches(&msg)) {
+                        downsampler.add(index, &msg);
+                    }
+                }
+            }
+        }
+
+        // lifecycles each filter with first_per already had its match in
+        let mut matched_lifecycles: Vec<HashSet<usize>> = routes.iter().map(|_| HashSet::new()).collect();
+        let mut dedups: Vec<Option<Deduplicator>> = routes.iter()
+            .map(|(filters, _)| filters.dedup().then(Deduplicator::new))
+            .collect();
+        let mut sections: Vec<Option<Sections>> = routes.iter()
+            .map(|(filters, _)| filters.section().map(|(start, stop)| Sections::new(start, stop)))
+            .collect();
+
+        let mut context_lines = ContextLines::new(routes.iter().map(|(filters, _)| filters.context()).collect());
+
+        // index and lifecycle are determined before filtering, so they stay the same for any filter
+        for (index, msg) in messages.enumerate().take_while(|(index, _)| index <= index_range.end()) {
+            for (gaps, _) in counter_gaps.iter_mut().zip(&lost_markers).filter(|(_, lost_markers)| **lost_markers) {
+                gaps.add(&msg);
+            }
+            let lifecycle = lifecycles.add(&msg);
+            // sections open and close on every message, before the other filters
+            let in_sections: Vec<bool> = sections.iter_mut()
+                .map(|sections| sections.as_mut().is_none_or(|sections| sections.contains(&msg)))
+                .collect();
+            if index < *index_range.start() {
+                continue;
+            }
+            let mut matched = vec![];
+            for (route, (filters, _)) in routes.iter().enumerate() {
+                if in_sections[route]
+                    && filters.matches(&msg)
+                    && downsamplers[route].as_mut().is_none_or(|downsampler| downsampler.keep(index, &msg))
+                    && (filters.first_per() != Some(FirstPer::Lifecycle) || matched_lifecycles[route].insert(lifecycle))
+                    && dedups[route].as_mut().is_none_or(|dedup| dedup.keep(&msg)) {
+                    matched.push(route);
+                }
+            }
+
+            for (position, matched) in context_lines.add(index, lifecycle, msg, &matched) {
+                let recent = context_lines.get_mut(position);
+                let is_callstack = *recent.is_callstack.get_or_insert_with(|| {
+                    let (is_callstack, finished_callstack) = callstack_collector.add(&recent.msg);
+                    if let Some(block) = finished_callstack {
+                        exit_on_write_error(write!(stdout, "{block}"));
+                    }
+                    is_callstack
+                });
+                if is_callstack {
+                    continue;
+                }
+                let Recent { index, lifecycle, msg, .. } = &*recent;
+                let (index, lifecycle) = (*index, *lifecycle);
+
+                for route in matched {
+                    let (filters, outputs) = &routes[route];
+                    let captures = filters.find_patterns(msg);
+                    // written along the message by each output of lines
+                    let repeat_marker = dedups[route].as_mut().and_then(|dedup| dedup.take_repeats(msg))
+                        .map(|(key, repeats)| format!("last message of {key} repeated {repeats} times"));
+                    let lost_marker = lost_markers[route].then(|| counter_gaps[route].take_lost(msg)).flatten()
+                        .map(|(key, lost)| format!("{lost} messages lost in {key}"));
+                    let markers: Vec<String> = repeat_marker.into_iter().chain(lost_marker).collect();
+                    // filters without patterns have no captures, their fields are still written
+                    let extraction = captures.unwrap_or_default();
+
+                    for (sink, out) in (first_sinks[route]..).zip(outputs) {
+                        let context = FieldContext { extraction: &extraction, index, lifecycle, registry: &registry, references: out.references.as_ref(), metadata, missing: out.missing() };
+                        if let Some(export) = dlt_exports[sink].as_mut() {
+                            let result = export.write(msg, &extraction)
+                                .and_then(|_| if line_buffered { export.flush() } else { Ok(()) });
+                            if let Err(err) = result {
+                                eprintln!("error on writing dlt: {err}");
+                            }
+                            continue;
+                        }
+                        if let Some(writer) = arrow_writers[sink].as_mut() {
+                            let values: Vec<String> = out.fields.iter().map(|field| msg.format_field(field, &context)).collect();
+                            if let Err(err) = writer.write(&values, line_buffered) {
+                                eprintln!("error on writing arrow: {err}");
+                            }
+                            continue;
+                        }
+                        if let Some(writer) = sqlite_writers[sink].as_mut() {
+                            let values: Vec<String> = out.fields.iter().map(|field| msg.format_field(field, &context)).collect();
+                            if let Err(err) = writer.write(&values, line_buffered) {
+                                eprintln!("error on writing sqlite: {err}");
+                            }
+                            continue;
+                        }
+                        if let Some(writer) = parquet_writers[sink].as_mut() {
+                            if let Err(err) = writer.write(msg, &out.fields, &context) {
+                                eprintln!("error on writing parquet: {err}");
+                            }
+                            continue;
+                        }
+
+                        let delimiter = match out.output_type() {
+                            OutputType::Stdout(stdout) => stdout.delimiter,
+                            OutputType::Csv(csv) => csv.delimiter,
+                            OutputType::Dlt(_) => unreachable!("dlt output is written by the export"),
+                            OutputType::Arrow(_) => unreachable!("arrow output is written by its writer"),
+                            OutputType::Sqlite(_) => unreachable!("sqlite output is written by its writer"),
+                            OutputType::Parquet(_) => unreachable!("parquet output is written by its writer"),
+                        };
+                        let line = msg.format_line(out, delimiter, &context);
+                        match out.output_type() {
+                            OutputType::Stdout(out_stdout) => {
+                                for marker in &markers {
+                                    exit_on_write_error(writeln!(stdout, "-- {marker} --"));
                                 }
-                                result
+                                exit_on_write_error(writeln!(stdout, "{}{line}{}", out_stdout.prefix, out_stdout.suffix))
                             },
-                            OutputField::Payload => {
-                                let payload_iter = msg.payload.iter().filter(|data| match data { Value::String(_) => true, _ => false});
-                                let mut result = Ok(());
-
-                                for data in payload_iter {
-                                    let string = match data {
-                                        Value::String(string) => string,
-                                        _ => default_str,
+                            OutputType::Csv(csv) => {
+                                let shard_value = csv.shard_by().map_or("", |shard_by| msg.shard_value(shard_by));
+                                if let Some(writers) = csv_writers[sink].as_mut() {
+                                    let header = || {
+                                        let column_names = out.column_names(&msg.variables());
+                                        match out.template() {
+                                            Some(template) => template.render(column_names),
+                                            None => {
+                                                let cells: Vec<_> = column_names.iter().map(|name| csv::escape(name, delimiter)).collect();
+                                                cells.join(&delimiter.to_string())
+                                            },
+                                        }
                                     };
-                                    result = write!(&mut out_string, "{}{delimiter}", string);
-                                    if result.is_err() {
-                                        break;
+                                    if let Err(err) = writers.write(shard_value, &markers, &line, header, line_buffered) {
+                                        eprintln!("error on writing csv: {err}");
                                     }
                                 }
-                                result
-                            },
-                        };
-                        match result {
-                            Ok(_) => (),
-                            Err(err) => {
-                                eprintln!("error on constructing output to stdout: {err}");
                             },
+                            OutputType::Dlt(_) => unreachable!("dlt output is written by the export"),
+                            OutputType::Arrow(_) => unreachable!("arrow output is written by its writer"),
+                            OutputType::Sqlite(_) => unreachable!("sqlite output is written by its writer"),
+                            OutputType::Parquet(_) => unreachable!("parquet output is written by its writer"),
                         }
                     }
-                    println!("formatted out: {}", out_string.trim_end_matches(delimiter));
                 }
-            } else {
-                // TODO: make this prettier...
-                println!("{msg:?}")
             }
+            if line_buffered {
+                exit_on_write_error(stdout.flush());
+            }
+        }
+
+        // repeats at the end of the input have no message left to be reported along
+        for (route, dedup) in dedups.iter_mut().enumerate() {
+            let Some(dedup) = dedup.as_mut() else {
+                continue;
+            };
+            for (key, repeats) in dedup.take_all() {
+                let marker = format!("last message of {key} repeated {repeats} times");
+                for (sink, out) in (first_sinks[route]..).zip(&routes[route].1) {
+                    match out.output_type() {
+                        OutputType::Stdout(_) => exit_on_write_error(writeln!(stdout, "-- {marker} --")),
+                        OutputType::Csv(csv) => {
+                            let shard_value = csv.shard_by().map_or("", |shard_by| key.shard_value(shard_by));
+                            if let Some(Err(err)) = csv_writers[sink].as_mut().map(|writers| writers.comment(shard_value, &marker)) {
+                                eprintln!("error on writing csv: {err}");
+                            }
+                        },
+                        OutputType::Dlt(_) | OutputType::Arrow(_) | OutputType::Sqlite(_) | OutputType::Parquet(_) => {},
+                    }
+                }
+            }
+        }
+    });
+    if let Some(block) = callstack_collector.finish() {
+        exit_on_write_error(write!(stdout, "{block}"));
+    }
+    for export in dlt_exports.into_iter().flatten() {
+        if let Err(err) = export.finish() {
+            eprintln!("error on writing dlt: {err}");
+        }
+    }
+    for writer in arrow_writers.into_iter().flatten() {
+        if let Err(err) = writer.finish() {
+            eprintln!("error on writing arrow: {err}");
+        }
+    }
+    for writer in sqlite_writers.into_iter().flatten() {
+        if let Err(err) = writer.finish() {
+            eprintln!("error on writing sqlite: {err}");
+        }
+    }
+    for writer in parquet_writers.into_iter().flatten() {
+        if let Err(err) = writer.finish() {
+            eprintln!("error on writing parquet: {err}");
+        }
+    }
+    for writers in csv_writers.into_iter().flatten() {
+        if let Err(err) = writers.finish() {
+            eprintln!("error on writing csv: {err}");
+        }
+    }
+    exit_on_write_error(stdout.flush());
+    exit_on_failures(&failures, file_paths.len());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dlt::headers::{write_log_headers, LogHeaders};
+
+    fn headers(counter: u8, num_of_args: u8) -> LogHeaders<'static> {
+        LogHeaders {
+            seconds: 1_700_000_000,
+            microseconds: 0,
+            ecu_id: "ECU1",
+            counter,
+            session_id: None,
+            timestamp: None,
+            is_big_endian: false,
+            app_id: "APP",
+            context_id: "CTX",
+            level: MessageTypeInfoLog::Info,
+            verbose: true,
+            num_of_args,
+        }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn marks_messages_with_structs_nested_too_deeply_malformed() {
+        // a struct of one struct each, as many as fit into a message, without an end
+        let payload: Vec<u8> = [0x4000u32.to_le_bytes().as_slice(), &1u16.to_le_bytes()].concat().repeat(10_800);
+        let mut trace = vec![];
+        write_log_headers(&mut trace, &headers(0, 1), payload.len());
+        trace.extend_from_slice(&payload);
+        write_log_headers(&mut trace, &headers(1, 0), 0);
+
+        let messages: Vec<_> = TraceData::new(&trace, 0).into_iter().collect();
+        assert_eq!(messages.len(), 2);
+        assert!(messages[0].is_malformed());
+        assert!(!messages[1].is_malformed());
+    }
+}
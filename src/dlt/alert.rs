@@ -0,0 +1,289 @@
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::process;
+use std::time::{Duration, Instant, SystemTime};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_derive::Serialize;
+use crate::config::{self, Alert};
+use crate::dlt::headers::{find_message_frame, verify_message_frame};
+use crate::dlt::query::Query;
+use crate::dlt::{Message, TraceData};
+use crate::dlt::watch::{self, FileWatcher};
+
+/// Bytes read from the input at once.
+const READ_SIZE: usize = 64 * 1024;
+/// Data kept without finding a message in it, before it is dropped as not being DLT.
+const MAX_UNFRAMED_SIZE: usize = 1024 * 1024;
+
+struct CompiledAlert {
+    alert: Alert,
+    query: Query,
+    /// storage time of the last report
+    last_report: Option<f64>,
+    reported: usize,
+    suppressed: usize,
+}
+
+#[derive(Serialize)]
+struct AlertEvent<'e> {
+    alert: &'e str,
+    time: Option<String>,
+    ecu: &'e str,
+    app: Option<&'e str>,
+    ctx: Option<&'e str>,
+    payload: String,
+}
+
+#[derive(Serialize)]
+struct AlertCounts {
+    reported: usize,
+    suppressed: usize,
+}
+
+/// Counts since the previous summary.
+#[derive(Serialize)]
+struct Summary {
+    /// wall clock time the summary was written at
+    time: String,
+    seconds: f64,
+    messages: usize,
+    messages_per_second: f64,
+    bytes: u64,
+    bytes_per_second: f64,
+    skipped_bytes: usize,
+    alerts: BTreeMap<String, AlertCounts>,
+}
+
+#[derive(Serialize)]
+struct SummaryLine {
+    summary: Summary,
+}
+
+/// Reads the input as a stream and writes nothing but a JSON line per alert and a summary every
+/// `summary_interval`, for soak tests running for days. Only a read buffer is kept, the messages
+/// are checked against the alerts and dropped. With `follow`, the input is read on as it grows,
+/// like `tail -f`, otherwise the run ends with a last summary at the end of the input. While
+/// following, a rotated input is read from the start of the new file and the alerts are reloaded
+/// when the config changes; `poll` looks for these changes by polling, for network shares.
+/// The summaries are appended to `summary_output` if given, to chart the health of a capture over
+/// time, and are due at fixed intervals, so they do not drift later with the time spent reading.
+pub fn run_alerts(input: &Path, config_path: Option<&Path>, follow: bool, poll: bool, summary_interval: Duration, summary_output: Option<&Path>) {
+    let mut alerts = config_path.map(load_alerts).unwrap_or(Ok(vec![])).unwrap_or_else(|err| {
+        eprintln!("{err}");
+        process::exit(1);
+    });
+    if alerts.is_empty() {
+        eprintln!("no alerts configured");
+        process::exit(1);
+    }
+    let mut file = File::open(input).unwrap_or_else(|err| {
+        eprintln!("error on opening {input:?}: {err}");
+        process::exit(1);
+    });
+    let watcher = follow.then(|| {
+        let paths: Vec<&Path> = [Some(input), config_path].into_iter().flatten().collect();
+        FileWatcher::new(&paths, poll).unwrap_or_else(|err| {
+            eprintln!("error on watching {paths:?}: {err}");
+            process::exit(1);
+        })
+    });
+
+    let mut summary_out: Option<File> = summary_output.map(|summary_output| {
+        OpenOptions::new().create(true).append(true).open(summary_output).unwrap_or_else(|err| {
+            eprintln!("error on opening {summary_output:?}: {err}");
+            process::exit(1);
+        })
+    });
+
+    let mut stdout = io::stdout().lock();
+    let mut buffer = Vec::with_capacity(2 * READ_SIZE);
+    let mut position = 0;
+    let mut messages = 0;
+    let mut skipped_bytes = 0;
+    let mut bytes = 0;
+    let mut last_summary = Instant::now();
+    let mut next_summary = last_summary + summary_interval;
+    loop {
+        let start = buffer.len();
+        buffer.resize(start + READ_SIZE, 0);
+        let read = match file.read(&mut buffer[start..]) {
+            Ok(read) => read,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => 0,
+            Err(err) => {
+                eprintln!("error on reading {input:?}: {err}");
+                process::exit(1);
+            },
+        };
+        buffer.truncate(start + read);
+        position += read as u64;
+        bytes += read as u64;
+
+        let (end, skipped) = complete_messages(&buffer);
+        skipped_bytes += skipped;
+        for msg in &TraceData::new(&buffer[..end], 0) {
+            messages += 1;
+            for alert in alerts.iter_mut().filter(|alert| alert.query.matches(&msg)) {
+                alert.report(&msg, &mut stdout);
+            }
+        }
+        buffer.drain(..end);
+        if buffer.len() > MAX_UNFRAMED_SIZE {
+            skipped_bytes += buffer.len();
+            buffer.clear();
+        }
+
+        let is_done = read == 0 && !follow;
+        let now = Instant::now();
+        if is_done || now >= next_summary {
+            let summary = summary(&mut alerts, now - last_summary, messages, bytes, skipped_bytes);
+            match summary_out.as_mut() {
+                Some(summary_out) => write_line(summary_out, &summary),
+                None => write_line(&mut stdout, &summary),
+            }
+            messages = 0;
+            bytes = 0;
+            skipped_bytes = 0;
+            last_summary = now;
+            // the next one on schedule, skipping the ones missed while busy
+            while next_summary <= now {
+                next_summary += summary_interval.max(Duration::from_millis(1));
+            }
+        }
+        if is_done {
+            if !buffer.is_empty() {
+                eprintln!("{input:?}: the input ends in a truncated message of {} bytes", buffer.len());
+            }
+            break;
+        }
+        let Some(watcher) = watcher.as_ref().filter(|_| read == 0) else {
+            continue;
+        };
+
+        // the end of the input is reached, wait for it to grow, be rotated or the config to change
+        let changed = watcher.wait(watch::POLL_INTERVAL.min(next_summary.saturating_duration_since(Instant::now())));
+        if let Some(config_path) = config_path.filter(|config_path| config_path.canonicalize().is_ok_and(|path| changed.contains(&path.as_path()))) {
+            match load_alerts(config_path) {
+                Ok(reloaded) if !reloaded.is_empty() => {
+                    eprintln!("reloaded {} alerts from {config_path:?}", reloaded.len());
+                    alerts = carry_over(alerts, reloaded);
+                },
+                Ok(_) => eprintln!("{config_path:?} has no alerts, keeping the previous ones"),
+                Err(err) => eprintln!("{err}, keeping the previous alerts"),
+            }
+        }
+        if watch::is_replaced(&file, input, position) {
+            match File::open(input) {
+                Ok(new_file) => {
+                    eprintln!("{input:?} was rotated, reading the new file from its start");
+                    if !buffer.is_empty() {
+                        skipped_bytes += buffer.len();
+                        buffer.clear();
+                    }
+                    file = new_file;
+                    position = 0;
+                },
+                Err(err) => eprintln!("error on opening rotated {input:?}: {err}"),
+            }
+        }
+    }
+}
+
+fn load_alerts(config_path: &Path) -> Result<Vec<CompiledAlert>, String> {
+    let config = config::read_config(config_path).map_err(|err| format!("error in reading config {config_path:?}: {err}"))?;
+    config.alerts().iter().flatten().map(|alert| {
+        let query = Query::compile(alert.condition()).map_err(|err| format!("invalid condition of alert '{}': {err}", alert.name()))?;
+        Ok(CompiledAlert { alert: alert.clone(), query, last_report: None, reported: 0, suppressed: 0 })
+    }).collect()
+}
+
+/// Keeps the cooldown and the counts since the last summary of the alerts still configured.
+fn carry_over(previous: Vec<CompiledAlert>, mut reloaded: Vec<CompiledAlert>) -> Vec<CompiledAlert> {
+    for alert in &mut reloaded {
+        if let Some(old) = previous.iter().find(|old| old.alert.name() == alert.alert.name()) {
+            alert.last_report = old.last_report;
+            alert.reported = old.reported;
+            alert.suppressed = old.suppressed;
+        }
+    }
+    reloaded
+}
+
+/// Length of the complete messages at the start of the data and the bytes among them that are
+/// not part of a message. A truncated message at the end is left for the next read.
+fn complete_messages(data: &[u8]) -> (usize, usize) {
+    let mut end = 0;
+    let mut skipped = 0;
+    while end < data.len() {
+        match verify_message_frame(data, end) {
+            Ok(size) => end += size,
+            Err(_) => match find_message_frame(data, end + 1) {
+                Some(next) => {
+                    skipped += next - end;
+                    end = next;
+                },
+                None => break,
+            },
+        }
+    }
+    (end, skipped)
+}
+
+impl CompiledAlert {
+    fn report(&mut self, msg: &Message, out: &mut impl Write) {
+        let time = msg.storage_header.time();
+        if self.last_report.is_some_and(|last| time - last < self.alert.cooldown()) {
+            self.suppressed += 1;
+            return;
+        }
+        self.last_report = Some(time);
+        self.reported += 1;
+
+        let header = msg.extended_header.as_ref();
+        let event = AlertEvent {
+            alert: self.alert.name(),
+            time: msg.storage_header.rfc3339(),
+            ecu: msg.ecu_id(),
+            app: header.map(|header| header.app_id().as_str()),
+            ctx: header.map(|header| header.context_id().as_str()),
+            payload: msg.payload_text(),
+        };
+        write_line(out, &event);
+    }
+}
+
+/// Takes the counts of the alerts since the last summary.
+fn summary(alerts: &mut [CompiledAlert], elapsed: Duration, messages: usize, bytes: u64, skipped_bytes: usize) -> SummaryLine {
+    let alerts = alerts.iter_mut().map(|alert| {
+        let counts = AlertCounts { reported: alert.reported, suppressed: alert.suppressed };
+        alert.reported = 0;
+        alert.suppressed = 0;
+        (alert.alert.name().clone(), counts)
+    }).collect();
+    let seconds = elapsed.as_secs_f64();
+    let rate = |count: f64| if seconds > 0.0 { count / seconds } else { 0.0 };
+    SummaryLine {
+        summary: Summary {
+            time: DateTime::<Utc>::from(SystemTime::now()).to_rfc3339_opts(SecondsFormat::Millis, true),
+            seconds,
+            messages,
+            messages_per_second: rate(messages as f64),
+            bytes,
+            bytes_per_second: rate(bytes as f64),
+            skipped_bytes,
+            alerts,
+        },
+    }
+}
+
+/// Writes the JSON line and flushes it, alerts are watched for as they happen.
+fn write_line(out: &mut impl Write, line: &impl serde::Serialize) {
+    let result = serde_json::to_writer(&mut *out, line).map_err(io::Error::from)
+        .and_then(|_| writeln!(out))
+        .and_then(|_| out.flush());
+    if let Err(err) = result {
+        eprintln!("error on writing output: {err}");
+        process::exit(1);
+    }
+}
@@ -0,0 +1,73 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use crate::dlt::headers::{MessageType, MessageTypeInfoAppTrace};
+use crate::dlt::Message;
+use crate::dlt::payload::Value;
+
+/// Function call reconstructed from a FunctionIn and the matching FunctionOut message.
+#[derive(Debug)]
+pub struct Call {
+    pub ecu_id: String,
+    pub app_id: String,
+    pub context_id: String,
+    pub session_id: Option<u32>,
+    pub name: String,
+    /// microseconds
+    pub start: f64,
+    /// microseconds
+    pub end: f64,
+}
+
+impl Call {
+    /// Duration in microseconds.
+    pub fn duration(&self) -> f64 {
+        self.end - self.start
+    }
+}
+
+impl Display for Call {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let session = self.session_id.map_or_else(|| "-".to_string(), |session_id| session_id.to_string());
+        write!(f, "{} {}/{} session {session}: {} {:.3}ms", self.ecu_id, self.app_id, self.context_id, self.name, self.duration() / 1000.0)
+    }
+}
+
+type CallKey = (String, String, String, Option<u32>);
+
+/// Pairs FunctionIn/FunctionOut messages per session. Nested calls are matched like a call stack.
+#[derive(Default)]
+pub struct CallTracker {
+    open_calls: HashMap<CallKey, Vec<(String, f64)>>,
+}
+
+impl CallTracker {
+    pub fn new() -> CallTracker {
+        CallTracker::default()
+    }
+
+    /// Returns the finished call if the message is the FunctionOut of an open call.
+    pub fn add(&mut self, msg: &Message) -> Option<Call> {
+        let header = msg.extended_header.as_ref()?;
+        if !matches!(header.msg_type(), MessageType::AppTrace) {
+            return None;
+        }
+
+        let key = (msg.storage_header.ecu_id().clone(), header.app_id().clone(), header.context_id().clone(), msg.standard_header.session_id());
+        match header.msg_type_info_app_trace()? {
+            MessageTypeInfoAppTrace::FunctionIn => {
+                let name = msg.payload.iter().find_map(|value| match value.value() {
+                    Value::String(name) => Some(name.to_string()),
+                    _ => None,
+                }).unwrap_or_else(|| "function".to_string());
+                self.open_calls.entry(key).or_default().push((name, msg.time()));
+                None
+            },
+            MessageTypeInfoAppTrace::FunctionOut => {
+                let (name, start) = self.open_calls.get_mut(&key)?.pop()?;
+                let (ecu_id, app_id, context_id, session_id) = key;
+                Some(Call { ecu_id, app_id, context_id, session_id, name, start, end: msg.time() })
+            },
+            _ => None,
+        }
+    }
+}
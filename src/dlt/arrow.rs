@@ -0,0 +1,67 @@
+use std::io::{self, BufWriter, Write};
+use std::sync::Arc;
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_array::builder::StringBuilder;
+use arrow_ipc::writer::StreamWriter;
+use arrow_schema::{ArrowError, DataType, Field, Schema, SchemaRef};
+use crate::dlt::chaos::SinkFile;
+use crate::{Arrow, OutputField};
+
+/// Rows buffered before they are written as one record batch.
+const BATCH_ROWS: usize = 4096;
+
+/// Writes the rows of the arrow output as IPC stream, with one string column per field.
+pub struct ArrowWriter {
+    writer: StreamWriter<Box<dyn Write>>,
+    schema: SchemaRef,
+    columns: Vec<StringBuilder>,
+    rows: usize,
+}
+
+impl ArrowWriter {
+    pub fn create(arrow: &Arrow, fields: &[OutputField]) -> Result<ArrowWriter, ArrowError> {
+        let schema = Arc::new(Schema::new(fields.iter()
+            .map(|field| Field::new(field.name(), DataType::Utf8, false))
+            .collect::<Vec<_>>()));
+        let out: Box<dyn Write> = match &arrow.file_path {
+            Some(file_path) => Box::new(BufWriter::new(SinkFile::create(file_path)?)),
+            None => Box::new(io::stdout()),
+        };
+        let writer = StreamWriter::try_new(out, &schema)?;
+        let columns = fields.iter().map(|_| StringBuilder::new()).collect();
+        Ok(ArrowWriter { writer, schema, columns, rows: 0 })
+    }
+
+    /// Adds the row, a value per field. The rows are written in batches, unless `flush` is set.
+    pub fn write(&mut self, values: &[String], flush: bool) -> Result<(), ArrowError> {
+        for (column, value) in self.columns.iter_mut().zip(values) {
+            column.append_value(value);
+        }
+        self.rows += 1;
+        if flush || self.rows >= BATCH_ROWS {
+            self.write_batch()?;
+        }
+        if flush {
+            self.writer.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Writes the remaining rows and the end of the stream.
+    pub fn finish(mut self) -> Result<(), ArrowError> {
+        self.write_batch()?;
+        self.writer.finish()?;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn write_batch(&mut self) -> Result<(), ArrowError> {
+        if self.rows == 0 {
+            return Ok(());
+        }
+        let arrays: Vec<ArrayRef> = self.columns.iter_mut().map(|column| Arc::new(column.finish()) as ArrayRef).collect();
+        let batch = RecordBatch::try_new(self.schema.clone(), arrays)?;
+        self.rows = 0;
+        self.writer.write(&batch)
+    }
+}
@@ -0,0 +1,88 @@
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+use std::process;
+use regex::bytes::Regex;
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+use crate::dlt::stats::Stats;
+use crate::dlt::warning::{self, Warning};
+use crate::dlt::{map_file, FileFailure, StatsSummary, TraceData};
+
+/// Entries of the archive besides the trace and the config.
+const SUMMARY_NAME: &str = "summary.json";
+const RECOVERY_LOG_NAME: &str = "recovery.jsonl";
+
+/// Packages what a supplier needs to look into a problem into a zip archive: the messages of the
+/// input within the time window, with the matches of the `redactions` in their payloads
+/// overwritten, the config used, the statistics of the messages packaged and the warnings about
+/// corrupt data skipped in the input. The matches are overwritten with `*` of the same length, so
+/// the messages keep their size and are not decoded and encoded again.
+pub fn run_bundle(file_path: &Path, config_path: Option<&Path>, from: Option<f64>, to: Option<f64>, redactions: &[Regex], output_path: &Path) {
+    let data = map_file(file_path).unwrap_or_else(|err| {
+        eprintln!("{file_path:?}: {err}");
+        process::exit(1);
+    });
+
+    let mut trace = vec![];
+    let mut stats = Stats::new();
+    let mut redacted = 0;
+    let trace_data = TraceData::new(&data, 0);
+    let mut messages = trace_data.iter();
+    for msg in &mut messages {
+        let time = msg.storage_header.time();
+        if from.is_some_and(|from| time < from) || to.is_some_and(|to| time > to) {
+            continue;
+        }
+        stats.add(&msg);
+        let start = trace.len();
+        trace.extend_from_slice(msg.bytes());
+        let payload_start = trace.len().saturating_sub(msg.payload_len()).max(start);
+        redacted += redact(&mut trace[payload_start..], redactions);
+    }
+    let failures: Vec<FileFailure> = messages.error().iter()
+        .map(|error| FileFailure { file: file_path.to_path_buf(), error: error.clone() })
+        .collect();
+
+    let summary = StatsSummary { stats: &stats, failures: &failures };
+    let result = write_archive(output_path, file_path, &trace, config_path, &summary, messages.warnings());
+    if let Err(err) = result {
+        eprintln!("error on writing bundle {output_path:?}: {err}");
+        process::exit(1);
+    }
+    eprintln!("bundled {} messages to {output_path:?}, redacted {redacted} matches", stats.messages());
+}
+
+fn write_archive(output_path: &Path, file_path: &Path, trace: &[u8], config_path: Option<&Path>, summary: &StatsSummary, warnings: &[Warning]) -> Result<(), Box<dyn Error>> {
+    let mut archive = ZipWriter::new(File::create(output_path)?);
+    let options = SimpleFileOptions::default();
+    let trace_name = file_path.file_name().map_or("trace.dlt".into(), |name| name.to_string_lossy());
+    archive.start_file(trace_name, options)?;
+    archive.write_all(trace)?;
+    if let Some(config_path) = config_path {
+        let config_name = config_path.file_name().map_or("config.toml".into(), |name| name.to_string_lossy());
+        archive.start_file(config_name, options)?;
+        archive.write_all(&fs::read(config_path)?)?;
+    }
+    archive.start_file(SUMMARY_NAME, options)?;
+    serde_json::to_writer_pretty(&mut archive, summary)?;
+    archive.start_file(RECOVERY_LOG_NAME, options)?;
+    warning::write_records(&mut archive, file_path, warnings)?;
+    archive.finish()?.flush()?;
+    Ok(())
+}
+
+/// Overwrites the matches of the patterns in the payload, returns how many there were.
+fn redact(payload: &mut [u8], redactions: &[Regex]) -> usize {
+    let mut count = 0;
+    for redaction in redactions {
+        let matches: Vec<_> = redaction.find_iter(payload).map(|found| found.range()).collect();
+        count += matches.len();
+        for range in matches {
+            payload[range].fill(b'*');
+        }
+    }
+    count
+}
+
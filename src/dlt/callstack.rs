@@ -0,0 +1,96 @@
+use std::fmt::{Display, Formatter};
+use regex::Regex;
+use crate::config::Callstack;
+use crate::dlt::Message;
+use crate::dlt::payload::Value;
+
+/// A callstack dump collected from consecutive messages of a crash reporter context.
+pub struct CallstackBlock {
+    ecu_id: String,
+    app_id: String,
+    context_id: String,
+    text: Vec<String>,
+    frames: Vec<String>,
+}
+
+impl Display for CallstackBlock {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "callstack {} {}/{} ({} frames):", self.ecu_id, self.app_id, self.context_id, self.frames.len())?;
+        for line in &self.text {
+            writeln!(f, "  {line}")?;
+        }
+        for (idx, frame) in self.frames.iter().enumerate() {
+            writeln!(f, "  #{idx:<3} {frame}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Gathers the messages of the configured crash reporter contexts into one block per dump.
+/// A dump ends as soon as a message of another context shows up.
+pub struct CallstackCollector<'c> {
+    callstacks: &'c [Callstack],
+    address: Regex,
+    current: Option<CallstackBlock>,
+}
+
+impl<'c> CallstackCollector<'c> {
+    pub fn new(callstacks: &'c [Callstack]) -> CallstackCollector<'c> {
+        CallstackCollector {
+            callstacks,
+            address: Regex::new(r"\b(?:0[xX])?[0-9a-fA-F]{8,16}\b").unwrap(),
+            current: None,
+        }
+    }
+
+    fn is_callstack(&self, app_id: &str, context_id: &str) -> bool {
+        self.callstacks.iter().any(|callstack| {
+            callstack.app_id().as_ref().is_none_or(|id| id == app_id)
+                && callstack.context_id().as_ref().is_none_or(|id| id == context_id)
+        })
+    }
+
+    /// Takes the message if it belongs to a callstack dump. Returns `true` if the message was
+    /// consumed, and the finished previous dump if the message ended it.
+    pub fn add(&mut self, msg: &Message) -> (bool, Option<CallstackBlock>) {
+        let Some(header) = &msg.extended_header else {
+            return (false, self.current.take());
+        };
+        if !self.is_callstack(header.app_id(), header.context_id()) {
+            return (false, self.current.take());
+        }
+
+        let ecu_id = msg.storage_header.ecu_id();
+        let finished = match &self.current {
+            Some(block) if block.ecu_id != *ecu_id || block.app_id != *header.app_id() || block.context_id != *header.context_id() => self.current.take(),
+            _ => None,
+        };
+        let block = self.current.get_or_insert_with(|| CallstackBlock {
+            ecu_id: ecu_id.clone(),
+            app_id: header.app_id().clone(),
+            context_id: header.context_id().clone(),
+            text: vec![],
+            frames: vec![],
+        });
+
+        for value in &msg.payload {
+            if let Value::String(string) = value.value() {
+                let frames : Vec<_> = self.address.find_iter(string).map(|address| address.as_str()).collect();
+                if frames.is_empty() {
+                    block.text.push(string.to_string());
+                } else {
+                    block.frames.extend(frames.iter().map(|frame| {
+                        let digits = frame.trim_start_matches("0x").trim_start_matches("0X");
+                        format!("0x{}", digits.to_lowercase())
+                    }));
+                }
+            }
+        }
+        (true, finished)
+    }
+
+    /// Returns the dump still being collected at the end of the input.
+    pub fn finish(&mut self) -> Option<CallstackBlock> {
+        self.current.take()
+    }
+}
@@ -0,0 +1,230 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::path::Path;
+use crate::dlt::headers::{MessageType, MessageTypeInfoNetworkTrace};
+use crate::dlt::payload::Value;
+use crate::dlt::{dbc, Message};
+
+/// Flag of the CAN id marking 29 bit ids, as set by SocketCAN.
+const EXTENDED_FRAME_FLAG: u32 = 0x8000_0000;
+const EXTENDED_ID_MASK: u32 = 0x1FFF_FFFF;
+const STANDARD_ID_MASK: u32 = 0x7FF;
+
+/// CAN frame carried by a network trace message, with its signals if the frame is described in
+/// the DBC file.
+#[derive(Debug)]
+pub struct CanFrame<'d> {
+    pub id: u32,
+    pub is_extended: bool,
+    pub data: &'d [u8],
+    /// name of the frame in the DBC file
+    pub name: Option<&'static str>,
+    pub signals: Vec<CanSignalValue>,
+}
+
+#[derive(Debug)]
+pub struct CanSignalValue {
+    pub name: &'static str,
+    pub value: f64,
+    pub unit: &'static str,
+}
+
+impl<'d> CanFrame<'d> {
+    /// Decodes the CAN network trace messages. The first raw argument holds the CAN id as 32 bit
+    /// big endian value, with the SocketCAN flag for extended ids, the second one the data. If
+    /// the message has a single raw argument, it holds the id followed by the data.
+    pub fn from_message(msg: &Message<'d>) -> Option<CanFrame<'d>> {
+        let header = msg.extended_header.as_ref()?;
+        if !matches!(header.msg_type(), MessageType::NetworkTrace) || !matches!(header.msg_type_info_network_trace(), Some(MessageTypeInfoNetworkTrace::Can)) {
+            return None;
+        }
+
+        let mut raw_arguments = msg.payload.iter().filter_map(|value| match value.value() {
+            Value::Raw(data) => Some(*data),
+            _ => None,
+        });
+        let (id_bytes, data) = match (raw_arguments.next()?, raw_arguments.next()) {
+            (header, Some(data)) => (header.get(header.len().checked_sub(4)?..)?, data),
+            (data, None) => (data.get(..4)?, &data[4..]),
+        };
+        let raw_id = u32::from_be_bytes(id_bytes.try_into().ok()?);
+        let is_extended = raw_id & EXTENDED_FRAME_FLAG != 0 || raw_id > STANDARD_ID_MASK;
+        let id = raw_id & EXTENDED_ID_MASK;
+
+        let description = dbc().and_then(|dbc| dbc.messages.get(&id));
+        let signals = description.map(|description| description.decode(data)).unwrap_or_default();
+        Some(CanFrame { id, is_extended, data, name: description.map(|description| description.name.as_str()), signals })
+    }
+}
+
+impl Display for CanFrame<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.is_extended {
+            write!(f, "CAN 0x{:08x}", self.id)?;
+        } else {
+            write!(f, "CAN 0x{:03x}", self.id)?;
+        }
+        if let Some(name) = self.name {
+            write!(f, " {name}")?;
+        }
+        write!(f, " [{}]", self.data.len())?;
+        self.data.iter().try_for_each(|byte| write!(f, " {byte:02x}"))?;
+        for signal in &self.signals {
+            write!(f, " {}={}", signal.name, signal.value)?;
+            if !signal.unit.is_empty() {
+                write!(f, " {}", signal.unit)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Multiplexing {
+    None,
+    /// the signal selects which of the multiplexed signals the frame carries
+    Multiplexor,
+    /// the signal is only present if the multiplexor has this value
+    Multiplexed(u64),
+}
+
+#[derive(Debug)]
+struct CanSignal {
+    name: String,
+    start_bit: usize,
+    length: usize,
+    is_little_endian: bool,
+    is_signed: bool,
+    factor: f64,
+    offset: f64,
+    unit: String,
+    multiplexing: Multiplexing,
+}
+
+impl CanSignal {
+    /// Parses a signal line: `SG_ name [M|mN] : start|length@order sign (factor,offset) [min|max] "unit" receivers`.
+    fn parse(line: &str) -> Option<CanSignal> {
+        let (definition, layout) = line.strip_prefix("SG_")?.split_once(':')?;
+        let mut definition = definition.split_whitespace();
+        let name = definition.next()?.to_string();
+        let multiplexing = match definition.next() {
+            None => Multiplexing::None,
+            Some("M") => Multiplexing::Multiplexor,
+            Some(multiplexed) => Multiplexing::Multiplexed(multiplexed.strip_prefix('m')?.parse().ok()?),
+        };
+
+        let layout = layout.trim();
+        let (position, rest) = layout.split_once(' ')?;
+        let (start_bit, position) = position.split_once('|')?;
+        let (length, order) = position.split_once('@')?;
+        let (factor, rest) = rest.trim_start().strip_prefix('(')?.split_once(')')?;
+        let (factor, offset) = factor.split_once(',')?;
+        let unit = rest.split('"').nth(1).unwrap_or_default().to_string();
+
+        Some(CanSignal {
+            name,
+            start_bit: start_bit.parse().ok()?,
+            length: length.parse().ok().filter(|length| (1..=64).contains(length))?,
+            is_little_endian: order.starts_with('1'),
+            is_signed: order.ends_with('-'),
+            factor: factor.trim().parse().ok()?,
+            offset: offset.trim().parse().ok()?,
+            unit,
+            multiplexing,
+        })
+    }
+
+    /// Raw value of the signal, `None` if it reaches beyond the data.
+    fn raw(&self, data: &[u8]) -> Option<u64> {
+        let bit = |position: usize| data.get(position / 8).map(|byte| u64::from(byte >> (position % 8) & 1));
+        let mut raw = 0;
+        if self.is_little_endian {
+            for idx in 0..self.length {
+                raw |= bit(self.start_bit + idx)? << idx;
+            }
+        } else {
+            // big endian signals start at their most significant bit and continue with the
+            // next lower bit, wrapping to the most significant bit of the next byte
+            let mut position = self.start_bit;
+            for _ in 0..self.length {
+                raw = raw << 1 | bit(position)?;
+                position = if position.is_multiple_of(8) { position + 15 } else { position - 1 };
+            }
+        }
+        Some(raw)
+    }
+
+    fn value(&self, raw: u64) -> f64 {
+        let raw = if self.is_signed && self.length < 64 && raw >> (self.length - 1) & 1 == 1 {
+            (raw | !0 << self.length) as i64 as f64
+        } else if self.is_signed {
+            raw as i64 as f64
+        } else {
+            raw as f64
+        };
+        raw * self.factor + self.offset
+    }
+}
+
+#[derive(Debug)]
+struct CanMessage {
+    name: String,
+    signals: Vec<CanSignal>,
+}
+
+impl CanMessage {
+    fn decode(&'static self, data: &[u8]) -> Vec<CanSignalValue> {
+        let multiplexor = self.signals.iter()
+            .find(|signal| signal.multiplexing == Multiplexing::Multiplexor)
+            .and_then(|signal| signal.raw(data));
+        self.signals.iter()
+            .filter(|signal| match signal.multiplexing {
+                Multiplexing::Multiplexed(value) => multiplexor == Some(value),
+                _ => true,
+            })
+            .filter_map(|signal| Some(CanSignalValue { name: &signal.name, value: signal.value(signal.raw(data)?), unit: &signal.unit }))
+            .collect()
+    }
+}
+
+/// Frames and signals of a DBC file, used to decode the data of CAN frames.
+#[derive(Debug, Default)]
+pub struct Dbc {
+    messages: HashMap<u32, CanMessage>,
+}
+
+impl Dbc {
+    /// Reads the messages (`BO_`) and their signals (`SG_`), everything else is ignored.
+    pub fn read(file_path: &Path) -> Result<Dbc, String> {
+        let contents = fs::read(file_path).map_err(|err| format!("error on reading DBC file {file_path:?}: {err}"))?;
+        // DBC files are often written in a Windows code page, names and units are ASCII anyway
+        let contents = String::from_utf8_lossy(&contents);
+
+        let mut messages = HashMap::new();
+        let mut current: Option<(u32, CanMessage)> = None;
+        for (line_number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if let Some(definition) = line.strip_prefix("BO_ ") {
+                let mut parts = definition.split_whitespace();
+                let id = parts.next().and_then(|id| id.parse::<u32>().ok());
+                let name = parts.next().and_then(|name| name.strip_suffix(':'));
+                let (Some(id), Some(name)) = (id, name) else {
+                    return Err(format!("{file_path:?}:{}: invalid message definition: {line}", line_number + 1));
+                };
+                messages.extend(current.take());
+                current = Some((id & EXTENDED_ID_MASK, CanMessage { name: name.to_string(), signals: vec![] }));
+            } else if line.starts_with("SG_ ") {
+                let signal = CanSignal::parse(line)
+                    .ok_or_else(|| format!("{file_path:?}:{}: invalid signal definition: {line}", line_number + 1))?;
+                if let Some((_, message)) = current.as_mut() {
+                    message.signals.push(signal);
+                }
+            } else if !line.is_empty() {
+                messages.extend(current.take());
+            }
+        }
+        messages.extend(current);
+        Ok(Dbc { messages })
+    }
+}
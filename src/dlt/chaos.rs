@@ -0,0 +1,83 @@
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::OnceLock;
+use std::thread;
+use std::time::Duration;
+use clap::ValueEnum;
+
+/// Fault injected into the files the outputs write, by the hidden `--chaos` option of the
+/// integration tests, to see how the sinks cope with it. The csv, DLT, arrow and parquet files
+/// are written through `SinkFile`; the sqlite database is written by the sqlite library, so its
+/// writer checks each row with `write_row` instead. Network errors are not injected, as no
+/// output sends to the network yet.
+#[derive(ValueEnum,Debug,Clone,Copy,PartialEq)]
+pub enum Chaos {
+    /// writes fail with "no space left on device" once a file holds `DISK_FULL_BYTES`
+    DiskFull,
+    /// each write to a file takes `SLOW_SINK_DELAY` longer
+    SlowSink,
+}
+
+const DISK_FULL_BYTES: u64 = 64 * 1024;
+const SLOW_SINK_DELAY: Duration = Duration::from_millis(20);
+
+static CHAOS: OnceLock<Chaos> = OnceLock::new();
+
+/// Sets the fault of all output files, before any is created.
+pub fn set_chaos(chaos: Chaos) {
+    let _ = CHAOS.set(chaos);
+}
+
+/// Applies the fault to a write of `len` bytes to a sink already holding `written` bytes,
+/// returning how many of them fit.
+fn inject(written: u64, len: usize) -> io::Result<usize> {
+    match CHAOS.get() {
+        Some(Chaos::DiskFull) => {
+            let space = DISK_FULL_BYTES.saturating_sub(written) as usize;
+            if space == 0 && len > 0 {
+                return Err(io::Error::new(io::ErrorKind::StorageFull, "no space left on device (simulated)"));
+            }
+            Ok(len.min(space))
+        },
+        Some(Chaos::SlowSink) => {
+            thread::sleep(SLOW_SINK_DELAY);
+            Ok(len)
+        },
+        None => Ok(len),
+    }
+}
+
+/// Applies the fault to a row of `len` bytes written to a sink already holding `written` bytes,
+/// for sinks that cannot write part of a row. Fails unless all of it fits.
+pub fn write_row(written: u64, len: usize) -> io::Result<()> {
+    if inject(written, len)? < len {
+        return Err(io::Error::new(io::ErrorKind::StorageFull, "no space left on device (simulated)"));
+    }
+    Ok(())
+}
+
+/// File written by an output, suffering the fault set with `set_chaos`.
+pub struct SinkFile {
+    file: File,
+    written: u64,
+}
+
+impl SinkFile {
+    pub fn create(path: &Path) -> io::Result<SinkFile> {
+        Ok(SinkFile { file: File::create(path)?, written: 0 })
+    }
+}
+
+impl Write for SinkFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = inject(self.written, buf.len())?;
+        let written = self.file.write(&buf[..len])?;
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
@@ -0,0 +1,66 @@
+use std::env;
+use std::io;
+use terminal_size::{terminal_size_of, Width};
+use crate::dlt::{FieldContext, Message};
+use crate::OutputField;
+
+const ELLIPSIS: char = '…';
+
+/// Aligned output of messages for reading them on the terminal: the id columns have a fixed
+/// width and the line is cut to the width of the terminal.
+pub struct Console {
+    /// width of the terminal, `None` if the output does not go to one
+    width: Option<usize>,
+}
+
+impl Console {
+    /// Takes the width of the terminal stdout is written to, or `COLUMNS` if stdout is redirected,
+    /// e.g. to a pager. Without either, lines are not cut.
+    pub fn new() -> Console {
+        let width = terminal_size_of(io::stdout()).map(|(Width(width), _)| usize::from(width))
+            .or_else(|| env::var("COLUMNS").ok().and_then(|columns| columns.parse().ok()))
+            .filter(|width| *width > 0);
+        Console { width }
+    }
+
+    /// Width of the column of the field, `None` if the field takes the width of its value.
+    fn column_width(field: &OutputField) -> Option<usize> {
+        match field {
+            OutputField::Ecu | OutputField::App | OutputField::Ctx => Some(4),
+            // seconds since boot with four decimal places, up to 99999 seconds
+            OutputField::Timestamp => Some(10),
+            OutputField::Time => Some(27),
+            OutputField::Session => Some(10),
+            OutputField::Type => Some(11),
+            OutputField::PayloadLen => Some(5),
+            OutputField::Noar => Some(3),
+            _ => None,
+        }
+    }
+
+    /// Formats the message with each field in its column, the numbers right aligned, and cuts the
+    /// line with an ellipsis if it does not fit the terminal.
+    pub fn format(&self, msg: &Message, index: usize, fields: &[OutputField], context: &FieldContext) -> String {
+        let mut line = format!("{index:>7}");
+        for field in fields {
+            let value = msg.format_fields(std::slice::from_ref(field), ' ', context);
+            let value = value.trim_end_matches(' ');
+            line.push(' ');
+            match Console::column_width(field) {
+                Some(width) if matches!(field, OutputField::Timestamp | OutputField::Session | OutputField::PayloadLen | OutputField::Noar) => line.push_str(&format!("{value:>width$}")),
+                Some(width) => line.push_str(&format!("{value:<width$}")),
+                None => line.push_str(value),
+            }
+        }
+        let line = line.trim_end();
+
+        match self.width {
+            Some(width) if line.chars().count() > width => {
+                let mut cut: String = line.chars().take(width.saturating_sub(1)).collect();
+                cut.push(ELLIPSIS);
+                cut
+            },
+            _ => line.to_string(),
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use std::collections::{BTreeMap, VecDeque};
+use crate::dlt::Message;
+
+/// A message recently read, with the routes it was written to.
+pub struct Recent<'d> {
+    pub index: usize,
+    pub lifecycle: usize,
+    pub msg: Message<'d>,
+    /// whether the message was taken by the callstack collector, known once it is written
+    pub is_callstack: Option<bool>,
+    written: Vec<bool>,
+}
+
+/// Adds the messages around a match to the output of a route, like `grep -C`: the `before`
+/// messages preceding it and the `after` messages following it. A message is written at most
+/// once per route, even if it is close to several matches.
+pub struct ContextLines<'d> {
+    /// per route
+    before: Vec<usize>,
+    after: Vec<usize>,
+    /// following messages still to be written per route
+    remaining: Vec<usize>,
+    recent: VecDeque<Recent<'d>>,
+}
+
+impl<'d> ContextLines<'d> {
+    /// Takes the messages before and after the matches of each route.
+    pub fn new(context: Vec<(usize, usize)>) -> ContextLines<'d> {
+        let (before, after): (Vec<_>, Vec<_>) = context.into_iter().unzip();
+        let remaining = vec![0; before.len()];
+        ContextLines { before, after, remaining, recent: VecDeque::new() }
+    }
+
+    /// Adds the message with the routes it matched. Returns the positions of the messages to
+    /// write now, in the order read, with the routes to write each to.
+    pub fn add(&mut self, index: usize, lifecycle: usize, msg: Message<'d>, matched: &[usize]) -> Vec<(usize, Vec<usize>)> {
+        let kept = self.before.iter().max().copied().unwrap_or(0) + 1;
+        while self.recent.len() >= kept {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(Recent { index, lifecycle, msg, is_callstack: None, written: vec![false; self.before.len()] });
+
+        let last = self.recent.len() - 1;
+        let mut writes: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+        for route in 0..self.before.len() {
+            let positions = if matched.contains(&route) {
+                self.remaining[route] = self.after[route];
+                last.saturating_sub(self.before[route])..=last
+            } else if self.remaining[route] > 0 {
+                self.remaining[route] -= 1;
+                last..=last
+            } else {
+                continue;
+            };
+            for position in positions {
+                let recent = &mut self.recent[position];
+                if !recent.written[route] {
+                    recent.written[route] = true;
+                    writes.entry(position).or_default().push(route);
+                }
+            }
+        }
+        writes.into_iter().collect()
+    }
+
+    pub fn get_mut(&mut self, position: usize) -> &mut Recent<'d> {
+        &mut self.recent[position]
+    }
+}
@@ -0,0 +1,417 @@
+use std::fmt::{Display, Formatter};
+use std::mem;
+use std::str;
+use crate::dlt::headers::{MessageType, MessageTypeInfoControl};
+use crate::dlt::Message;
+use crate::dlt::payload::Value;
+
+pub const SERVICE_ID_SET_LOG_LEVEL: u32 = 0x01;
+pub const SERVICE_ID_SET_TRACE_STATUS: u32 = 0x02;
+pub const SERVICE_ID_GET_LOG_INFO: u32 = 0x03;
+pub const SERVICE_ID_GET_DEFAULT_LOG_LEVEL: u32 = 0x04;
+pub const SERVICE_ID_STORE_CONFIGURATION: u32 = 0x05;
+pub const SERVICE_ID_RESET_TO_FACTORY_DEFAULT: u32 = 0x06;
+pub const SERVICE_ID_SET_MESSAGE_FILTERING: u32 = 0x0A;
+pub const SERVICE_ID_SET_DEFAULT_LOG_LEVEL: u32 = 0x11;
+pub const SERVICE_ID_SET_DEFAULT_TRACE_STATUS: u32 = 0x12;
+pub const SERVICE_ID_GET_SOFTWARE_VERSION: u32 = 0x13;
+pub const SERVICE_ID_MESSAGE_BUFFER_OVERFLOW: u32 = 0x14;
+pub const SERVICE_ID_GET_DEFAULT_TRACE_STATUS: u32 = 0x15;
+pub const SERVICE_ID_GET_TRACE_STATUS: u32 = 0x1F;
+// services specific to the GENIVI/COVESA dlt-daemon
+pub const SERVICE_ID_UNREGISTER_CONTEXT: u32 = 0xF01;
+pub const SERVICE_ID_CONNECTION_INFO: u32 = 0xF02;
+pub const SERVICE_ID_TIMEZONE: u32 = 0xF03;
+pub const SERVICE_ID_MARKER: u32 = 0xF04;
+
+const ID_SIZE: usize = 4;
+
+fn service_name(service_id: u32) -> Option<&'static str> {
+    match service_id {
+        SERVICE_ID_SET_LOG_LEVEL => Some("SetLogLevel"),
+        SERVICE_ID_SET_TRACE_STATUS => Some("SetTraceStatus"),
+        SERVICE_ID_GET_LOG_INFO => Some("GetLogInfo"),
+        SERVICE_ID_GET_DEFAULT_LOG_LEVEL => Some("GetDefaultLogLevel"),
+        SERVICE_ID_STORE_CONFIGURATION => Some("StoreConfiguration"),
+        SERVICE_ID_RESET_TO_FACTORY_DEFAULT => Some("ResetToFactoryDefault"),
+        SERVICE_ID_SET_MESSAGE_FILTERING => Some("SetMessageFiltering"),
+        SERVICE_ID_SET_DEFAULT_LOG_LEVEL => Some("SetDefaultLogLevel"),
+        SERVICE_ID_SET_DEFAULT_TRACE_STATUS => Some("SetDefaultTraceStatus"),
+        SERVICE_ID_GET_SOFTWARE_VERSION => Some("GetSoftwareVersion"),
+        SERVICE_ID_MESSAGE_BUFFER_OVERFLOW => Some("MessageBufferOverflow"),
+        SERVICE_ID_GET_DEFAULT_TRACE_STATUS => Some("GetDefaultTraceStatus"),
+        SERVICE_ID_GET_TRACE_STATUS => Some("GetTraceStatus"),
+        SERVICE_ID_UNREGISTER_CONTEXT => Some("UnregisterContext"),
+        SERVICE_ID_CONNECTION_INFO => Some("ConnectionInfo"),
+        SERVICE_ID_TIMEZONE => Some("Timezone"),
+        SERVICE_ID_MARKER => Some("Marker"),
+        _ => None,
+    }
+}
+
+fn status_name(status: u8) -> String {
+    match status {
+        0 => "ok".to_string(),
+        1 => "not supported".to_string(),
+        2 => "error".to_string(),
+        _ => status.to_string(),
+    }
+}
+
+fn log_level_name(log_level: i8) -> String {
+    match log_level {
+        -1 => "default".to_string(),
+        0 => "off".to_string(),
+        1 => "fatal".to_string(),
+        2 => "error".to_string(),
+        3 => "warn".to_string(),
+        4 => "info".to_string(),
+        5 => "debug".to_string(),
+        6 => "verbose".to_string(),
+        _ => log_level.to_string(),
+    }
+}
+
+fn trace_status_name(trace_status: i8) -> String {
+    match trace_status {
+        -1 => "default".to_string(),
+        0 => "off".to_string(),
+        1 => "on".to_string(),
+        _ => trace_status.to_string(),
+    }
+}
+
+/// Reads the parameters of a control message, which are encoded in the byte order of the
+/// message.
+struct ParameterReader<'d> {
+    data: &'d [u8],
+    index: usize,
+    is_big_endian: bool,
+}
+
+impl<'d> ParameterReader<'d> {
+    fn bytes<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let bytes = self.data.get(self.index..self.index + N)?.try_into().ok()?;
+        self.index += N;
+        Some(bytes)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.bytes::<1>().map(|bytes| bytes[0])
+    }
+
+    fn i8(&mut self) -> Option<i8> {
+        self.u8().map(|value| value as i8)
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        let bytes = self.bytes::<{ mem::size_of::<u16>() }>()?;
+        Some(if self.is_big_endian { u16::from_be_bytes(bytes) } else { u16::from_le_bytes(bytes) })
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        let bytes = self.bytes::<{ mem::size_of::<u32>() }>()?;
+        Some(if self.is_big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) })
+    }
+
+    fn i32(&mut self) -> Option<i32> {
+        self.u32().map(|value| value as i32)
+    }
+
+    fn text(&mut self, len: usize) -> Option<&'d str> {
+        let bytes = self.data.get(self.index..self.index + len)?;
+        self.index += len;
+        str::from_utf8(bytes).ok().map(|text| text.trim_matches(char::from(0)))
+    }
+
+    fn id(&mut self) -> Option<&'d str> {
+        self.text(ID_SIZE)
+    }
+}
+
+/// Context of an application as reported in a GetLogInfo response.
+#[derive(Debug)]
+pub struct ContextInfo<'d> {
+    pub context_id: &'d str,
+    pub log_level: Option<i8>,
+    pub trace_status: Option<i8>,
+    pub description: Option<&'d str>,
+}
+
+/// Application and its contexts as reported in a GetLogInfo response.
+#[derive(Debug)]
+pub struct AppInfo<'d> {
+    pub app_id: &'d str,
+    pub contexts: Vec<ContextInfo<'d>>,
+    pub description: Option<&'d str>,
+}
+
+/// Notification of the DLT daemon that its message buffer overflowed and messages got lost.
+#[derive(Debug)]
+pub struct BufferOverflow {
+    status: u8,
+    lost: u32,
+}
+
+impl BufferOverflow {
+    pub fn from_message(msg: &Message) -> Option<BufferOverflow> {
+        match ControlMessage::from_message(msg)? {
+            ControlMessage::BufferOverflow(overflow) => Some(overflow),
+            _ => None,
+        }
+    }
+
+    pub fn status(&self) -> u8 {
+        self.status
+    }
+
+    /// Number of messages the daemon had to drop.
+    pub fn lost(&self) -> u32 {
+        self.lost
+    }
+}
+
+impl Display for BufferOverflow {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "buffer overflow [ status: {}, lost messages: {} ]", self.status, self.lost)
+    }
+}
+
+/// Decoded request or response of the DLT control protocol.
+#[derive(Debug)]
+pub enum ControlMessage<'d> {
+    SetLogLevel {
+        app_id: &'d str,
+        context_id: &'d str,
+        log_level: i8,
+    },
+    SetTraceStatus {
+        app_id: &'d str,
+        context_id: &'d str,
+        trace_status: i8,
+    },
+    GetLogInfo {
+        options: u8,
+        app_id: &'d str,
+        context_id: &'d str,
+    },
+    SetMessageFiltering {
+        enabled: bool,
+    },
+    SetDefaultLogLevel {
+        log_level: i8,
+    },
+    SetDefaultTraceStatus {
+        trace_status: i8,
+    },
+    GetTraceStatus {
+        app_id: &'d str,
+        context_id: &'d str,
+    },
+    /// request without parameters
+    Request {
+        service_id: u32,
+    },
+    /// response that only carries the status
+    Status {
+        service_id: u32,
+        status: u8,
+    },
+    LogInfo {
+        status: u8,
+        apps: Vec<AppInfo<'d>>,
+    },
+    DefaultLogLevel {
+        status: u8,
+        log_level: i8,
+    },
+    TraceStatus {
+        service_id: u32,
+        status: u8,
+        trace_status: i8,
+    },
+    SoftwareVersion {
+        status: u8,
+        version: &'d str,
+    },
+    BufferOverflow(BufferOverflow),
+    UnregisterContext {
+        status: u8,
+        app_id: &'d str,
+        context_id: &'d str,
+    },
+    ConnectionInfo {
+        status: u8,
+        state: u8,
+    },
+    Timezone {
+        status: u8,
+        timezone: i32,
+        is_dst: bool,
+    },
+    /// service that is unknown or whose parameters could not be decoded
+    Unknown {
+        service_id: u32,
+        data: &'d [u8],
+    },
+}
+
+impl<'d> ControlMessage<'d> {
+    pub fn from_message(msg: &Message<'d>) -> Option<ControlMessage<'d>> {
+        let header = msg.extended_header.as_ref()?;
+        if !matches!(header.msg_type(), MessageType::Control) {
+            return None;
+        }
+        let is_response = match header.msg_type_info_control()? {
+            MessageTypeInfoControl::Request => false,
+            MessageTypeInfoControl::Response => true,
+        };
+        let Some(Value::NonVerbose { message_id: service_id, data }) = msg.payload.first() else {
+            return None;
+        };
+
+        let mut reader = ParameterReader { data, index: 0, is_big_endian: msg.standard_header.is_big_endian() };
+        let decoded = if is_response {
+            ControlMessage::read_response(*service_id, &mut reader)
+        } else {
+            ControlMessage::read_request(*service_id, &mut reader)
+        };
+        Some(decoded.unwrap_or(ControlMessage::Unknown { service_id: *service_id, data }))
+    }
+
+    fn read_request(service_id: u32, reader: &mut ParameterReader<'d>) -> Option<ControlMessage<'d>> {
+        let request = match service_id {
+            SERVICE_ID_SET_LOG_LEVEL => ControlMessage::SetLogLevel { app_id: reader.id()?, context_id: reader.id()?, log_level: reader.i8()? },
+            SERVICE_ID_SET_TRACE_STATUS => ControlMessage::SetTraceStatus { app_id: reader.id()?, context_id: reader.id()?, trace_status: reader.i8()? },
+            SERVICE_ID_GET_LOG_INFO => ControlMessage::GetLogInfo { options: reader.u8()?, app_id: reader.id()?, context_id: reader.id()? },
+            SERVICE_ID_SET_MESSAGE_FILTERING => ControlMessage::SetMessageFiltering { enabled: reader.u8()? != 0 },
+            SERVICE_ID_SET_DEFAULT_LOG_LEVEL => ControlMessage::SetDefaultLogLevel { log_level: reader.i8()? },
+            SERVICE_ID_SET_DEFAULT_TRACE_STATUS => ControlMessage::SetDefaultTraceStatus { trace_status: reader.i8()? },
+            SERVICE_ID_GET_TRACE_STATUS => ControlMessage::GetTraceStatus { app_id: reader.id()?, context_id: reader.id()? },
+            _ if service_name(service_id).is_some() => ControlMessage::Request { service_id },
+            _ => return None,
+        };
+        Some(request)
+    }
+
+    fn read_response(service_id: u32, reader: &mut ParameterReader<'d>) -> Option<ControlMessage<'d>> {
+        let status = reader.u8()?;
+        let response = match service_id {
+            SERVICE_ID_GET_LOG_INFO => ControlMessage::LogInfo { status, apps: ControlMessage::read_log_info(status, reader)? },
+            SERVICE_ID_GET_DEFAULT_LOG_LEVEL => ControlMessage::DefaultLogLevel { status, log_level: reader.i8()? },
+            SERVICE_ID_GET_DEFAULT_TRACE_STATUS | SERVICE_ID_GET_TRACE_STATUS => ControlMessage::TraceStatus { service_id, status, trace_status: reader.i8()? },
+            SERVICE_ID_GET_SOFTWARE_VERSION => {
+                let len = reader.u32()? as usize;
+                ControlMessage::SoftwareVersion { status, version: reader.text(len)? }
+            },
+            SERVICE_ID_MESSAGE_BUFFER_OVERFLOW => ControlMessage::BufferOverflow(BufferOverflow { status, lost: reader.u32()? }),
+            SERVICE_ID_UNREGISTER_CONTEXT => ControlMessage::UnregisterContext { status, app_id: reader.id()?, context_id: reader.id()? },
+            SERVICE_ID_CONNECTION_INFO => ControlMessage::ConnectionInfo { status, state: reader.u8()? },
+            SERVICE_ID_TIMEZONE => ControlMessage::Timezone { status, timezone: reader.i32()?, is_dst: reader.u8()? != 0 },
+            _ if service_name(service_id).is_some() => ControlMessage::Status { service_id, status },
+            _ => return None,
+        };
+        Some(response)
+    }
+
+    /// Reads the applications and contexts of a GetLogInfo response. The status tells which
+    /// information the response carries: 3 only ids, 4 also log levels, 5 also trace status, 6
+    /// both and 7 additionally the descriptions. Any other status comes without log info.
+    fn read_log_info(status: u8, reader: &mut ParameterReader<'d>) -> Option<Vec<AppInfo<'d>>> {
+        if !(3..=7).contains(&status) {
+            return Some(vec![]);
+        }
+        let has_log_level = matches!(status, 4 | 6 | 7);
+        let has_trace_status = matches!(status, 5..=7);
+        let has_description = status == 7;
+
+        let app_count = reader.u16()?;
+        let mut apps = Vec::with_capacity(app_count as usize);
+        for _ in 0..app_count {
+            let app_id = reader.id()?;
+            let context_count = reader.u16()?;
+            let mut contexts = Vec::with_capacity(context_count as usize);
+            for _ in 0..context_count {
+                let context_id = reader.id()?;
+                let log_level = if has_log_level { Some(reader.i8()?) } else { None };
+                let trace_status = if has_trace_status { Some(reader.i8()?) } else { None };
+                let description = if has_description {
+                    let len = reader.u16()? as usize;
+                    Some(reader.text(len)?)
+                } else {
+                    None
+                };
+                contexts.push(ContextInfo { context_id, log_level, trace_status, description });
+            }
+            let description = if has_description {
+                let len = reader.u16()? as usize;
+                Some(reader.text(len)?)
+            } else {
+                None
+            };
+            apps.push(AppInfo { app_id, contexts, description });
+        }
+        Some(apps)
+    }
+}
+
+impl Display for ControlMessage<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ControlMessage::SetLogLevel { app_id, context_id, log_level } => write!(f, "SetLogLevel {app_id}/{context_id} {}", log_level_name(*log_level)),
+            ControlMessage::SetTraceStatus { app_id, context_id, trace_status } => write!(f, "SetTraceStatus {app_id}/{context_id} {}", trace_status_name(*trace_status)),
+            ControlMessage::GetLogInfo { options, app_id, context_id } => write!(f, "GetLogInfo {app_id}/{context_id} options {options}"),
+            ControlMessage::SetMessageFiltering { enabled } => write!(f, "SetMessageFiltering {}", if *enabled { "on" } else { "off" }),
+            ControlMessage::SetDefaultLogLevel { log_level } => write!(f, "SetDefaultLogLevel {}", log_level_name(*log_level)),
+            ControlMessage::SetDefaultTraceStatus { trace_status } => write!(f, "SetDefaultTraceStatus {}", trace_status_name(*trace_status)),
+            ControlMessage::GetTraceStatus { app_id, context_id } => write!(f, "GetTraceStatus {app_id}/{context_id}"),
+            ControlMessage::Request { service_id } => write!(f, "{}", service_name(*service_id).unwrap_or_default()),
+            ControlMessage::Status { service_id, status } => write!(f, "{} {}", service_name(*service_id).unwrap_or_default(), status_name(*status)),
+            ControlMessage::LogInfo { status, apps } => {
+                write!(f, "GetLogInfo {}", status_name(*status))?;
+                for app in apps {
+                    write!(f, " {}", app.app_id)?;
+                    if let Some(description) = app.description {
+                        write!(f, " ({description})")?;
+                    }
+                    write!(f, " [")?;
+                    for (idx, context) in app.contexts.iter().enumerate() {
+                        if idx > 0 {
+                            write!(f, ",")?;
+                        }
+                        write!(f, " {}", context.context_id)?;
+                        if let Some(log_level) = context.log_level {
+                            write!(f, " {}", log_level_name(log_level))?;
+                        }
+                        if let Some(trace_status) = context.trace_status {
+                            write!(f, " trace {}", trace_status_name(trace_status))?;
+                        }
+                        if let Some(description) = context.description {
+                            write!(f, " ({description})")?;
+                        }
+                    }
+                    write!(f, " ]")?;
+                }
+                Ok(())
+            },
+            ControlMessage::DefaultLogLevel { status, log_level } => write!(f, "GetDefaultLogLevel {} {}", status_name(*status), log_level_name(*log_level)),
+            ControlMessage::TraceStatus { service_id, status, trace_status } => write!(f, "{} {} {}", service_name(*service_id).unwrap_or_default(), status_name(*status), trace_status_name(*trace_status)),
+            ControlMessage::SoftwareVersion { status, version } => write!(f, "GetSoftwareVersion {} {version}", status_name(*status)),
+            ControlMessage::BufferOverflow(overflow) => write!(f, "{overflow}"),
+            ControlMessage::UnregisterContext { status, app_id, context_id } => write!(f, "UnregisterContext {} {app_id}/{context_id}", status_name(*status)),
+            ControlMessage::ConnectionInfo { status, state } => {
+                let state = match state {
+                    1 => "disconnected".to_string(),
+                    2 => "connected".to_string(),
+                    _ => state.to_string(),
+                };
+                write!(f, "ConnectionInfo {} {state}", status_name(*status))
+            },
+            ControlMessage::Timezone { status, timezone, is_dst } => write!(f, "Timezone {} {timezone}s{}", status_name(*status), if *is_dst { " dst" } else { "" }),
+            ControlMessage::Unknown { service_id, data } => {
+                write!(f, "service 0x{service_id:x} ")?;
+                data.iter().try_for_each(|byte| write!(f, "{byte:02x}"))
+            },
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use crate::config::ShardKey;
+use crate::dlt::Message;
+
+const COUNTER_WRAP: usize = 256;
+
+/// ECU, app and context id the message counter runs over.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CounterKey {
+    pub ecu_id: String,
+    pub app_id: String,
+    pub context_id: String,
+}
+
+impl CounterKey {
+    pub fn of(msg: &Message) -> CounterKey {
+        let (app_id, context_id) = msg.extended_header.as_ref()
+            .map_or((String::new(), String::new()), |header| (header.app_id().clone(), header.context_id().clone()));
+        CounterKey { ecu_id: msg.ecu_id().to_string(), app_id, context_id }
+    }
+
+    /// Id the csv output of the context is sharded by, like `Message::shard_value`, except that
+    /// the ECU id is the one of the standard header if the message has one.
+    pub fn shard_value(&self, key: ShardKey) -> &str {
+        let id = match key {
+            ShardKey::Ecu => &self.ecu_id,
+            ShardKey::App => &self.app_id,
+            ShardKey::Ctx => &self.context_id,
+        };
+        if id.is_empty() { "none" } else { id }
+    }
+}
+
+impl Display for CounterKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}/{}", self.ecu_id, self.app_id, self.context_id)
+    }
+}
+
+/// Number of messages lost between two messages of the same context; the message counter wraps at
+/// 256, anything but the successor of the last value means that messages got lost.
+pub fn counter_gap(last: usize, counter: usize) -> usize {
+    (counter + COUNTER_WRAP - last - 1) % COUNTER_WRAP
+}
+
+/// Follows the message counters of each ECU/app/context to find the messages lost in between,
+/// e.g. when the logger could not keep up. Gaps longer than a wrap of the counter go unnoticed.
+#[derive(Default)]
+pub struct CounterGaps {
+    last: HashMap<CounterKey, usize>,
+    /// messages lost per context since `take_lost` was last called for it
+    unreported: HashMap<CounterKey, usize>,
+}
+
+impl CounterGaps {
+    pub fn new() -> CounterGaps {
+        CounterGaps::default()
+    }
+
+    /// Adds the next message of the trace, returns the number of messages of its context lost
+    /// right before it.
+    pub fn add(&mut self, msg: &Message) -> usize {
+        let key = CounterKey::of(msg);
+        let counter = msg.standard_header.counter();
+        let lost = self.last.insert(key.clone(), counter).map_or(0, |last| counter_gap(last, counter));
+        if lost > 0 {
+            *self.unreported.entry(key).or_default() += lost;
+        }
+        lost
+    }
+
+    /// Takes the messages lost in the context of the message since the last call, so they can
+    /// be reported along the next message of the context that gets written.
+    pub fn take_lost(&mut self, msg: &Message) -> Option<(CounterKey, usize)> {
+        self.unreported.remove_entry(&CounterKey::of(msg))
+    }
+}
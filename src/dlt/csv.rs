@@ -0,0 +1,153 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use serde_derive::Serialize;
+use crate::dlt::chaos::SinkFile;
+use crate::provenance::Provenance;
+use crate::Csv;
+
+/// Writes the rows of the csv output, to one file per shard, or with `chunk_rows` to numbered
+/// chunks of that many rows and a manifest listing them.
+pub struct CsvWriters<'a> {
+    csv: &'a Csv,
+    provenance: &'a Option<Provenance>,
+    /// files by the path of the shard, before chunking
+    files: HashMap<PathBuf, CsvFile>,
+}
+
+struct CsvFile {
+    writer: BufWriter<SinkFile>,
+    /// rows of each chunk written so far, the last one is being written
+    chunks: Vec<Chunk>,
+}
+
+#[derive(Serialize)]
+struct Chunk {
+    file: String,
+    rows: usize,
+}
+
+/// Written next to the chunks as `<name>.manifest.json`, for jobs ingesting them in parallel.
+#[derive(Serialize)]
+struct Manifest<'c> {
+    rows: usize,
+    chunks: &'c [Chunk],
+}
+
+impl<'a> CsvWriters<'a> {
+    pub fn new(csv: &'a Csv, provenance: &'a Option<Provenance>) -> CsvWriters<'a> {
+        CsvWriters { csv, provenance, files: HashMap::new() }
+    }
+
+    /// Writes the row, preceded by the comment lines, to the file of the shard. A new chunk is
+    /// started once the current one holds `chunk_rows` rows. `header` gives the header row of a
+    /// new file, if the output has one.
+    pub fn write(&mut self, shard_value: &str, comments: &[String], row: &str, header: impl Fn() -> String, flush: bool) -> io::Result<()> {
+        let path = self.csv.file_path(shard_value);
+        let line_ending = self.csv.line_ending();
+        let header = self.csv.header().then(header);
+        let file = match self.files.get_mut(&path) {
+            Some(file) => {
+                if self.csv.chunk_rows.is_some_and(|chunk_rows| file.chunks.last().is_some_and(|chunk| chunk.rows >= chunk_rows)) {
+                    file.writer.flush()?;
+                    let chunk_path = chunk_path(&path, file.chunks.len());
+                    file.writer = create_file(self.csv, self.provenance, header.as_deref(), &chunk_path);
+                    file.chunks.push(Chunk { file: file_name(&chunk_path), rows: 0 });
+                }
+                file
+            },
+            None => {
+                let file_path = match self.csv.chunk_rows {
+                    Some(_) => chunk_path(&path, 0),
+                    None => path.clone(),
+                };
+                let writer = create_file(self.csv, self.provenance, header.as_deref(), &file_path);
+                let chunks = vec![Chunk { file: file_name(&file_path), rows: 0 }];
+                self.files.entry(path).or_insert(CsvFile { writer, chunks })
+            },
+        };
+
+        for comment in comments {
+            write!(file.writer, "# {comment}{line_ending}")?;
+        }
+        write!(file.writer, "{row}{line_ending}")?;
+        if let Some(chunk) = file.chunks.last_mut() {
+            chunk.rows += 1;
+        }
+        if flush {
+            file.writer.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Writes a comment line without a row after it, to a file already written to.
+    pub fn comment(&mut self, shard_value: &str, comment: &str) -> io::Result<()> {
+        let line_ending = self.csv.line_ending();
+        match self.files.get_mut(&self.csv.file_path(shard_value)) {
+            Some(file) => write!(file.writer, "# {comment}{line_ending}"),
+            None => Ok(()),
+        }
+    }
+
+    /// Flushes the files and writes the manifests of the chunked ones.
+    pub fn finish(self) -> io::Result<()> {
+        for (path, mut file) in self.files {
+            file.writer.flush()?;
+            if self.csv.chunk_rows.is_some() {
+                let manifest = Manifest { rows: file.chunks.iter().map(|chunk| chunk.rows).sum(), chunks: &file.chunks };
+                let json = serde_json::to_string_pretty(&manifest).map_err(io::Error::from)?;
+                fs::write(manifest_path(&path), json)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Creates the file, starting with the provenance as comment lines and the header row.
+fn create_file(csv: &Csv, provenance: &Option<Provenance>, header: Option<&str>, path: &Path) -> BufWriter<SinkFile> {
+    let file = SinkFile::create(path).unwrap_or_else(|err| {
+        eprintln!("error on creating csv file {path:?}: {err}");
+        process::exit(1);
+    });
+    let mut writer = BufWriter::new(file);
+    for line in provenance.iter().flat_map(Provenance::lines) {
+        if let Err(err) = write!(writer, "# {line}{}", csv.line_ending()) {
+            eprintln!("error on writing csv: {err}");
+        }
+    }
+    if let Some(Err(err)) = header.map(|header| write!(writer, "{header}{}", csv.line_ending())) {
+        eprintln!("error on writing csv: {err}");
+    }
+    writer
+}
+
+/// Quotes the value if it contains the delimiter, a quote or a line break, so it stays one cell.
+pub fn escape(value: &str, delimiter: char) -> Cow<'_, str> {
+    if value.contains([delimiter, '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", value.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(value)
+    }
+}
+
+/// `out.csv` becomes `out.00000.csv`, `out.00001.csv`, ...
+fn chunk_path(path: &Path, chunk: usize) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(extension) => format!("{stem}.{chunk:05}.{}", extension.to_string_lossy()),
+        None => format!("{stem}.{chunk:05}"),
+    };
+    path.with_file_name(name)
+}
+
+fn manifest_path(path: &Path) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{stem}.manifest.json"))
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().unwrap_or_default().to_string_lossy().to_string()
+}
@@ -0,0 +1,44 @@
+use std::collections::HashMap;
+use crate::dlt::Message;
+use crate::dlt::counters::CounterKey;
+
+/// Collapses repeats of the same payload in an ECU/app/context, e.g. an app spamming the same
+/// line hundreds of times per second. The first message is kept, the identical ones following it
+/// in its context are dropped and counted, so they can be reported along the next message of the
+/// context that gets written, like syslog's "last message repeated N times".
+#[derive(Default)]
+pub struct Deduplicator {
+    last: HashMap<CounterKey, String>,
+    repeats: HashMap<CounterKey, usize>,
+}
+
+impl Deduplicator {
+    pub fn new() -> Deduplicator {
+        Deduplicator::default()
+    }
+
+    /// Whether to keep the message, `false` if it repeats the last payload of its context.
+    pub fn keep(&mut self, msg: &Message) -> bool {
+        let key = CounterKey::of(msg);
+        let text = msg.payload_text();
+        if self.last.get(&key) == Some(&text) {
+            *self.repeats.entry(key).or_default() += 1;
+            false
+        } else {
+            self.last.insert(key, text);
+            true
+        }
+    }
+
+    /// Takes the repeats dropped in the context of the message since the last call.
+    pub fn take_repeats(&mut self, msg: &Message) -> Option<(CounterKey, usize)> {
+        self.repeats.remove_entry(&CounterKey::of(msg))
+    }
+
+    /// Takes the repeats of all contexts not reported yet, at the end of the input.
+    pub fn take_all(&mut self) -> Vec<(CounterKey, usize)> {
+        let mut repeats: Vec<_> = self.repeats.drain().collect();
+        repeats.sort_by_key(|(key, _)| key.to_string());
+        repeats
+    }
+}
@@ -0,0 +1,38 @@
+use std::collections::HashMap;
+use std::time::Duration;
+use crate::dlt::Message;
+use crate::dlt::counters::CounterKey;
+
+/// Thins out the messages of each ECU/app/context to one per interval, e.g. periodic signals
+/// logged far more often than needed for a plot. The first and the last message of each context
+/// are always kept, so the last one has to be known up front from a pass over the matches.
+pub struct Downsampler {
+    /// in microseconds, like the message time
+    interval: f64,
+    last_index: HashMap<CounterKey, usize>,
+    last_kept: HashMap<CounterKey, f64>,
+}
+
+impl Downsampler {
+    pub fn new(interval: Duration) -> Downsampler {
+        Downsampler { interval: interval.as_micros() as f64, last_index: HashMap::new(), last_kept: HashMap::new() }
+    }
+
+    /// Notes the message as the last of its context so far, for the first pass.
+    pub fn add(&mut self, index: usize, msg: &Message) {
+        self.last_index.insert(CounterKey::of(msg), index);
+    }
+
+    /// Whether to keep the message, if the interval passed since the last one kept of its context.
+    /// A message earlier than that, as after the uptime of a restarted ECU, is kept as well.
+    pub fn keep(&mut self, index: usize, msg: &Message) -> bool {
+        let key = CounterKey::of(msg);
+        let time = msg.time();
+        let is_last = self.last_index.get(&key) == Some(&index);
+        let is_due = self.last_kept.get(&key).is_none_or(|last| !(0.0..self.interval).contains(&(time - last)));
+        if is_last || is_due {
+            self.last_kept.insert(key, time);
+        }
+        is_last || is_due
+    }
+}
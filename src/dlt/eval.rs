@@ -0,0 +1,88 @@
+use std::collections::BTreeMap;
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::config::{self, Ids};
+use crate::dlt::filter::{Filter, FilterId, FilterType, Pattern};
+use crate::dlt::headers::{LogHeaders, MessageTypeInfoLog, write_log_headers};
+use crate::dlt::payload::write_string;
+use crate::dlt::registry::Registry;
+use crate::dlt::{FieldContext, TraceData};
+use crate::{Output, OutputType};
+
+/// Longest string argument that fits the 16 bit message length along with the headers.
+const MAX_PAYLOAD_LEN: usize = u16::MAX as usize - 64;
+
+/// Runs the patterns of the filter on a payload typed in instead of a trace and prints the
+/// captures and the row each output would get, for trying out patterns while writing a config.
+/// The payload is logged as the only string argument of an info message with the first ids of
+/// the filter, stored now. Exits with 1 if the payload does not match.
+pub fn run_eval(cfg_filter: &config::Filter, outputs: &[Output], payload: &str) {
+    let Some(patterns) = cfg_filter.patterns() else {
+        eprintln!("filter '{}' has no patterns", cfg_filter.name());
+        process::exit(1);
+    };
+    if payload.len() > MAX_PAYLOAD_LEN {
+        eprintln!("payload of {} bytes is too long for a message, at most {MAX_PAYLOAD_LEN} bytes fit", payload.len());
+        process::exit(1);
+    }
+    let pattern = Pattern::with_case(patterns, cfg_filter.ignore_case()).unwrap_or_else(|err| {
+        eprintln!("patterns: {err}");
+        process::exit(1);
+    });
+    if pattern.captures(payload).is_none() {
+        println!("payload does not match filter '{}'", cfg_filter.name());
+        process::exit(1);
+    }
+
+    let first_id = |ids: &Option<Ids>, default: &str| ids.as_ref()
+        .and_then(|ids| ids.to_vec().into_iter().next())
+        .unwrap_or(default.to_string());
+    let (ecu_id, app_id, context_id) = (first_id(cfg_filter.ecu_id(), "ECU"), first_id(cfg_filter.app_id(), "APP"), first_id(cfg_filter.context_id(), "CTX"));
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let headers = LogHeaders {
+        seconds: now.as_secs() as u32,
+        microseconds: now.subsec_micros(),
+        ecu_id: &ecu_id,
+        counter: 0,
+        session_id: None,
+        timestamp: None,
+        is_big_endian: false,
+        app_id: &app_id,
+        context_id: &context_id,
+        level: MessageTypeInfoLog::Info,
+        verbose: true,
+        num_of_args: 1,
+    };
+    let mut payload_bytes = vec![];
+    write_string(&mut payload_bytes, payload);
+    let mut message = vec![];
+    write_log_headers(&mut message, &headers, payload_bytes.len());
+    message.extend_from_slice(&payload_bytes);
+
+    let trace_data = TraceData::new(&message, 0);
+    let Some(msg) = trace_data.iter().next() else {
+        eprintln!("error on encoding the payload as message");
+        process::exit(1);
+    };
+    let mut filter = Filter::new();
+    filter.add(FilterId::Patterns, FilterType::Patterns(pattern));
+    let extraction = filter.find_patterns(&msg).unwrap_or_default();
+
+    for name in Pattern::capture_names(patterns).unwrap_or_default() {
+        for value in extraction.values(&name) {
+            println!("<{name}> = {value}");
+        }
+    }
+
+    let registry = Registry::new();
+    for out in outputs {
+        let delimiter = match out.output_type() {
+            OutputType::Stdout(stdout) => stdout.delimiter,
+            OutputType::Csv(csv) => csv.delimiter,
+            OutputType::Arrow(_) | OutputType::Sqlite(_) | OutputType::Parquet(_) => ',',
+            OutputType::Dlt(_) => continue,
+        };
+        let context = FieldContext { extraction: &extraction, index: 0, lifecycle: 1, registry: &registry, references: out.references.as_ref(), metadata: &BTreeMap::new(), missing: out.missing() };
+        println!("{}", msg.format_line(out, delimiter, &context));
+    }
+}
@@ -0,0 +1,199 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use crate::config::Annotation;
+use crate::dlt::chaos::SinkFile;
+use crate::dlt::fibex::fibex_xml;
+use crate::dlt::filter::Extraction;
+use crate::dlt::headers::{LogHeaders, MessageTypeInfoLog, write_log_headers};
+use crate::dlt::payload::write_string;
+use crate::dlt::Message;
+use crate::Dlt;
+
+/// Writes messages to a DLT file and injects the annotation messages, so the analysis shows up
+/// inline when the trace is opened in a DLT viewer. The messages are copied unchanged, or their
+/// captures are re-encoded as non-verbose messages.
+///
+/// Messages earlier in storage time than one exported before, e.g. after merging traces, are
+/// counted and reported. With a reorder window, that many messages are held back and written in
+/// storage time order.
+pub struct DltExport {
+    file_path: PathBuf,
+    writer: BufWriter<SinkFile>,
+    annotation: Option<Annotation>,
+    /// message id and capture names of the non-verbose re-encoding
+    signals: Option<(u32, Vec<String>)>,
+    /// storage times of the annotations still to be written, latest first
+    pending: Vec<f64>,
+    counter: u8,
+    ecu_id: String,
+    reorder_window: usize,
+    /// messages held back by storage time in nanoseconds and order of arrival, with their
+    /// timestamp
+    held_back: BTreeMap<(u64, usize), (Option<u32>, Vec<u8>)>,
+    received: usize,
+    /// latest storage time received and written
+    latest_received: u64,
+    latest_written: u64,
+    /// messages earlier than one received before
+    received_out_of_order: usize,
+    /// messages earlier than one written before, left out of order by the reorder window
+    out_of_order: usize,
+}
+
+impl DltExport {
+    /// Creates the DLT file and, for the non-verbose re-encoding, the FIBEX file describing it.
+    pub fn create(dlt: &Dlt) -> io::Result<DltExport> {
+        let annotation = &dlt.annotation;
+        let mut pending = annotation.as_ref().map(|annotation| annotation.at().clone()).unwrap_or_default();
+        pending.sort_by(|a, b| b.total_cmp(a));
+        let signals = dlt.non_verbose.as_ref().map(|non_verbose| (non_verbose.message_id(), dlt.capture_names.clone()));
+        if let Some(non_verbose) = &dlt.non_verbose {
+            fs::write(non_verbose.fibex_path(), fibex_xml(non_verbose.message_id(), &dlt.capture_names))?;
+        }
+        Ok(DltExport {
+            file_path: dlt.file_path.clone(),
+            writer: BufWriter::new(SinkFile::create(&dlt.file_path)?),
+            annotation: annotation.clone(),
+            signals,
+            pending,
+            counter: 0,
+            ecu_id: String::new(),
+            reorder_window: dlt.reorder_window,
+            held_back: BTreeMap::new(),
+            received: 0,
+            latest_received: 0,
+            latest_written: 0,
+            received_out_of_order: 0,
+            out_of_order: 0,
+        })
+    }
+
+    pub fn write(&mut self, msg: &Message, extraction: &Extraction) -> io::Result<()> {
+        let nanos = msg.storage_header.seconds() as u64 * 1_000_000_000 + msg.storage_header.nanoseconds().unwrap_or(0) as u64;
+        self.ecu_id = msg.ecu_id().to_string();
+        let message = match &self.signals {
+            Some((message_id, names)) => encode_non_verbose(msg, *message_id, names, extraction),
+            None => msg.bytes().to_vec(),
+        };
+
+        if nanos < self.latest_received {
+            self.received_out_of_order += 1;
+        }
+        self.latest_received = self.latest_received.max(nanos);
+        self.held_back.insert((nanos, self.received), (msg.standard_header.timestamp(), message));
+        self.received += 1;
+        while self.held_back.len() > self.reorder_window {
+            self.write_earliest()?;
+        }
+        Ok(())
+    }
+
+    /// Writes the earliest message held back, preceded by the annotations due before it.
+    fn write_earliest(&mut self) -> io::Result<()> {
+        let Some(((nanos, _), (timestamp, message))) = self.held_back.pop_first() else {
+            return Ok(());
+        };
+        if nanos < self.latest_written {
+            self.out_of_order += 1;
+        }
+        self.latest_written = self.latest_written.max(nanos);
+
+        let time = nanos as f64 / 1_000_000_000.0;
+        while self.pending.last().is_some_and(|at| *at <= time) {
+            let at = self.pending.pop().unwrap();
+            self.write_annotation(at, None)?;
+        }
+        self.writer.write_all(&message)?;
+        if self.annotation.as_ref().is_some_and(Annotation::on_match) {
+            self.write_annotation(time, timestamp)?;
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    /// Writes the messages held back and the annotations after the last exported message,
+    /// flushes the file and reports how the messages were ordered.
+    pub fn finish(mut self) -> io::Result<()> {
+        while !self.held_back.is_empty() {
+            self.write_earliest()?;
+        }
+        let reordered = self.received_out_of_order.saturating_sub(self.out_of_order);
+        if reordered > 0 {
+            eprintln!("{:?}: {reordered} messages were moved into storage time order", self.file_path);
+        }
+        if self.out_of_order > 0 {
+            let hint = if self.reorder_window > 0 { "increase reorder_window to repair" } else { "set reorder_window to repair" };
+            eprintln!("{:?}: {} messages are earlier in storage time than the one before, {hint}", self.file_path, self.out_of_order);
+        }
+        while let Some(at) = self.pending.pop() {
+            self.write_annotation(at, None)?;
+        }
+        self.writer.flush()
+    }
+
+    fn write_annotation(&mut self, time: f64, timestamp: Option<u32>) -> io::Result<()> {
+        let Some(annotation) = &self.annotation else {
+            return Ok(());
+        };
+        let headers = LogHeaders {
+            seconds: time.trunc() as u32,
+            microseconds: (time.fract() * 1_000_000.0).round() as u32,
+            ecu_id: &self.ecu_id,
+            counter: self.counter,
+            session_id: None,
+            timestamp,
+            is_big_endian: false,
+            app_id: annotation.app_id(),
+            context_id: annotation.context_id(),
+            level: MessageTypeInfoLog::Info,
+            verbose: true,
+            num_of_args: 1,
+        };
+        let mut payload = vec![];
+        write_string(&mut payload, annotation.payload());
+        let mut message = vec![];
+        write_log_headers(&mut message, &headers, payload.len());
+        message.extend_from_slice(&payload);
+
+        self.counter = self.counter.wrapping_add(1);
+        self.writer.write_all(&message)
+    }
+}
+
+/// Encodes the captures of the message as non-verbose message with the headers of the original
+/// one. Each capture is a UTF-8 string prefixed by its length, missing captures are empty.
+fn encode_non_verbose(msg: &Message, message_id: u32, names: &[String], extraction: &Extraction) -> Vec<u8> {
+    let mut payload = message_id.to_le_bytes().to_vec();
+    for name in names {
+        let value = extraction.values(name).next().unwrap_or_default();
+        let value = &value.as_bytes()[..value.len().min(u16::MAX as usize)];
+        payload.extend_from_slice(&(value.len() as u16).to_le_bytes());
+        payload.extend_from_slice(value);
+    }
+
+    let header = msg.extended_header.as_ref();
+    let level = msg.log_level().unwrap_or(MessageTypeInfoLog::Info);
+    let headers = LogHeaders {
+        seconds: msg.storage_header.seconds(),
+        microseconds: msg.storage_header.microseconds(),
+        ecu_id: msg.ecu_id(),
+        counter: msg.standard_header.counter() as u8,
+        session_id: msg.session_id(),
+        timestamp: msg.standard_header.timestamp(),
+        is_big_endian: false,
+        app_id: header.map_or("", |header| header.app_id()),
+        context_id: header.map_or("", |header| header.context_id()),
+        level,
+        verbose: false,
+        num_of_args: 0,
+    };
+    let mut message = vec![];
+    write_log_headers(&mut message, &headers, payload.len());
+    message.extend_from_slice(&payload);
+    message
+}
@@ -0,0 +1,288 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+use roxmltree::{Document, Node};
+use serde_derive::{Deserialize, Serialize};
+
+/// Coded type of a signal, from the BASE-DATA-TYPE of its coding.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+enum SignalType {
+    Bool,
+    UInt(usize),
+    SInt(usize),
+    Float32,
+    Float64,
+    /// string prefixed by its 16 bit length
+    String,
+}
+
+impl SignalType {
+    fn from(base_data_type: &str) -> Option<SignalType> {
+        let signal_type = match base_data_type {
+            "A_BOOLEAN" => SignalType::Bool,
+            "A_UINT8" => SignalType::UInt(1),
+            "A_UINT16" => SignalType::UInt(2),
+            "A_UINT32" => SignalType::UInt(4),
+            "A_UINT64" => SignalType::UInt(8),
+            "A_INT8" => SignalType::SInt(1),
+            "A_INT16" => SignalType::SInt(2),
+            "A_INT32" => SignalType::SInt(4),
+            "A_INT64" => SignalType::SInt(8),
+            "A_FLOAT32" => SignalType::Float32,
+            "A_FLOAT64" => SignalType::Float64,
+            "A_UNICODE2STRING" | "A_ASCIISTRING" => SignalType::String,
+            _ => return None,
+        };
+        Some(signal_type)
+    }
+}
+
+/// Argument of a non-verbose message: a signal read from the payload or a constant text, which
+/// is part of the description only.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum Argument {
+    Signal { name: String, signal_type: SignalType, unit: Option<String> },
+    Text,
+}
+
+/// Descriptions of non-verbose messages read from a FIBEX file, so their arguments can be decoded
+/// by name.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Fibex {
+    frames: HashMap<u32, Vec<Argument>>,
+}
+
+impl Fibex {
+    /// Reads the frames `ID_<message id>` of the FIBEX file. The arguments are the PDUs of a
+    /// frame in sequence, named after the SHORT-NAME of the PDU; PDUs without signal carry
+    /// constant text. The unit of a signal is the one referenced by the COMPU-METHOD of its
+    /// coding. Frames with signals of unsupported types are skipped.
+    pub fn read(file_path: &Path) -> Result<Fibex, String> {
+        let contents = fs::read_to_string(file_path).map_err(|err| format!("error on reading FIBEX file {file_path:?}: {err}"))?;
+        let document = Document::parse(&contents).map_err(|err| format!("invalid FIBEX file {file_path:?}: {err}"))?;
+        let elements_by_id = |tag: &str| -> HashMap<&str, Node> {
+            document.descendants()
+                .filter(|node| node.tag_name().name() == tag)
+                .filter_map(|node| Some((node.attribute("ID")?, node)))
+                .collect()
+        };
+        let pdus = elements_by_id("PDU");
+        let signals = elements_by_id("SIGNAL");
+        let codings = elements_by_id("CODING");
+        let units = elements_by_id("UNIT");
+
+        let signal_type = |signal_id: &str| -> Option<SignalType> {
+            let coding_id = child(*signals.get(signal_id)?, "CODING-REF")?.attribute("ID-REF")?;
+            let coded_type = child(*codings.get(coding_id)?, "CODED-TYPE")?;
+            let base_data_type = coded_type.attributes().find(|attribute| attribute.name() == "BASE-DATA-TYPE")?.value();
+            SignalType::from(base_data_type)
+        };
+        let signal_unit = |signal_id: &str| -> Option<String> {
+            let coding_id = child(*signals.get(signal_id)?, "CODING-REF")?.attribute("ID-REF")?;
+            let unit_id = codings.get(coding_id)?.descendants()
+                .find(|node| node.tag_name().name() == "UNIT-REF")?
+                .attribute("ID-REF")?;
+            let unit = *units.get(unit_id)?;
+            child(unit, "DISPLAY-NAME").or_else(|| child(unit, "SHORT-NAME")).and_then(text)
+                .filter(|unit| !unit.is_empty())
+                .map(str::to_string)
+        };
+
+        let mut frames = HashMap::new();
+        for frame in document.descendants().filter(|node| node.tag_name().name() == "FRAME") {
+            let Some(message_id) = frame.attribute("ID").and_then(|id| id.strip_prefix("ID_")?.parse().ok()) else {
+                continue;
+            };
+            let mut instances: Vec<_> = frame.descendants()
+                .filter(|node| node.tag_name().name() == "PDU-INSTANCE")
+                .filter_map(|instance| {
+                    let sequence_number: usize = text(child(instance, "SEQUENCE-NUMBER")?)?.parse().ok()?;
+                    Some((sequence_number, child(instance, "PDU-REF")?.attribute("ID-REF")?))
+                })
+                .collect();
+            instances.sort_by_key(|(sequence_number, _)| *sequence_number);
+
+            let arguments: Option<Vec<_>> = instances.iter().map(|(_, pdu_id)| {
+                let pdu = *pdus.get(pdu_id)?;
+                let signal_ref = pdu.descendants()
+                    .find(|node| node.tag_name().name() == "SIGNAL-REF")
+                    .and_then(|signal_ref| signal_ref.attribute("ID-REF"));
+                match signal_ref {
+                    Some(signal_id) => {
+                        let name = child(pdu, "SHORT-NAME").and_then(text).unwrap_or(pdu_id).to_string();
+                        Some(Argument::Signal { name, signal_type: signal_type(signal_id)?, unit: signal_unit(signal_id) })
+                    },
+                    None => Some(Argument::Text),
+                }
+            }).collect();
+            if let Some(arguments) = arguments {
+                frames.insert(message_id, arguments);
+            }
+        }
+        Ok(Fibex { frames })
+    }
+
+    /// Names of the signals of the given messages.
+    pub fn signal_names(&self, message_ids: &[u32]) -> Vec<String> {
+        let mut names: Vec<String> = vec![];
+        for arguments in message_ids.iter().filter_map(|message_id| self.frames.get(message_id)) {
+            for argument in arguments {
+                if let Argument::Signal { name, .. } = argument {
+                    if !names.contains(name) {
+                        names.push(name.clone());
+                    }
+                }
+            }
+        }
+        names
+    }
+
+    /// Names and units of the signals of the given messages which have a unit.
+    pub fn signal_units(&self, message_ids: &[u32]) -> Vec<(String, String)> {
+        let mut units: Vec<(String, String)> = vec![];
+        for arguments in message_ids.iter().filter_map(|message_id| self.frames.get(message_id)) {
+            for argument in arguments {
+                if let Argument::Signal { name, unit: Some(unit), .. } = argument {
+                    if !units.iter().any(|(known, _)| known == name) {
+                        units.push((name.clone(), unit.clone()));
+                    }
+                }
+            }
+        }
+        units
+    }
+
+    /// Decodes the arguments of a non-verbose message to name and value of each signal, `None` if
+    /// the message is unknown or its payload does not fit the description.
+    pub fn decode(&self, message_id: u32, data: &[u8], is_big_endian: bool) -> Option<Vec<(String, String)>> {
+        let mut reader = SignalReader { data, index: 0, is_big_endian };
+        let mut signals = vec![];
+        for argument in self.frames.get(&message_id)? {
+            if let Argument::Signal { name, signal_type, .. } = argument {
+                signals.push((name.clone(), reader.read(*signal_type)?));
+            }
+        }
+        Some(signals)
+    }
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, tag: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|child| child.tag_name().name() == tag)
+}
+
+fn text<'a>(node: Node<'a, '_>) -> Option<&'a str> {
+    node.text().map(str::trim)
+}
+
+struct SignalReader<'d> {
+    data: &'d [u8],
+    index: usize,
+    is_big_endian: bool,
+}
+
+impl SignalReader<'_> {
+    fn take<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let bytes = self.data.get(self.index..self.index.checked_add(N)?)?.try_into().ok()?;
+        self.index += N;
+        Some(bytes)
+    }
+
+    fn read(&mut self, signal_type: SignalType) -> Option<String> {
+        macro_rules! number {
+            ($type:ty) => {{
+                let bytes = self.take()?;
+                if self.is_big_endian { <$type>::from_be_bytes(bytes) } else { <$type>::from_le_bytes(bytes) }.to_string()
+            }}
+        }
+        let value = match signal_type {
+            SignalType::Bool => (self.take::<1>()?[0] != 0).to_string(),
+            SignalType::UInt(1) => number!(u8),
+            SignalType::UInt(2) => number!(u16),
+            SignalType::UInt(4) => number!(u32),
+            SignalType::UInt(_) => number!(u64),
+            SignalType::SInt(1) => number!(i8),
+            SignalType::SInt(2) => number!(i16),
+            SignalType::SInt(4) => number!(i32),
+            SignalType::SInt(_) => number!(i64),
+            SignalType::Float32 => number!(f32),
+            SignalType::Float64 => number!(f64),
+            SignalType::String => {
+                let len = self.take().map(|bytes| if self.is_big_endian { u16::from_be_bytes(bytes) } else { u16::from_le_bytes(bytes) })? as usize;
+                let bytes = self.data.get(self.index..self.index.checked_add(len)?)?;
+                self.index += len;
+                String::from_utf8_lossy(bytes).trim_end_matches(char::from(0)).to_string()
+            },
+        };
+        Some(value)
+    }
+}
+
+/// Generates the FIBEX description of the non-verbose message `message_id`, whose payload is one
+/// UTF-8 string per signal, each prefixed by its 16 bit length. Every signal gets a PDU of its
+/// own, named after the signal, the layout DLT viewers expect for non-verbose log messages.
+pub fn fibex_xml(message_id: u32, signal_names: &[String]) -> String {
+    let mut pdu_instances = String::new();
+    let mut pdus = String::new();
+    for (idx, name) in signal_names.iter().enumerate() {
+        let _ = write!(pdu_instances, r#"
+          <fx:PDU-INSTANCE ID="P_{message_id}_{idx}">
+            <fx:PDU-REF ID-REF="PDU_{message_id}_{idx}"/>
+            <fx:SEQUENCE-NUMBER>{idx}</fx:SEQUENCE-NUMBER>
+          </fx:PDU-INSTANCE>"#);
+        let _ = write!(pdus, r#"
+      <fx:PDU ID="PDU_{message_id}_{idx}">
+        <ho:SHORT-NAME>{name}</ho:SHORT-NAME>
+        <ho:DESC>{name}</ho:DESC>
+        <fx:BYTE-LENGTH>0</fx:BYTE-LENGTH>
+        <fx:PDU-TYPE>OTHER</fx:PDU-TYPE>
+        <fx:SIGNAL-INSTANCES>
+          <fx:SIGNAL-INSTANCE ID="S_{message_id}_{idx}">
+            <fx:SEQUENCE-NUMBER>0</fx:SEQUENCE-NUMBER>
+            <fx:SIGNAL-REF ID-REF="S_STRG_UTF8"/>
+          </fx:SIGNAL-INSTANCE>
+        </fx:SIGNAL-INSTANCES>
+      </fx:PDU>"#);
+    }
+
+    format!(r#"<?xml version="1.0" encoding="UTF-8"?>
+<fx:FIBEX xmlns:fx="http://www.asam.net/xml/fbx" xmlns:ho="http://www.asam.net/xml" VERSION="3.1.0">
+  <fx:PROJECT ID="dlt-kraken">
+    <ho:SHORT-NAME>dlt-kraken</ho:SHORT-NAME>
+  </fx:PROJECT>
+  <fx:ELEMENTS>
+    <fx:FRAMES>
+      <fx:FRAME ID="ID_{message_id}">
+        <ho:SHORT-NAME>ID_{message_id}</ho:SHORT-NAME>
+        <fx:BYTE-LENGTH>0</fx:BYTE-LENGTH>
+        <fx:FRAME-TYPE>OTHER</fx:FRAME-TYPE>
+        <fx:PDU-INSTANCES>{pdu_instances}
+        </fx:PDU-INSTANCES>
+        <fx:MANUFACTURER-EXTENSION>
+          <MESSAGE_TYPE>DLT_TYPE_LOG</MESSAGE_TYPE>
+          <MESSAGE_INFO>DLT_LOG_INFO</MESSAGE_INFO>
+        </fx:MANUFACTURER-EXTENSION>
+      </fx:FRAME>
+    </fx:FRAMES>
+    <fx:PDUS>{pdus}
+    </fx:PDUS>
+    <fx:SIGNALS>
+      <fx:SIGNAL ID="S_STRG_UTF8">
+        <ho:SHORT-NAME>S_STRG_UTF8</ho:SHORT-NAME>
+        <fx:CODING-REF ID-REF="UTF8"/>
+      </fx:SIGNAL>
+    </fx:SIGNALS>
+  </fx:ELEMENTS>
+  <fx:PROCESSING-INFORMATION>
+    <fx:CODINGS>
+      <fx:CODING ID="UTF8">
+        <ho:SHORT-NAME>UTF8</ho:SHORT-NAME>
+        <ho:CODED-TYPE ho:BASE-DATA-TYPE="A_UNICODE2STRING" CATEGORY="LEADING-LENGTH-INFO-TYPE" ENCODING="UTF-8">
+          <ho:BIT-LENGTH>16</ho:BIT-LENGTH>
+        </ho:CODED-TYPE>
+      </fx:CODING>
+    </fx:CODINGS>
+  </fx:PROCESSING-INFORMATION>
+</fx:FIBEX>
+"#)
+}
@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use crate::dlt::Message;
+use crate::dlt::payload::Value;
+
+const FILE_TRANSFER_START: &str = "FLST";
+const FILE_TRANSFER_DATA: &str = "FLDA";
+const FILE_TRANSFER_END: &str = "FLFI";
+
+/// Messages of the dlt-system file transfer protocol. Each message is enclosed by its marker
+/// string, e.g. `FLST, serial, name, size, date, packages, buffer size, FLST`.
+#[derive(Debug)]
+pub enum FileTransfer<'d> {
+    Start {
+        serial: u64,
+        name: &'d str,
+        size: u64,
+        packages: u64,
+        buffer_size: u64,
+    },
+    Data {
+        serial: u64,
+        package: u64,
+        data: &'d [u8],
+    },
+    End {
+        serial: u64,
+    },
+}
+
+fn unsigned(value: &Value) -> Option<u64> {
+    match value {
+        Value::UInt8(value) => Some(*value as u64),
+        Value::UInt16(value) => Some(*value as u64),
+        Value::UInt32(value) => Some(*value as u64),
+        Value::UInt64(value) => Some(*value),
+        _ => None,
+    }
+}
+
+impl<'d> FileTransfer<'d> {
+    pub fn from_message(msg: &Message<'d>) -> Option<FileTransfer<'d>> {
+        let (Some(Value::String(marker)), Some(Value::String(end_marker))) = (msg.payload.first(), msg.payload.last()) else {
+            return None;
+        };
+        if marker != end_marker {
+            return None;
+        }
+
+        match (*marker, &msg.payload[1..]) {
+            (FILE_TRANSFER_START, [serial, Value::String(name), size, _date, packages, buffer_size, _]) => {
+                Some(FileTransfer::Start {
+                    serial: unsigned(serial)?,
+                    name,
+                    size: unsigned(size)?,
+                    packages: unsigned(packages)?,
+                    buffer_size: unsigned(buffer_size)?,
+                })
+            },
+            (FILE_TRANSFER_DATA, [serial, package, Value::Raw(data), _]) => {
+                Some(FileTransfer::Data { serial: unsigned(serial)?, package: unsigned(package)?, data })
+            },
+            (FILE_TRANSFER_END, [serial, _]) => Some(FileTransfer::End { serial: unsigned(serial)? }),
+            _ => None,
+        }
+    }
+}
+
+struct PartialFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    packages: u64,
+    buffer_size: u64,
+    received: u64,
+}
+
+/// Reassembles transferred files, writing each package to its position in the output file as it
+/// arrives.
+pub struct FileExtractor {
+    output_dir: PathBuf,
+    files: HashMap<u64, PartialFile>,
+}
+
+impl FileExtractor {
+    pub fn new(output_dir: &Path) -> FileExtractor {
+        FileExtractor { output_dir: output_dir.to_path_buf(), files: HashMap::new() }
+    }
+
+    pub fn add(&mut self, transfer: FileTransfer) -> Result<(), String> {
+        match transfer {
+            FileTransfer::Start { serial, name, size, packages, buffer_size } => {
+                // never trust the sender with the location, only use the file name
+                let file_name = Path::new(name).file_name()
+                    .ok_or_else(|| format!("file transfer {serial} has an invalid file name '{name}'"))?;
+                let path = self.output_dir.join(file_name);
+                let file = File::create(&path)
+                    .map_err(|err| format!("error on creating {path:?}: {err}"))?;
+                self.files.insert(serial, PartialFile { path, file, size, packages, buffer_size, received: 0 });
+                Ok(())
+            },
+            FileTransfer::Data { serial, package, data } => {
+                let partial = self.files.get_mut(&serial)
+                    .ok_or_else(|| format!("data for unknown file transfer {serial}"))?;
+                // packages are counted starting with 1
+                let offset = package.saturating_sub(1) * partial.buffer_size;
+                partial.file.seek(SeekFrom::Start(offset))
+                    .and_then(|_| partial.file.write_all(data))
+                    .map_err(|err| format!("error on writing {:?}: {err}", partial.path))?;
+                partial.received += 1;
+                Ok(())
+            },
+            FileTransfer::End { serial } => {
+                let partial = self.files.remove(&serial)
+                    .ok_or_else(|| format!("end of unknown file transfer {serial}"))?;
+                if partial.received != partial.packages {
+                    return Err(format!("{:?} incomplete: received {} of {} packages", partial.path, partial.received, partial.packages));
+                }
+                println!("extracted {:?} ({} bytes)", partial.path, partial.size);
+                Ok(())
+            },
+        }
+    }
+
+    /// Reports the transfers that were started but never finished.
+    pub fn finish(self) -> Vec<String> {
+        self.files.values()
+            .map(|partial| format!("{:?} incomplete: transfer did not finish, received {} of {} packages", partial.path, partial.received, partial.packages))
+            .collect()
+    }
+}
@@ -1,8 +1,18 @@
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
 use std::time::Duration;
 use regex::{Captures, Regex, RegexSet};
-use crate::dlt::Message;
+use crate::config::{AppTraceType, EcuSource, FirstPer, LogLevel};
+use crate::dlt::control::BufferOverflow;
+use crate::dlt::fibex::Fibex;
+use crate::dlt::headers::{MessageType, MessageTypeInfoAppTrace};
+use crate::dlt::{Message, MessageTypeInfoLog};
 use crate::dlt::payload::Value;
+use crate::dlt::query::Query;
+use crate::dlt::wasm::WasmModule;
+
+/// Last nanosecond of a second.
+const MAX_NANOSECONDS: u32 = 999_999_999;
 
 #[derive(Debug)]
 pub struct Pattern {
@@ -11,13 +21,25 @@ pub struct Pattern {
 }
 
 impl Pattern {
-    pub fn from<I, S>(expressions: I) -> Pattern
+    pub fn from<I, S>(expressions: I) -> Result<Pattern, regex::Error>
+    where
+        S: AsRef<str>,
+        I: IntoIterator<Item = S> {
+        let regex_set = RegexSet::new(expressions)?;
+        let regexes = regex_set.patterns().iter().map(|pat| Regex::new(pat)).collect::<Result<_, _>>()?;
+        Ok(Pattern { regex_set, regexes })
+    }
+
+    /// Compiles the expressions, with `ignore_case` as if each started with `(?i)`.
+    pub fn with_case<I, S>(expressions: I, ignore_case: bool) -> Result<Pattern, regex::Error>
     where
         S: AsRef<str>,
         I: IntoIterator<Item = S> {
-        let regex_set = RegexSet::new(expressions).unwrap();
-        let regexes: Vec<_> = regex_set.patterns().iter().map(|pat| Regex::new(pat).unwrap()).collect();
-        Pattern { regex_set, regexes }
+        if ignore_case {
+            Pattern::from(expressions.into_iter().map(|expression| format!("(?i){}", expression.as_ref())))
+        } else {
+            Pattern::from(expressions)
+        }
     }
 
     pub fn capture_names(patterns: &Vec<String>) -> Option<Vec<String>> {
@@ -40,7 +62,11 @@ impl Pattern {
         }
     }
 
-    fn captures<'d>(& self, string: &'d str) -> Option<Vec<Captures<'d>>> {
+    pub fn is_match(&self, string: &str) -> bool {
+        self.regex_set.is_match(string)
+    }
+
+    pub fn captures<'d>(& self, string: &'d str) -> Option<Vec<Captures<'d>>> {
         let captures : Vec<_> = self.regex_set.matches(string).into_iter()
             .map(|match_idx| &self.regexes[match_idx])
             .filter_map(|regex| regex.captures(string)).collect();
@@ -52,53 +78,248 @@ impl Pattern {
     }
 }
 
+/// Values extracted from a message, by the patterns from its string arguments, by the FIBEX
+/// description from its non-verbose arguments or by a WASM transform. All are looked up by name,
+/// so one output column can take the value from either kind of message.
+#[derive(Debug, Default)]
+pub struct Extraction<'d> {
+    captures: Vec<Captures<'d>>,
+    signals: Vec<(String, String)>,
+}
+
+impl Extraction<'_> {
+    /// All values of the given name, a name can be captured by several patterns.
+    pub fn values<'e>(&'e self, name: &'e str) -> impl Iterator<Item = &'e str> {
+        let captures = self.captures.iter().filter_map(move |capture| capture.name(name)).map(|value| value.as_str());
+        let signals = self.signals.iter().filter(move |(signal, _)| signal == name).map(|(_, value)| value.as_str());
+        captures.chain(signals)
+    }
+}
+
+/// Whether the id matches the pattern, in which `*` stands for any number of characters and `?`
+/// for exactly one, e.g. `NAV*` or `??LO`.
+pub fn id_matches(pattern: &str, id: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let id: Vec<char> = id.chars().collect();
+    let (mut pattern_idx, mut id_idx) = (0, 0);
+    // position of the last `*` and of the id when it was reached, to backtrack to
+    let mut star = None;
+    while id_idx < id.len() {
+        match pattern.get(pattern_idx) {
+            Some('*') => {
+                star = Some((pattern_idx, id_idx));
+                pattern_idx += 1;
+            },
+            Some(c) if *c == '?' || *c == id[id_idx] => {
+                pattern_idx += 1;
+                id_idx += 1;
+            },
+            _ => match star {
+                // let the `*` take one more character
+                Some((star_idx, star_id_idx)) => {
+                    pattern_idx = star_idx + 1;
+                    id_idx = star_id_idx + 1;
+                    star = Some((star_idx, id_idx));
+                },
+                None => return false,
+            },
+        }
+    }
+    pattern[pattern_idx..].iter().all(|c| *c == '*')
+}
+
+fn matches_any(patterns: &[String], id: &str) -> bool {
+    patterns.iter().any(|pattern| id_matches(pattern, id))
+}
+
 #[derive(Debug, Eq, PartialEq, Hash)]
 pub enum FilterId {
     EcuId,
     ContextId,
     AppId,
+    ExcludeEcuId,
+    ExcludeContextId,
+    ExcludeAppId,
+    ExcludePatterns,
     Time,
+    Uptime,
+    KeepOnePer,
+    FirstPer,
+    Dedup,
+    Section,
+    Context,
     Patterns,
+    MessageIds,
+    HeaderOnly,
+    BufferOverflow,
+    AppTrace,
+    LogLevel,
+    Query,
+    NonVerbose,
+    Variables,
+    Wasm,
 }
 
 #[derive(Debug)]
 pub enum FilterType {
-    EcuId(String),
-    ContextId(String),
-    AppId(String),
+    /// the message passes if it has any of the ids, which may contain the wildcards `*` and `?`
+    EcuId(Vec<String>),
+    ContextId(Vec<String>),
+    AppId(Vec<String>),
+    /// ids of the messages to drop, with the same wildcards
+    Exclude(Vec<String>),
+    /// the messages whose payload matches any of the patterns are dropped
+    ExcludePatterns(Pattern),
+    /// storage time window since the epoch, both ends included
     Time(Duration, Duration),
+    /// window of the timestamp of the standard header, the time since boot, both ends included
+    Uptime(Duration, Duration),
+    /// interval the messages of each context are thinned out to, see `Downsampler`
+    KeepOnePer(Duration),
+    /// only the first match of each span passes
+    FirstPer(FirstPer),
+    /// repeats of the last payload of a context are dropped, see `Deduplicator`
+    Dedup,
+    /// start and stop pattern of the sections that pass, see `Sections`
+    Section(Regex, Regex),
+    /// messages written before and after each match, see `ContextLines`
+    Context(usize, usize),
     Patterns(Pattern),
+    /// only non-verbose messages with any of the message ids pass
+    MessageIds(Vec<u32>),
+    /// whether the messages without payload pass, or only those
+    HeaderOnly(bool),
+    BufferOverflow,
+    AppTrace(AppTraceType),
+    /// least severe level of the log messages that pass
+    LogLevel(LogLevel),
+    Query(Query),
+    /// non-verbose messages whose arguments are extracted with the FIBEX description
+    NonVerbose(Fibex, Vec<u32>),
+    /// names of the arguments sent with variable info whose values are extracted
+    Variables(Vec<String>),
+    Wasm(Box<WasmModule>),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct Filter {
     filters: HashMap<FilterId, FilterType>,
+    /// warn about ids in the trace that only differ from the filtered ones by padding
+    strict_ids: bool,
+    /// ECU id of the messages the ECU ids are compared with
+    ecu_source: EcuSource,
+    /// ids of the trace already warned about
+    padding_warnings: RefCell<HashSet<String>>,
 }
 
 impl Filter {
     pub fn new() -> Filter {
-        Filter { filters: HashMap::new() }
+        Filter::default()
+    }
+
+    pub fn set_strict_ids(&mut self, strict_ids: bool) -> &mut Filter {
+        self.strict_ids = strict_ids;
+        self
     }
 
-    pub fn add<'f>(&'f mut self, key : FilterId, value: FilterType) -> &'f mut Filter {
+    pub fn set_ecu_source(&mut self, ecu_source: EcuSource) -> &mut Filter {
+        self.ecu_source = ecu_source;
+        self
+    }
+
+    /// Warns once per id if the id of the message does not match any of the filtered ones, but
+    /// would without its padding, e.g. "APP " in the trace and "APP" in the config.
+    fn check_padding(&self, kind: &str, id: &str, filtered: &[String]) {
+        if !self.strict_ids {
+            return;
+        }
+        let unpadded = id.trim_matches([' ', '\0']);
+        if unpadded != id && matches_any(filtered, unpadded)
+            && self.padding_warnings.borrow_mut().insert(format!("{kind} {id}")) {
+            eprintln!("{kind} {id:?} of the trace differs from the filtered {unpadded:?} only by padding, it does not match");
+        }
+    }
+
+    pub fn add(&mut self, key : FilterId, value: FilterType) -> &mut Filter {
         self.filters.insert(key, value);
         self
     }
 
+    pub fn keep_one_per(&self) -> Option<Duration> {
+        match self.filters.get(&FilterId::KeepOnePer) {
+            Some(FilterType::KeepOnePer(interval)) => Some(*interval),
+            _ => None,
+        }
+    }
+
+    pub fn first_per(&self) -> Option<FirstPer> {
+        match self.filters.get(&FilterId::FirstPer) {
+            Some(FilterType::FirstPer(first_per)) => Some(*first_per),
+            _ => None,
+        }
+    }
+
+    pub fn dedup(&self) -> bool {
+        self.filters.contains_key(&FilterId::Dedup)
+    }
+
+    /// Messages to write before and after each match.
+    pub fn context(&self) -> (usize, usize) {
+        match self.filters.get(&FilterId::Context) {
+            Some(FilterType::Context(before, after)) => (*before, *after),
+            _ => (0, 0),
+        }
+    }
+
+    pub fn section(&self) -> Option<(&Regex, &Regex)> {
+        match self.filters.get(&FilterId::Section) {
+            Some(FilterType::Section(start, stop)) => Some((start, stop)),
+            _ => None,
+        }
+    }
+
+    fn is_excluded(&self, key: FilterId, id: &str) -> bool {
+        matches!(self.filters.get(&key), Some(FilterType::Exclude(excluded)) if matches_any(excluded, id))
+    }
+
+    /// ECU ids of the message compared with the filtered ones, according to the ECU source.
+    fn ecu_ids<'m>(&self, msg: &'m Message) -> Vec<&'m str> {
+        let storage = msg.storage_header.ecu_id().as_str();
+        let header = msg.standard_header.ecu_id().as_deref();
+        match self.ecu_source {
+            EcuSource::Storage => vec![storage],
+            EcuSource::Header => header.into_iter().collect(),
+            EcuSource::Either => [Some(storage), header].into_iter().flatten().collect(),
+        }
+    }
+
     pub fn filter_ecu_id(&self, msg: &Message) -> bool {
+        let ecu_ids = self.ecu_ids(msg);
+        if ecu_ids.iter().any(|ecu_id| self.is_excluded(FilterId::ExcludeEcuId, ecu_id)) {
+            return false;
+        }
         match self.filters.get(&FilterId::EcuId) {
-            Some(FilterType::EcuId(ecu_id)) if ecu_id == msg.storage_header.ecu_id() => true,
-            Some(FilterType::EcuId(_)) => false,
+            Some(FilterType::EcuId(filtered)) if ecu_ids.iter().any(|ecu_id| matches_any(filtered, ecu_id)) => true,
+            Some(FilterType::EcuId(filtered)) => {
+                for ecu_id in ecu_ids {
+                    self.check_padding("ecu id", ecu_id, filtered);
+                }
+                false
+            },
             _ => true,
         }
     }
 
     pub fn filter_app_id(&self, msg: &Message) -> bool {
         match &msg.extended_header {
+            Some(extended_header) if self.is_excluded(FilterId::ExcludeAppId, extended_header.app_id()) => false,
             Some(extended_header) => {
                 match self.filters.get(&FilterId::AppId) {
-                    Some(FilterType::AppId(app_id)) if app_id == extended_header.app_id() => true,
-                    Some(FilterType::AppId(_)) => false,
+                    Some(FilterType::AppId(app_ids)) if matches_any(app_ids, extended_header.app_id()) => true,
+                    Some(FilterType::AppId(app_ids)) => {
+                        self.check_padding("app id", extended_header.app_id(), app_ids);
+                        false
+                    },
                     _ => true,
                 }
             },
@@ -108,10 +329,14 @@ impl Filter {
 
     pub fn filter_context_id(&self, msg: &Message) -> bool {
         match &msg.extended_header {
+            Some(extended_header) if self.is_excluded(FilterId::ExcludeContextId, extended_header.context_id()) => false,
             Some(extended_header) => {
                 match self.filters.get(&FilterId::ContextId) {
-                    Some(FilterType::ContextId(app_id)) if app_id == extended_header.context_id() => true,
-                    Some(FilterType::ContextId(_)) => false,
+                    Some(FilterType::ContextId(context_ids)) if matches_any(context_ids, extended_header.context_id()) => true,
+                    Some(FilterType::ContextId(context_ids)) => {
+                        self.check_padding("context id", extended_header.context_id(), context_ids);
+                        false
+                    },
                     _ => true,
                 }
             },
@@ -119,27 +344,303 @@ impl Filter {
         }
     }
 
+    /// Keeps the messages with a string argument matching any of the patterns, or the
+    /// non-verbose messages with one of the message ids described by the FIBEX file.
+    pub fn filter_patterns(&self, msg: &Message) -> bool {
+        let patterns = self.filters.get(&FilterId::Patterns);
+        let non_verbose = self.filters.get(&FilterId::NonVerbose);
+        if patterns.is_none() && non_verbose.is_none() {
+            return true;
+        }
+        let is_pattern_match = matches!(patterns, Some(FilterType::Patterns(patterns))
+            if msg.payload.iter().any(|val| matches!(val.value(), Value::String(string) if patterns.is_match(string))));
+        let is_non_verbose_match = matches!((non_verbose, msg.payload.first()),
+            (Some(FilterType::NonVerbose(_, message_ids)), Some(Value::NonVerbose { message_id, .. })) if message_ids.contains(message_id));
+        is_pattern_match || is_non_verbose_match
+    }
+
+    pub fn filter_message_id(&self, msg: &Message) -> bool {
+        match self.filters.get(&FilterId::MessageIds) {
+            Some(FilterType::MessageIds(message_ids)) => matches!(msg.payload.first(),
+                Some(Value::NonVerbose { message_id, .. }) if message_ids.contains(message_id)),
+            _ => true,
+        }
+    }
+
+    pub fn filter_buffer_overflow(&self, msg: &Message) -> bool {
+        match self.filters.get(&FilterId::BufferOverflow) {
+            Some(FilterType::BufferOverflow) => BufferOverflow::from_message(msg).is_some(),
+            _ => true,
+        }
+    }
+
+    pub fn filter_app_trace(&self, msg: &Message) -> bool {
+        match self.filters.get(&FilterId::AppTrace) {
+            Some(FilterType::AppTrace(app_trace)) => {
+                let Some(header) = msg.extended_header.as_ref().filter(|header| matches!(header.msg_type(), MessageType::AppTrace)) else {
+                    return false;
+                };
+                matches!((app_trace, header.msg_type_info_app_trace()),
+                    (AppTraceType::Variable, Some(MessageTypeInfoAppTrace::Variable))
+                    | (AppTraceType::FunctionIn, Some(MessageTypeInfoAppTrace::FunctionIn))
+                    | (AppTraceType::FunctionOut, Some(MessageTypeInfoAppTrace::FunctionOut))
+                    | (AppTraceType::State, Some(MessageTypeInfoAppTrace::State))
+                    | (AppTraceType::Vfb, Some(MessageTypeInfoAppTrace::Vfb)))
+            },
+            _ => true,
+        }
+    }
+
+    pub fn filter_time(&self, msg: &Message) -> bool {
+        match self.filters.get(&FilterId::Time) {
+            Some(FilterType::Time(from, to)) => {
+                // out of range subseconds are taken as the end of the second instead of
+                // carrying over into the next one
+                let nanoseconds = msg.storage_header.nanoseconds().map_or(MAX_NANOSECONDS, |nanoseconds| nanoseconds.min(MAX_NANOSECONDS));
+                let time = Duration::new(msg.storage_header.seconds() as u64, nanoseconds);
+                (from..=to).contains(&&time)
+            },
+            _ => true,
+        }
+    }
+
+    /// Drops the messages outside the uptime window, including those without timestamp.
+    pub fn filter_uptime(&self, msg: &Message) -> bool {
+        match self.filters.get(&FilterId::Uptime) {
+            Some(FilterType::Uptime(from, to)) => msg.standard_header.timestamp()
+                // the timestamp counts in 0.1 milliseconds
+                .map(|timestamp| Duration::from_micros(timestamp as u64 * 100))
+                .is_some_and(|uptime| (from..=to).contains(&&uptime)),
+            _ => true,
+        }
+    }
+
+    /// Drops the log messages less severe than the level, messages of other types pass.
+    pub fn filter_log_level(&self, msg: &Message) -> bool {
+        match (self.filters.get(&FilterId::LogLevel), msg.log_level()) {
+            (Some(FilterType::LogLevel(min_level)), Some(level)) => {
+                let min_level = match min_level {
+                    LogLevel::Fatal => MessageTypeInfoLog::Fatal,
+                    LogLevel::Error => MessageTypeInfoLog::Error,
+                    LogLevel::Warn => MessageTypeInfoLog::Warn,
+                    LogLevel::Info => MessageTypeInfoLog::Info,
+                    LogLevel::Debug => MessageTypeInfoLog::Debug,
+                    LogLevel::Verbose => MessageTypeInfoLog::Verbose,
+                };
+                level <= min_level
+            },
+            _ => true,
+        }
+    }
+
+    /// Keeps only the messages without payload, or drops them, as `header_only` is set.
+    pub fn filter_header_only(&self, msg: &Message) -> bool {
+        match self.filters.get(&FilterId::HeaderOnly) {
+            Some(FilterType::HeaderOnly(header_only)) => msg.is_header_only() == *header_only,
+            _ => true,
+        }
+    }
+
+    /// Drops the messages whose payload text matches any of the exclusion patterns.
+    pub fn filter_exclude_patterns(&self, msg: &Message) -> bool {
+        match self.filters.get(&FilterId::ExcludePatterns) {
+            Some(FilterType::ExcludePatterns(patterns)) => !patterns.is_match(&msg.payload_text()),
+            _ => true,
+        }
+    }
+
+    pub fn filter_query(&self, msg: &Message) -> bool {
+        match self.filters.get(&FilterId::Query) {
+            Some(FilterType::Query(query)) => query.matches(msg),
+            _ => true,
+        }
+    }
+
+    pub fn filter_wasm(&self, msg: &Message) -> bool {
+        match self.filters.get(&FilterId::Wasm) {
+            Some(FilterType::Wasm(module)) => module.filter(msg),
+            _ => true,
+        }
+    }
+
+    /// Whether the message passes all the filters.
+    pub fn matches(&self, msg: &Message) -> bool {
+        self.filter_ecu_id(msg)
+            && self.filter_app_id(msg)
+            && self.filter_context_id(msg)
+            && self.filter_time(msg)
+            && self.filter_uptime(msg)
+            && self.filter_log_level(msg)
+            && self.filter_message_id(msg)
+            && self.filter_patterns(msg)
+            && self.filter_header_only(msg)
+            && self.filter_buffer_overflow(msg)
+            && self.filter_app_trace(msg)
+            && self.filter_exclude_patterns(msg)
+            && self.filter_query(msg)
+            && self.filter_wasm(msg)
+    }
+
     // TODO: does this belong here? Not really a filter...
-    pub fn find_patterns<'d>(&self, msg: &'d Message) -> Option<Vec<Captures<'d>>> {
-        match self.filters.get(&FilterId::Patterns) {
-            Some(FilterType::Patterns(patterns)) => {
-                for val in &msg.payload {
-                    match val {
-                        Value::String(string) => {
-                            let capture_matches = patterns.captures(string);
-
-                            if capture_matches.is_some() {
-                                return capture_matches
-                            } else {
-                                continue
-                            }
-                        },
-                        _ => continue,
-                    }
+    /// Extracts the values of the patterns, of the described non-verbose messages, of the named
+    /// arguments and of the WASM transform, `None` if the filter has none of them or if its
+    /// patterns do not match.
+    pub fn find_patterns<'d>(&self, msg: &'d Message) -> Option<Extraction<'d>> {
+        let patterns = self.filters.get(&FilterId::Patterns);
+        let non_verbose = self.filters.get(&FilterId::NonVerbose);
+        let variables = self.filters.get(&FilterId::Variables);
+        let wasm = self.filters.get(&FilterId::Wasm);
+        if patterns.is_none() && non_verbose.is_none() && variables.is_none() && wasm.is_none() {
+            return None;
+        }
+
+        let mut extraction = Filter::extract(msg, patterns, non_verbose)?;
+        if let Some(FilterType::Variables(names)) = variables {
+            let values = msg.payload.iter()
+                .filter_map(|value| Some((value.name()?, value.value())))
+                .filter(|(name, _)| names.iter().any(|wanted| wanted == name))
+                .map(|(name, value)| (name.to_string(), value.to_string()));
+            extraction.signals.extend(values);
+        }
+        if let Some(FilterType::Wasm(module)) = wasm {
+            extraction.signals.extend(module.transform(msg));
+        }
+        Some(extraction)
+    }
+
+    /// Values of the described non-verbose message or captures of the patterns, `None` if
+    /// neither matches.
+    fn extract<'d>(msg: &'d Message, patterns: Option<&FilterType>, non_verbose: Option<&FilterType>) -> Option<Extraction<'d>> {
+        if let (Some(FilterType::NonVerbose(fibex, message_ids)), Some(Value::NonVerbose { message_id, data })) = (non_verbose, msg.payload.first()) {
+            if message_ids.contains(message_id) {
+                let signals = fibex.decode(*message_id, data, msg.standard_header.is_big_endian()).unwrap_or_default();
+                return Some(Extraction { captures: vec![], signals });
+            }
+        }
+        if let Some(FilterType::Patterns(patterns)) = patterns {
+            for val in &msg.payload {
+                match val.value() {
+                    Value::String(string) => {
+                        let capture_matches = patterns.captures(string);
+
+                        if let Some(captures) = capture_matches {
+                            return Some(Extraction { captures, signals: vec![] })
+                        } else {
+                            continue
+                        }
+                    },
+                    _ => continue,
                 }
-                Some(vec![])
-            },
-            _ => None,
+            }
         }
+        (patterns.is_none() && non_verbose.is_none()).then(Extraction::default)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dlt::headers::{write_log_headers, LogHeaders};
+    use crate::dlt::payload::write_value;
+    use crate::dlt::TraceData;
+
+    /// Info message with the given payload, verbose if it has arguments.
+    fn message(payload: &[u8], num_of_args: u8) -> Vec<u8> {
+        let headers = LogHeaders {
+            seconds: 1_700_000_000,
+            microseconds: 0,
+            ecu_id: "ECU1",
+            counter: 0,
+            session_id: None,
+            timestamp: None,
+            is_big_endian: false,
+            app_id: "APP",
+            context_id: "CTX",
+            level: MessageTypeInfoLog::Info,
+            verbose: num_of_args > 0,
+            num_of_args,
+        };
+        let mut message = vec![];
+        write_log_headers(&mut message, &headers, payload.len());
+        message.extend_from_slice(payload);
+        message
+    }
+
+    /// Verbose message logging the payload as string.
+    fn verbose(payload: &str) -> Vec<u8> {
+        let mut argument = vec![];
+        assert!(write_value(&mut argument, &Value::String(payload), false));
+        message(&argument, 1)
+    }
+
+    /// Non-verbose message with the given id and two bytes of data.
+    fn non_verbose(message_id: u32) -> Vec<u8> {
+        message(&[message_id.to_le_bytes().as_slice(), &[0xca, 0xfe]].concat(), 0)
+    }
+
+    /// Trace of one verbose message per payload.
+    fn trace(payloads: &[&str]) -> Vec<u8> {
+        payloads.iter().flat_map(|payload| verbose(payload)).collect()
     }
-}
\ No newline at end of file
+
+    /// The payloads of the messages of the trace that match the filter.
+    fn matching(filter: &Filter, trace: &[u8]) -> Vec<String> {
+        TraceData::new(trace, 0).into_iter()
+            .filter(|msg| filter.matches(msg))
+            .map(|msg| msg.payload_text())
+            .collect()
+    }
+
+    fn patterns(expressions: &[&str]) -> Filter {
+        let mut filter = Filter::new();
+        filter.add(FilterId::Patterns, FilterType::Patterns(Pattern::from(expressions).unwrap()));
+        filter
+    }
+
+    #[test]
+    fn keeps_only_the_messages_matching_the_patterns() {
+        let filter = patterns(&["speed (?P<val>[0-9]+)"]);
+        let trace = trace(&["speed 42", "unrelated line", "other"]);
+        assert_eq!(matching(&filter, &trace), vec!["speed 42"]);
+    }
+
+    #[test]
+    fn extracts_the_captures_of_matching_messages_only() {
+        let filter = patterns(&["speed (?P<val>[0-9]+)"]);
+        let trace = trace(&["speed 42", "unrelated line"]);
+        let messages: Vec<_> = TraceData::new(&trace, 0).into_iter().collect();
+
+        let extraction = filter.find_patterns(&messages[0]).unwrap();
+        assert_eq!(extraction.values("val").collect::<Vec<_>>(), vec!["42"]);
+        assert!(filter.find_patterns(&messages[1]).is_none());
+    }
+
+    #[test]
+    fn keeps_all_messages_without_patterns() {
+        let trace = trace(&["speed 42", "unrelated line"]);
+        assert_eq!(matching(&Filter::new(), &trace), vec!["speed 42", "unrelated line"]);
+    }
+
+    #[test]
+    fn keeps_the_messages_matching_the_patterns_or_the_non_verbose_ids() {
+        let mut filter = patterns(&["speed (?P<val>[0-9]+)"]);
+        filter.add(FilterId::NonVerbose, FilterType::NonVerbose(Fibex::default(), vec![7]));
+        let trace = [verbose("speed 42"), non_verbose(7), verbose("unrelated line"), non_verbose(8)].concat();
+        let messages: Vec<_> = TraceData::new(&trace, 0).into_iter().collect();
+
+        let matches: Vec<bool> = messages.iter().map(|msg| filter.matches(msg)).collect();
+        assert_eq!(matches, vec![true, true, false, false]);
+        let extracted: Vec<bool> = messages.iter().map(|msg| filter.find_patterns(msg).is_some()).collect();
+        assert_eq!(extracted, vec![true, true, false, false]);
+    }
+
+    #[test]
+    fn keeps_only_the_described_non_verbose_messages_without_patterns() {
+        let mut filter = Filter::new();
+        filter.add(FilterId::NonVerbose, FilterType::NonVerbose(Fibex::default(), vec![7]));
+        let trace = [verbose("speed 42"), non_verbose(7), non_verbose(8)].concat();
+
+        let matches: Vec<bool> = TraceData::new(&trace, 0).into_iter().map(|msg| filter.matches(&msg)).collect();
+        assert_eq!(matches, vec![false, true, false]);
+    }
+}
@@ -0,0 +1,65 @@
+use clap::ValueEnum;
+use crate::dlt::headers::peek_message_length;
+use crate::dlt::pcap;
+
+const DLT_STORAGE_HEADER: [u8; 4] = [0x44, 0x4C, 0x54, 0x01];
+const DLT_SERIAL_HEADER: [u8; 4] = [0x44, 0x4C, 0x53, 0x01];
+/// Consecutive messages checked before data without header pattern is taken as raw messages.
+const RAW_MESSAGES_CHECKED: usize = 3;
+
+/// How the DLT messages of an input are framed.
+#[derive(ValueEnum,Debug,Clone,Copy,PartialEq)]
+pub enum InputFormat {
+    /// messages with storage header, as written by the logger or dlt-viewer
+    Storage,
+    /// messages as sent by the daemon over TCP, starting with the standard header
+    Raw,
+    /// messages framed by the serial header, as received over a serial line
+    Serial,
+    /// pcap or pcapng capture of DLT on port 3490
+    Pcap,
+}
+
+impl InputFormat {
+    /// Guesses the format from the first bytes of the input. Data in none of the formats is taken
+    /// as storage, so its corrupt start gets reported.
+    pub fn detect(data: &[u8]) -> InputFormat {
+        match data.get(..4) {
+            Some(pattern) if pattern == DLT_STORAGE_HEADER => InputFormat::Storage,
+            Some(pattern) if pattern == DLT_SERIAL_HEADER => InputFormat::Serial,
+            _ if pcap::is_capture(data) => InputFormat::Pcap,
+            _ if is_raw(data) => InputFormat::Raw,
+            _ => InputFormat::Storage,
+        }
+    }
+}
+
+/// Whether the data starts with a few plausible standard headers, each right after the other.
+fn is_raw(data: &[u8]) -> bool {
+    let mut offset = 0;
+    for _ in 0..RAW_MESSAGES_CHECKED {
+        if offset == data.len() {
+            break;
+        }
+        match data.get(offset..).and_then(peek_message_length) {
+            Some(length) if offset + length <= data.len() => offset += length,
+            _ => return false,
+        }
+    }
+    offset > 0
+}
+
+/// Stores the raw messages with storage headers, so the result reads like a DLT file. The
+/// messages carry no storage time, it is left at 0, and the ECU id is taken from the standard
+/// header. Data that is not DLT is returned as warning.
+pub fn read_raw(data: &[u8]) -> (Vec<u8>, Vec<String>) {
+    let mut buffer = data.to_vec();
+    let mut trace = vec![];
+    let skipped = pcap::store_messages(&mut buffer, 0, &mut trace) + buffer.len();
+    let warnings = if skipped > 0 {
+        vec![format!("{skipped} bytes were skipped as incomplete or not DLT")]
+    } else {
+        vec![]
+    };
+    (trace, warnings)
+}
@@ -1,4 +1,5 @@
 use std::fmt::{Display, Formatter};
+use chrono::{DateTime, SecondsFormat, Utc};
 use std::mem;
 use std::str;
 use crate::dlt::{TraceDataIter};
@@ -9,8 +10,9 @@ macro_rules! is_bit_set {
     }
 }
 
-#[derive(Debug)]
-enum MessageType {
+/// Type of a message, from its extended header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageType {
     Log,
     AppTrace,
     NetworkTrace,
@@ -18,8 +20,9 @@ enum MessageType {
     Reserved,
 }
 
-#[derive(Debug)]
-enum MessageTypeInfoLog {
+/// Log level of a log message, ordered from the most to the least severe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MessageTypeInfoLog {
     Fatal,
     Error,
     Warn,
@@ -35,7 +38,7 @@ impl Display for MessageTypeInfoLog {
 }
 
 #[derive(Debug)]
-enum MessageTypeInfoAppTrace {
+pub(crate) enum MessageTypeInfoAppTrace {
     Variable,
     FunctionIn,
     FunctionOut,
@@ -50,7 +53,7 @@ impl Display for MessageTypeInfoAppTrace {
 }
 
 #[derive(Debug)]
-enum MessageTypeInfoNetworkTrace {
+pub(crate) enum MessageTypeInfoNetworkTrace {
     Ipc,
     Can,
     FlexRay,
@@ -67,7 +70,7 @@ impl Display for MessageTypeInfoNetworkTrace {
 }
 
 #[derive(Debug)]
-enum MessageTypeInfoControl {
+pub(crate) enum MessageTypeInfoControl {
     Request,
     Response,
 }
@@ -81,7 +84,9 @@ impl Display for MessageTypeInfoControl {
 #[derive(Debug)]
 pub struct StorageHeader {
     timestamp_sec : u32,
-    timestamp_usec : u32,
+    /// subsecond part of the storage time, in nanoseconds if `is_nanoseconds`, else microseconds
+    timestamp_subsec : u32,
+    is_nanoseconds: bool,
     ecu : String,
 }
 
@@ -89,11 +94,49 @@ impl StorageHeader {
     pub fn ecu_id(&self) -> &String {
         &self.ecu
     }
+
+    pub fn seconds(&self) -> u32 {
+        self.timestamp_sec
+    }
+
+    pub fn microseconds(&self) -> u32 {
+        if self.is_nanoseconds { self.timestamp_subsec / 1_000 } else { self.timestamp_subsec }
+    }
+
+    /// Subsecond part of the storage time, `None` if the microseconds are out of range.
+    pub fn nanoseconds(&self) -> Option<u32> {
+        if self.is_nanoseconds { Some(self.timestamp_subsec) } else { self.timestamp_subsec.checked_mul(1_000) }
+    }
+
+    /// Takes the subsecond part as nanoseconds, for loggers writing the variant of the storage
+    /// header with nanosecond resolution. It cannot be told apart from the standard one by its
+    /// layout, so the profile of the ECU declares it.
+    pub(crate) fn set_nanoseconds(&mut self) {
+        self.is_nanoseconds = true;
+    }
+
+    /// Storage time in seconds since the epoch.
+    pub fn time(&self) -> f64 {
+        let resolution = if self.is_nanoseconds { 1_000_000_000.0 } else { 1_000_000.0 };
+        self.timestamp_sec as f64 + self.timestamp_subsec as f64 / resolution
+    }
+
+    /// Time the message was stored by the logger, `None` if the subseconds are out of range.
+    pub fn datetime(&self) -> Option<DateTime<Utc>> {
+        DateTime::from_timestamp(self.timestamp_sec as i64, self.nanoseconds()?)
+    }
+
+    /// The storage time as RFC 3339 text with the resolution the logger stored it in.
+    pub fn rfc3339(&self) -> Option<String> {
+        let format = if self.is_nanoseconds { SecondsFormat::Nanos } else { SecondsFormat::Micros };
+        Some(self.datetime()?.to_rfc3339_opts(format, true))
+    }
 }
 
 impl Display for StorageHeader {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "DltStorageHeader [ sec: {}, usec: {}, ecu: {} ]", self.timestamp_sec, self.timestamp_usec, self.ecu)
+        let unit = if self.is_nanoseconds { "nsec" } else { "usec" };
+        write!(f, "DltStorageHeader [ sec: {}, {unit}: {}, ecu: {} ]", self.timestamp_sec, self.timestamp_subsec, self.ecu)
     }
 }
 
@@ -132,7 +175,7 @@ impl ExtendedHeader {
         &self.context_id
     }
 
-    fn msg_type(&self) -> MessageType {
+    pub(crate) fn msg_type(&self) -> MessageType {
         match (self.msg_info & MSG_INFO_BIT_MASK) >> 1 {
             0x00 => MessageType::Log,
             0x01 => MessageType::AppTrace,
@@ -142,7 +185,7 @@ impl ExtendedHeader {
         }
     }
 
-    fn msg_type_info_log(&self) -> Option<MessageTypeInfoLog> {
+    pub(crate) fn msg_type_info_log(&self) -> Option<MessageTypeInfoLog> {
         match (self.msg_info & MSG_TYPE_INFO_BIT_MASK) >> 4 {
             0x01 => Some(MessageTypeInfoLog::Fatal),
             0x02 => Some(MessageTypeInfoLog::Error),
@@ -154,7 +197,7 @@ impl ExtendedHeader {
         }
     }
 
-    fn msg_type_info_app_trace(&self) -> Option<MessageTypeInfoAppTrace> {
+    pub(crate) fn msg_type_info_app_trace(&self) -> Option<MessageTypeInfoAppTrace> {
         match (self.msg_info & MSG_TYPE_INFO_BIT_MASK) >> 4 {
             0x01 => Some(MessageTypeInfoAppTrace::Variable),
             0x02 => Some(MessageTypeInfoAppTrace::FunctionIn),
@@ -165,7 +208,7 @@ impl ExtendedHeader {
         }
     }
 
-    fn msg_type_info_network_trace(&self) -> Option<MessageTypeInfoNetworkTrace> {
+    pub(crate) fn msg_type_info_network_trace(&self) -> Option<MessageTypeInfoNetworkTrace> {
         match (self.msg_info & MSG_TYPE_INFO_BIT_MASK) >> 4 {
             0x01 => Some(MessageTypeInfoNetworkTrace::Ipc),
             0x02 => Some(MessageTypeInfoNetworkTrace::Can),
@@ -177,7 +220,7 @@ impl ExtendedHeader {
         }
     }
 
-    fn msg_type_info_control(&self) -> Option<MessageTypeInfoControl> {
+    pub(crate) fn msg_type_info_control(&self) -> Option<MessageTypeInfoControl> {
         match (self.msg_info & MSG_TYPE_INFO_BIT_MASK) >> 4 {
             0x01 => Some(MessageTypeInfoControl::Request),
             0x02 => Some(MessageTypeInfoControl::Response),
@@ -186,15 +229,29 @@ impl ExtendedHeader {
     }
 }
 
+impl ExtendedHeader {
+    fn msg_type_info(&self) -> Option<String> {
+        match self.msg_type() {
+            MessageType::Log => self.msg_type_info_log().map(|info| info.to_string()),
+            MessageType::Reserved => None,
+            MessageType::Control => self.msg_type_info_control().map(|info| info.to_string()),
+            MessageType::NetworkTrace => self.msg_type_info_network_trace().map(|info| info.to_string()),
+            MessageType::AppTrace => self.msg_type_info_app_trace().map(|info| info.to_string()),
+        }
+    }
+
+    /// Message type and type info, e.g. `Log:Warn` or `AppTrace:FunctionIn`.
+    pub fn type_name(&self) -> String {
+        match self.msg_type_info() {
+            Some(info) => format!("{:?}:{info}", self.msg_type()),
+            None => format!("{:?}", self.msg_type()),
+        }
+    }
+}
+
 impl Display for ExtendedHeader {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let msg_type_info = match self.msg_type() {
-            MessageType::Log => self.msg_type_info_log().unwrap().to_string(),
-            MessageType::Reserved => "".to_string(),
-            MessageType::Control => self.msg_type_info_control().unwrap().to_string(),
-            MessageType::NetworkTrace => self.msg_type_info_network_trace().unwrap().to_string(),
-            MessageType::AppTrace => self.msg_type_info_app_trace().unwrap().to_string(),
-        };
+        let msg_type_info = self.msg_type_info().unwrap_or_default();
         write!(f, "DltExtendedHeader [ verbose: {}, type: {:?}, type_info: {:?}, argument count: {}, app_id: {}, context_id: {}, hdr_size: {} ]",
                self.is_verbose(), self.msg_type(), msg_type_info, self.num_of_args, self.app_id, self.context_id, self.length )
     }
@@ -254,6 +311,24 @@ impl StandardHeader {
     pub fn ecu_id(&self) -> &Option<String> {
         &self.ecu_id
     }
+
+    pub fn session_id(&self) -> Option<u32> {
+        self.session_id
+    }
+
+    pub fn counter(&self) -> usize {
+        self.counter
+    }
+
+    /// Converts a timestamp counted in ticks of `resolution` seconds to the standard 0.1 ms.
+    pub(crate) fn rescale_timestamp(&mut self, resolution: f64) {
+        self.timestamp = self.timestamp.map(|timestamp| (timestamp as f64 * resolution * 10_000.0) as u32);
+    }
+
+    /// Timestamp since ECU startup in units of 0.1 milliseconds.
+    pub fn timestamp(&self) -> Option<u32> {
+        self.timestamp
+    }
 }
 
 impl Display for StandardHeader {
@@ -263,40 +338,57 @@ impl Display for StandardHeader {
     }
 }
 
+/// Reads an id, substituting bytes that are not valid UTF-8 with replacement characters.
+fn read_id(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).trim_matches(char::from(0)).to_owned()
+}
+
 const DLT_PATTERN_SIZE : usize = 4;
 const ECU_NAME_SIZE : usize = 4;
 const DLT_STORAGE_START_PATTERN : [u8;4] = [0x44, 0x4C, 0x54, 0x01];
+const DLT_SERIAL_START_PATTERN : [u8;4] = [0x44, 0x4C, 0x53, 0x01];
 
+/// Reads the storage header of the message. Messages captured over a serial line are framed by
+/// the serial header pattern instead, which carries neither storage time nor ECU id, so both are
+/// left empty.
 pub fn read_storage_header(iter: &mut TraceDataIter) -> StorageHeader {
     let mut read_offset = iter.index;
 
     let mut read_to = read_offset + DLT_PATTERN_SIZE;
     let dlt_pattern = &iter.data[read_offset..read_to];
     read_offset = read_to;
+    if DLT_SERIAL_START_PATTERN == dlt_pattern {
+        iter.index = read_offset;
+        return StorageHeader {
+            timestamp_sec: 0,
+            timestamp_subsec: 0,
+            is_nanoseconds: false,
+            ecu: String::new(),
+        };
+    }
     if DLT_STORAGE_START_PATTERN != dlt_pattern {
-        // TODO: imrpve error handling
-        println!("ERROR: DLT pattern not found when expected");
-        panic!();
+        unreachable!("message frames are verified before they are read");
     }
 
     read_to = read_offset + mem::size_of::<u32>();
-    let time_sec = u32::from_be_bytes(*&iter.data[read_offset..read_to].try_into().unwrap());
+    let time_sec = u32::from_le_bytes(iter.data[read_offset..read_to].try_into().unwrap());
     read_offset = read_to;
 
     read_to = read_offset + mem::size_of::<u32>();
-    let time_usec = u32::from_be_bytes(*&iter.data[read_offset..read_to].try_into().unwrap());
+    let time_subsec = u32::from_le_bytes(iter.data[read_offset..read_to].try_into().unwrap());
     read_offset = read_to;
 
     read_to = read_offset + ECU_NAME_SIZE;
-    let ecu = str::from_utf8(&iter.data[read_offset..read_to]).unwrap().trim_matches(char::from(0)).to_owned();
+    let ecu = read_id(&iter.data[read_offset..read_to]);
     read_offset = read_to;
 
     iter.index = read_offset;
 
     StorageHeader {
         timestamp_sec: time_sec,
-        timestamp_usec: time_usec,
-        ecu: ecu,
+        timestamp_subsec: time_subsec,
+        is_nanoseconds: false,
+        ecu,
     }
 }
 
@@ -306,19 +398,19 @@ pub fn read_standard_header(iter: &mut TraceDataIter) -> StandardHeader {
     let mut read_offset = iter.index;
     let start_index = iter.index;
 
-    let htyp = *&iter.data[read_offset] as u8;
-    read_offset = read_offset + mem::size_of::<u8>();
+    let htyp = iter.data[read_offset];
+    read_offset += mem::size_of::<u8>();
 
-    let counter = *&iter.data[read_offset] as usize;
-    read_offset = read_offset + mem::size_of::<u8>();
+    let counter = iter.data[read_offset] as usize;
+    read_offset += mem::size_of::<u8>();
 
     let mut read_to = read_offset + mem::size_of::<u16>();
-    let length = u16::from_be_bytes(*&iter.data[read_offset..read_to].try_into().unwrap()) as usize;
+    let length = u16::from_be_bytes(iter.data[read_offset..read_to].try_into().unwrap()) as usize;
     read_offset = read_to;
 
     let mut standard_header = StandardHeader {
-        htyp: htyp,
-        counter: counter,
+        htyp,
+        counter,
         msg_length: length,
         ecu_id: None,
         session_id: None,
@@ -330,7 +422,7 @@ pub fn read_standard_header(iter: &mut TraceDataIter) -> StandardHeader {
         true => {
             read_to = read_offset + ECU_ID_SIZE;
             // TODO: use str reference?
-            let ecu_id = str::from_utf8(&iter.data[read_offset..read_to]).unwrap().trim_matches(char::from(0)).to_owned();
+            let ecu_id = read_id(&iter.data[read_offset..read_to]);
             read_offset = read_to;
             Some(ecu_id)
         },
@@ -340,7 +432,7 @@ pub fn read_standard_header(iter: &mut TraceDataIter) -> StandardHeader {
     standard_header.session_id = match standard_header.has_session_id() {
         true => {
             read_to = read_offset + mem::size_of::<u32>();
-            let session_id = u32::from_be_bytes(*&iter.data[read_offset..read_to].try_into().unwrap());
+            let session_id = u32::from_be_bytes(iter.data[read_offset..read_to].try_into().unwrap());
             read_offset = read_to;
             Some(session_id)
         },
@@ -350,7 +442,7 @@ pub fn read_standard_header(iter: &mut TraceDataIter) -> StandardHeader {
     standard_header.timestamp = match standard_header.has_timestamp() {
         true => {
             read_to = read_offset + mem::size_of::<u32>();
-            let timestamp = u32::from_be_bytes(*&iter.data[read_offset..read_to].try_into().unwrap());
+            let timestamp = u32::from_be_bytes(iter.data[read_offset..read_to].try_into().unwrap());
             read_offset = read_to;
             Some(timestamp)
         },
@@ -365,37 +457,205 @@ pub fn read_standard_header(iter: &mut TraceDataIter) -> StandardHeader {
 
 const APP_ID_SIZE : usize = 4;
 const CONTEXT_ID_SIZE : usize = 4;
+const STORAGE_HEADER_SIZE : usize = DLT_PATTERN_SIZE + 2 * mem::size_of::<u32>() + ECU_NAME_SIZE;
+const SERIAL_HEADER_SIZE : usize = DLT_PATTERN_SIZE;
+const EXTENDED_HEADER_SIZE : usize = 2 * mem::size_of::<u8>() + APP_ID_SIZE + CONTEXT_ID_SIZE;
+
+/// Size of the header framing the message at `index`, the storage header or the serial header,
+/// or `None` if neither pattern is found.
+fn frame_header_size(data: &[u8], index: usize) -> Option<usize> {
+    let pattern = data.get(index..index + DLT_PATTERN_SIZE)?;
+    if pattern == DLT_STORAGE_START_PATTERN {
+        Some(STORAGE_HEADER_SIZE)
+    } else if pattern == DLT_SERIAL_START_PATTERN {
+        Some(SERIAL_HEADER_SIZE)
+    } else {
+        None
+    }
+}
+
+/// Checks that a complete message starts at `index`: the storage or serial header pattern is
+/// there and the message length covers its headers without reaching beyond the end of the data.
+/// Returns the size of the message including its storage or serial header.
+pub fn verify_message_frame(data: &[u8], index: usize) -> Result<usize, String> {
+    if data.len() < index + DLT_PATTERN_SIZE {
+        return Err(format!("truncated message header at offset {index}"));
+    }
+    let frame_size = frame_header_size(data, index)
+        .ok_or_else(|| format!("DLT pattern not found at offset {index}"))?;
+    let header_end = index + frame_size + 2 * mem::size_of::<u16>();
+    if data.len() < header_end {
+        return Err(format!("truncated message header at offset {index}"));
+    }
+
+    let htyp = data[index + frame_size];
+    let length_offset = index + frame_size + 2 * mem::size_of::<u8>();
+    let msg_length = u16::from_be_bytes(data[length_offset..length_offset + mem::size_of::<u16>()].try_into().unwrap()) as usize;
+    let headers_length = 2 * mem::size_of::<u16>()
+        + if is_bit_set!(htyp, HTYP_ECU_ID_BIT_MASK) { ECU_ID_SIZE } else { 0 }
+        + if is_bit_set!(htyp, HTYP_SESSION_ID_BIT_MASK) { mem::size_of::<u32>() } else { 0 }
+        + if is_bit_set!(htyp, HTYP_TIMESTAMP_BIT_MASK) { mem::size_of::<u32>() } else { 0 }
+        + if is_bit_set!(htyp, HTYP_EXTENDED_HEADER_BIT_MASK) { EXTENDED_HEADER_SIZE } else { 0 };
+    if msg_length < headers_length {
+        return Err(format!("message length {msg_length} at offset {index} is shorter than its headers"));
+    }
+    if data.len() < index + frame_size + msg_length {
+        return Err(format!("truncated message at offset {index}"));
+    }
+    Ok(frame_size + msg_length)
+}
+
+/// Scans forward from `index` for the next offset a complete message starts at, to resynchronize
+/// after corrupt data.
+pub fn find_message_frame(data: &[u8], index: usize) -> Option<usize> {
+    (index..data.len().saturating_sub(DLT_PATTERN_SIZE))
+        .filter(|offset| frame_header_size(data, *offset).is_some())
+        .find(|offset| verify_message_frame(data, *offset).is_ok())
+}
+
+/// Storage header of the verified message at `index`, without parsing the message. Messages
+/// with a serial header have no storage header.
+pub fn peek_storage_header(data: &[u8], index: usize) -> Option<StorageHeader> {
+    if frame_header_size(data, index) != Some(STORAGE_HEADER_SIZE) {
+        return None;
+    }
+    let offset = index + DLT_PATTERN_SIZE;
+    let seconds = u32::from_le_bytes(data[offset..offset + mem::size_of::<u32>()].try_into().unwrap());
+    let offset = offset + mem::size_of::<u32>();
+    let subseconds = u32::from_le_bytes(data[offset..offset + mem::size_of::<u32>()].try_into().unwrap());
+    let offset = offset + mem::size_of::<u32>();
+    let ecu = read_id(&data[offset..offset + ECU_NAME_SIZE]);
+    Some(StorageHeader { timestamp_sec: seconds, timestamp_subsec: subseconds, is_nanoseconds: false, ecu })
+}
 
 pub fn read_extended_header(iter: &mut TraceDataIter) -> ExtendedHeader {
     let mut read_offset = iter.index;
     let start_index = iter.index;
 
-    let msg_info = *&iter.data[read_offset] as u8;
-    read_offset = read_offset + mem::size_of::<u8>();
+    let msg_info = iter.data[read_offset];
+    read_offset += mem::size_of::<u8>();
 
     let num_arguments = if is_bit_set!(msg_info, MSG_INFO_VERBOSE_BIT_MASK) {
-        *&iter.data[read_offset] as usize
+        iter.data[read_offset] as usize
     } else {
         0
     };
-    read_offset = read_offset + mem::size_of::<u8>();
+    read_offset += mem::size_of::<u8>();
 
     let mut read_to = read_offset + APP_ID_SIZE;
-    let app_id = str::from_utf8(&iter.data[read_offset..read_to]).unwrap().trim_matches(char::from(0)).to_owned();
+    let app_id = read_id(&iter.data[read_offset..read_to]);
     read_offset = read_to;
 
     read_to = read_offset + CONTEXT_ID_SIZE;
-    let context_id = str::from_utf8(&iter.data[read_offset..read_to]).unwrap().trim_matches(char::from(0)).to_owned();
+    let context_id = read_id(&iter.data[read_offset..read_to]);
     read_offset = read_to;
 
     let end_index = read_offset;
     iter.index = end_index;
 
     ExtendedHeader {
-        msg_info: msg_info,
+        msg_info,
         num_of_args: num_arguments,
-        app_id: app_id,
-        context_id: context_id,
+        app_id,
+        context_id,
         length: end_index - start_index,
     }
-}
\ No newline at end of file
+}
+const DLT_PROTOCOL_VERSION: u8 = 1;
+
+/// Headers of a log message written by `write_log_headers`.
+pub(crate) struct LogHeaders<'h> {
+    pub seconds: u32,
+    pub microseconds: u32,
+    pub ecu_id: &'h str,
+    pub counter: u8,
+    pub session_id: Option<u32>,
+    pub timestamp: Option<u32>,
+    /// sets the MSBF bit, the payload has to be written in big endian
+    pub is_big_endian: bool,
+    pub app_id: &'h str,
+    pub context_id: &'h str,
+    pub level: MessageTypeInfoLog,
+    pub verbose: bool,
+    pub num_of_args: u8,
+}
+
+fn write_id(out: &mut Vec<u8>, id: &str, size: usize) {
+    let mut bytes = id.as_bytes().to_vec();
+    bytes.resize(size, 0);
+    out.extend_from_slice(&bytes);
+}
+
+/// Appends a storage header, e.g. to store a message received over the network.
+pub(crate) fn write_storage_header(out: &mut Vec<u8>, seconds: u32, microseconds: u32, ecu_id: &str) {
+    out.extend_from_slice(&DLT_STORAGE_START_PATTERN);
+    out.extend_from_slice(&seconds.to_le_bytes());
+    out.extend_from_slice(&microseconds.to_le_bytes());
+    write_id(out, ecu_id, ECU_NAME_SIZE);
+}
+
+/// Length of the message whose standard header starts `data`, `None` if the header is incomplete
+/// or not plausible, i.e. of another protocol version or shorter than its headers.
+pub(crate) fn peek_message_length(data: &[u8]) -> Option<usize> {
+    let htyp = *data.first()?;
+    let msg_length = u16::from_be_bytes(data.get(2..4)?.try_into().unwrap()) as usize;
+    let headers_length = 2 * mem::size_of::<u16>()
+        + if is_bit_set!(htyp, HTYP_ECU_ID_BIT_MASK) { ECU_ID_SIZE } else { 0 }
+        + if is_bit_set!(htyp, HTYP_SESSION_ID_BIT_MASK) { mem::size_of::<u32>() } else { 0 }
+        + if is_bit_set!(htyp, HTYP_TIMESTAMP_BIT_MASK) { mem::size_of::<u32>() } else { 0 }
+        + if is_bit_set!(htyp, HTYP_EXTENDED_HEADER_BIT_MASK) { EXTENDED_HEADER_SIZE } else { 0 };
+    let version = (htyp & HTYP_VERSION_BIT_MASK) >> 5;
+    (version == DLT_PROTOCOL_VERSION && msg_length >= headers_length).then_some(msg_length)
+}
+
+/// ECU id of the standard header at the start of `data`, empty if it carries none.
+pub(crate) fn peek_standard_header_ecu_id(data: &[u8]) -> String {
+    match data.first() {
+        Some(htyp) if is_bit_set!(*htyp, HTYP_ECU_ID_BIT_MASK) && data.len() >= 4 + ECU_ID_SIZE => read_id(&data[4..4 + ECU_ID_SIZE]),
+        _ => String::new(),
+    }
+}
+
+/// Appends the storage, standard and extended header of a log message whose payload has the
+/// given length. The standard header carries the ECU id.
+pub(crate) fn write_log_headers(out: &mut Vec<u8>, headers: &LogHeaders, payload_len: usize) {
+    write_storage_header(out, headers.seconds, headers.microseconds, headers.ecu_id);
+
+    let mut htyp = DLT_PROTOCOL_VERSION << 5 | HTYP_EXTENDED_HEADER_BIT_MASK | HTYP_ECU_ID_BIT_MASK;
+    let mut msg_length = 2 * mem::size_of::<u16>() + ECU_ID_SIZE + EXTENDED_HEADER_SIZE + payload_len;
+    if headers.session_id.is_some() {
+        htyp |= HTYP_SESSION_ID_BIT_MASK;
+        msg_length += mem::size_of::<u32>();
+    }
+    if headers.timestamp.is_some() {
+        htyp |= HTYP_TIMESTAMP_BIT_MASK;
+        msg_length += mem::size_of::<u32>();
+    }
+    if headers.is_big_endian {
+        htyp |= HTYP_MSB_FIRST_BIT_MASK;
+    }
+    out.push(htyp);
+    out.push(headers.counter);
+    out.extend_from_slice(&(msg_length as u16).to_be_bytes());
+    write_id(out, headers.ecu_id, ECU_ID_SIZE);
+    if let Some(session_id) = headers.session_id {
+        out.extend_from_slice(&session_id.to_be_bytes());
+    }
+    if let Some(timestamp) = headers.timestamp {
+        out.extend_from_slice(&timestamp.to_be_bytes());
+    }
+
+    let level = match headers.level {
+        MessageTypeInfoLog::Fatal => 0x01,
+        MessageTypeInfoLog::Error => 0x02,
+        MessageTypeInfoLog::Warn => 0x03,
+        MessageTypeInfoLog::Info => 0x04,
+        MessageTypeInfoLog::Debug => 0x05,
+        MessageTypeInfoLog::Verbose => 0x06,
+    };
+    let verbose = if headers.verbose { MSG_INFO_VERBOSE_BIT_MASK } else { 0 };
+    out.push(level << 4 | verbose);
+    out.push(headers.num_of_args);
+    write_id(out, headers.app_id, APP_ID_SIZE);
+    write_id(out, headers.context_id, CONTEXT_ID_SIZE);
+}
@@ -0,0 +1,44 @@
+use std::collections::HashMap;
+use crate::dlt::Message;
+
+/// Uptime in 0.1 ms the timestamp has to jump back before it counts as a reboot. Messages are
+/// buffered per application on the ECU, so the uptime of consecutive messages is not strictly
+/// increasing.
+const REBOOT_TOLERANCE: u32 = 10_000;
+
+/// Numbers the lifecycles (boots) of the ECUs in a trace. A new lifecycle starts when the uptime
+/// of an ECU jumps back, the numbers are counted from 1 over all ECUs.
+#[derive(Default)]
+pub struct Lifecycles {
+    count: usize,
+    current: HashMap<String, (usize, u32)>,
+}
+
+impl Lifecycles {
+    pub fn new() -> Lifecycles {
+        Lifecycles::default()
+    }
+
+    /// Returns the lifecycle the message belongs to.
+    pub fn add(&mut self, msg: &Message) -> usize {
+        let ecu_id = msg.ecu_id().to_string();
+        let timestamp = msg.standard_header.timestamp();
+
+        match (self.current.get_mut(&ecu_id), timestamp) {
+            (Some((lifecycle, last)), Some(timestamp)) => {
+                if timestamp.saturating_add(REBOOT_TOLERANCE) < *last {
+                    self.count += 1;
+                    *lifecycle = self.count;
+                }
+                *last = timestamp;
+                *lifecycle
+            },
+            (Some((lifecycle, _)), None) => *lifecycle,
+            (None, timestamp) => {
+                self.count += 1;
+                self.current.insert(ecu_id, (self.count, timestamp.unwrap_or(0)));
+                self.count
+            },
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use std::sync::Arc;
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_array::builder::{Float64Builder, StringBuilder, TimestampMicrosecondBuilder, UInt64Builder};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::errors::ParquetError;
+use parquet::file::properties::WriterProperties;
+use crate::dlt::chaos::SinkFile;
+use crate::{OutputField, Parquet};
+use crate::dlt::{FieldContext, Message};
+
+/// Rows buffered before they are handed to the writer as one record batch.
+const BATCH_ROWS: usize = 4096;
+
+/// Writes the rows of the parquet output with a typed column per field: the storage time as
+/// timestamp, the uptime and reference delta as floats, counts as integers and the other fields
+/// as text. Values the message lacks are null.
+pub struct ParquetWriter {
+    writer: ArrowWriter<SinkFile>,
+    schema: SchemaRef,
+    columns: Vec<Column>,
+    rows: usize,
+}
+
+enum Column {
+    Text(StringBuilder),
+    Count(UInt64Builder),
+    Seconds(Float64Builder),
+    Time(TimestampMicrosecondBuilder),
+}
+
+impl ParquetWriter {
+    pub fn create(parquet: &Parquet, fields: &[OutputField]) -> Result<ParquetWriter, ParquetError> {
+        let schema = Arc::new(Schema::new(fields.iter()
+            .map(|field| Field::new(field.name(), data_type(field), true))
+            .collect::<Vec<_>>()));
+        let columns = fields.iter().map(|field| match data_type(field) {
+            DataType::UInt64 => Column::Count(UInt64Builder::new()),
+            DataType::Float64 => Column::Seconds(Float64Builder::new()),
+            DataType::Timestamp(..) => Column::Time(TimestampMicrosecondBuilder::new().with_timezone("UTC")),
+            _ => Column::Text(StringBuilder::new()),
+        }).collect();
+        let properties = WriterProperties::builder().set_compression(Compression::SNAPPY).build();
+        let writer = ArrowWriter::try_new(SinkFile::create(&parquet.file_path)?, schema.clone(), Some(properties))?;
+        Ok(ParquetWriter { writer, schema, columns, rows: 0 })
+    }
+
+    /// Adds the row of the message. The file can only be read once it is finished, so the rows
+    /// are not flushed before.
+    pub fn write(&mut self, msg: &Message, fields: &[OutputField], context: &FieldContext) -> Result<(), ParquetError> {
+        for (column, field) in self.columns.iter_mut().zip(fields) {
+            match column {
+                Column::Text(builder) => builder.append_option(text(msg, field, context)),
+                Column::Count(builder) => builder.append_option(count(msg, field, context)),
+                Column::Seconds(builder) => builder.append_option(seconds(msg, field, context)),
+                Column::Time(builder) => {
+                    let storage_header = &msg.storage_header;
+                    builder.append_value(storage_header.seconds() as i64 * 1_000_000 + storage_header.microseconds() as i64);
+                },
+            }
+        }
+        self.rows += 1;
+        if self.rows >= BATCH_ROWS {
+            self.write_batch()?;
+        }
+        Ok(())
+    }
+
+    /// Writes the remaining rows and the footer of the file.
+    pub fn finish(mut self) -> Result<(), ParquetError> {
+        self.write_batch()?;
+        self.writer.close()?;
+        Ok(())
+    }
+
+    fn write_batch(&mut self) -> Result<(), ParquetError> {
+        if self.rows == 0 {
+            return Ok(());
+        }
+        let arrays: Vec<ArrayRef> = self.columns.iter_mut().map(|column| match column {
+            Column::Text(builder) => Arc::new(builder.finish()) as ArrayRef,
+            Column::Count(builder) => Arc::new(builder.finish()) as ArrayRef,
+            Column::Seconds(builder) => Arc::new(builder.finish()) as ArrayRef,
+            Column::Time(builder) => Arc::new(builder.finish()) as ArrayRef,
+        }).collect();
+        let batch = RecordBatch::try_new(self.schema.clone(), arrays)?;
+        self.rows = 0;
+        self.writer.write(&batch)
+    }
+}
+
+fn data_type(field: &OutputField) -> DataType {
+    match field {
+        OutputField::Time => DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())),
+        OutputField::Timestamp | OutputField::RefDelta => DataType::Float64,
+        OutputField::Session | OutputField::PayloadLen | OutputField::Noar | OutputField::Index | OutputField::Lifecycle => DataType::UInt64,
+        _ => DataType::Utf8,
+    }
+}
+
+fn text(msg: &Message, field: &OutputField, context: &FieldContext) -> Option<String> {
+    let header = msg.extended_header.as_ref();
+    let is_missing = match field {
+        OutputField::App | OutputField::Ctx | OutputField::Type => header.is_none(),
+        OutputField::Ecu => msg.standard_header.ecu_id().is_none(),
+        OutputField::Level => msg.log_level().is_none(),
+        OutputField::Metadata(key) => !context.metadata.contains_key(key),
+        OutputField::Capture(name) => context.extraction.values(name).next().is_none(),
+        OutputField::RefEvent | OutputField::AppDescription | OutputField::ContextDescription => {
+            msg.format_field(field, context) == context.missing
+        },
+        _ => false,
+    };
+    (!is_missing).then(|| msg.format_field(field, context))
+}
+
+fn count(msg: &Message, field: &OutputField, context: &FieldContext) -> Option<u64> {
+    match field {
+        OutputField::Session => msg.session_id().map(u64::from),
+        OutputField::PayloadLen => Some(msg.payload_len() as u64),
+        OutputField::Noar => msg.number_of_arguments().map(|noar| noar as u64),
+        OutputField::Index => Some(context.index as u64),
+        OutputField::Lifecycle => Some(context.lifecycle as u64),
+        _ => None,
+    }
+}
+
+fn seconds(msg: &Message, field: &OutputField, context: &FieldContext) -> Option<f64> {
+    match field {
+        // the timestamp counts in 0.1 milliseconds
+        OutputField::Timestamp => msg.standard_header.timestamp().map(|timestamp| timestamp as f64 / 10_000.0),
+        OutputField::RefDelta => {
+            let time = msg.storage_header.time();
+            context.references.and_then(|references| references.nearest(time)).map(|(_, delta)| delta)
+        },
+        _ => None,
+    }
+}
@@ -1,5 +1,8 @@
+use std::fmt::{Display, Formatter};
 use std::mem;
 use paste::paste;
+use crate::config::StringCoding;
+use crate::dlt::{invalid_utf8, InvalidUtf8};
 use std::str;
 
 pub enum ByteConverter {
@@ -22,7 +25,22 @@ macro_rules! impl_from_bytes {
     )+)
 }
 
-impl_from_bytes! { u8 i8 u16 i16 u32 i32 u64 i64 u128 i128 }
+impl_from_bytes! { u8 i8 u16 i16 u32 i32 u64 i64 u128 i128 f32 f64 }
+
+macro_rules! impl_take {
+    ($($type:ident)+) => ($(
+        paste! {
+            impl PayloadIter<'_> {
+                fn [< take_ $type >](&mut self) -> Option<$type> {
+                    let bytes = self.take()?;
+                    Some(self.converter.[< $type _from_bytes >](bytes))
+                }
+            }
+        }
+    )+)
+}
+
+impl_take! { u8 i8 u16 i16 u32 i32 u64 i64 u128 i128 f32 f64 }
 
 enum TypeLength {
     Bits8,
@@ -62,6 +80,7 @@ impl From<u32> for TypeInfoStringEncoding {
     }
 }
 
+#[allow(dead_code)]
 struct TypeInfo {
     length: TypeLength,
     var_info: bool,
@@ -80,7 +99,8 @@ enum Type {
     Signed(TypeInfo),
     Unsigned(TypeInfo),
     Float(TypeInfo),
-    Array(TypeInfo),
+    /// array with the type of its elements
+    Array(TypeInfo, Box<Type>),
     String(TypeInfo),
     Raw(TypeInfo),
     // VariableInfo,
@@ -122,6 +142,180 @@ const TYPE_INFO_TRACE_INFO_BIT_MASK: u32 = 0x2000;
 const TYPE_INFO_STRUCT_BIT_MASK: u32 = 0x4000;
 const TYPE_INFO_STRING_CODING_BIT_MASK: u32 = 0x38000;
 
+/// Appends a little endian, UTF-8 coded string argument to the payload of a verbose message.
+pub(crate) fn write_string(out: &mut Vec<u8>, text: &str) {
+    let type_info = TYPE_INFO_STRING_BIT_MASK | 0x1 << 15;
+    out.extend_from_slice(&type_info.to_le_bytes());
+    // the length includes the terminating zero
+    out.extend_from_slice(&(text.len() as u16 + 1).to_le_bytes());
+    out.extend_from_slice(text.as_bytes());
+    out.push(0);
+}
+
+/// Byte order of the arguments written by `write_value`.
+struct ArgumentWriter<'o> {
+    out: &'o mut Vec<u8>,
+    is_big_endian: bool,
+}
+
+macro_rules! put {
+    ($writer:expr, $value:expr) => {
+        if $writer.is_big_endian {
+            $writer.out.extend_from_slice(&$value.to_be_bytes())
+        } else {
+            $writer.out.extend_from_slice(&$value.to_le_bytes())
+        }
+    }
+}
+
+/// Appends the value as argument of a verbose message, in the given byte order. Returns `false`
+/// and leaves the payload unchanged if the value cannot be encoded, e.g. a decoding fallback like
+/// `InvalidString` or an array whose elements differ in type.
+pub(crate) fn write_value(out: &mut Vec<u8>, value: &Value, is_big_endian: bool) -> bool {
+    let len = out.len();
+    let mut writer = ArgumentWriter { out, is_big_endian };
+    let written = writer.write_argument(value).is_some();
+    if !written {
+        out.truncate(len);
+    }
+    written
+}
+
+impl ArgumentWriter<'_> {
+    fn write_argument(&mut self, value: &Value) -> Option<()> {
+        let (variable_info, value) = match value {
+            Value::Named { name, unit, value } => (Some((*name, *unit)), value.as_ref()),
+            _ => (None, value),
+        };
+        let vari = if variable_info.is_some() { TYPE_INFO_VARIABLE_INFO_BIT_MASK } else { 0 };
+
+        match value {
+            Value::String(string) => {
+                put!(self, TYPE_INFO_STRING_BIT_MASK | vari | 0x1 << 15);
+                self.write_sized(string.as_bytes(), variable_info, true)
+            },
+            Value::Raw(data) => {
+                put!(self, TYPE_INFO_RAW_BIT_MASK | vari);
+                self.write_sized(data, variable_info, false)
+            },
+            Value::TraceData(text) if variable_info.is_none() => {
+                put!(self, TYPE_INFO_TRACE_INFO_BIT_MASK);
+                put!(self, u16::try_from(text.len()).ok()?);
+                self.out.extend_from_slice(text.as_bytes());
+                Some(())
+            },
+            Value::Struct(entries) => {
+                put!(self, TYPE_INFO_STRUCT_BIT_MASK | vari);
+                put!(self, u16::try_from(entries.len()).ok()?);
+                self.write_variable_info(variable_info, false)?;
+                entries.iter().try_for_each(|entry| self.write_argument(entry))
+            },
+            Value::Array(_) => {
+                let mut dimensions = vec![];
+                let mut element = value;
+                while let Value::Array(elements) = element {
+                    dimensions.push(u16::try_from(elements.len()).ok()?);
+                    element = elements.first()?;
+                }
+                put!(self, TYPE_INFO_ARRAY_BIT_MASK | vari | scalar_type_info(element)?);
+                put!(self, u16::try_from(dimensions.len()).ok()?);
+                dimensions.iter().for_each(|dimension| put!(self, dimension));
+                self.write_variable_info(variable_info, true)?;
+                self.write_array_elements(value, &dimensions, scalar_type_info(element)?)
+            },
+            _ => {
+                let type_info = scalar_type_info(value)?;
+                put!(self, type_info | vari);
+                self.write_variable_info(variable_info, type_info & TYPE_INFO_BOOL_BIT_MASK == 0)?;
+                self.write_scalar(value)
+            },
+        }
+    }
+
+    /// Writes the length, the variable info and the data of strings and raw data.
+    fn write_sized(&mut self, data: &[u8], variable_info: Option<(&str, Option<&str>)>, zero_terminated: bool) -> Option<()> {
+        let len = data.len() + usize::from(zero_terminated);
+        put!(self, u16::try_from(len).ok()?);
+        self.write_variable_info(variable_info, false)?;
+        self.out.extend_from_slice(data);
+        if zero_terminated {
+            self.out.push(0);
+        }
+        Some(())
+    }
+
+    fn write_variable_info(&mut self, variable_info: Option<(&str, Option<&str>)>, with_unit: bool) -> Option<()> {
+        let Some((name, unit)) = variable_info else {
+            return Some(());
+        };
+        if unit.is_some() && !with_unit {
+            return None;
+        }
+        // both texts are zero terminated
+        put!(self, u16::try_from(name.len() + 1).ok()?);
+        let unit = unit.unwrap_or_default();
+        if with_unit {
+            put!(self, u16::try_from(unit.len() + 1).ok()?);
+        }
+        for text in [name].into_iter().chain(with_unit.then_some(unit)) {
+            self.out.extend_from_slice(text.as_bytes());
+            self.out.push(0);
+        }
+        Some(())
+    }
+
+    fn write_array_elements(&mut self, value: &Value, dimensions: &[u16], type_info: u32) -> Option<()> {
+        match (value, dimensions.split_first()) {
+            (Value::Array(elements), Some((size, inner_dimensions))) if elements.len() == *size as usize => {
+                elements.iter().try_for_each(|element| self.write_array_elements(element, inner_dimensions, type_info))
+            },
+            (_, None) if scalar_type_info(value) == Some(type_info) => self.write_scalar(value),
+            _ => None,
+        }
+    }
+
+    fn write_scalar(&mut self, value: &Value) -> Option<()> {
+        match value {
+            Value::Bool(value) => self.out.push(u8::from(*value)),
+            Value::SInt8(value) => put!(self, value),
+            Value::SInt16(value) => put!(self, value),
+            Value::SInt32(value) => put!(self, value),
+            Value::SInt64(value) => put!(self, value),
+            Value::SInt128(value) => put!(self, value),
+            Value::UInt8(value) => put!(self, value),
+            Value::UInt16(value) => put!(self, value),
+            Value::UInt32(value) => put!(self, value),
+            Value::UInt64(value) => put!(self, value),
+            Value::UInt128(value) => put!(self, value),
+            Value::Float32(value) => put!(self, value),
+            Value::Float64(value) => put!(self, value),
+            _ => return None,
+        }
+        Some(())
+    }
+}
+
+/// Type info of booleans and numbers, `None` for all other values.
+fn scalar_type_info(value: &Value) -> Option<u32> {
+    let type_info = match value {
+        Value::Bool(_) => TYPE_INFO_BOOL_BIT_MASK | 0x1,
+        Value::SInt8(_) => TYPE_INFO_INT_BIT_MASK | 0x1,
+        Value::SInt16(_) => TYPE_INFO_INT_BIT_MASK | 0x2,
+        Value::SInt32(_) => TYPE_INFO_INT_BIT_MASK | 0x3,
+        Value::SInt64(_) => TYPE_INFO_INT_BIT_MASK | 0x4,
+        Value::SInt128(_) => TYPE_INFO_INT_BIT_MASK | 0x5,
+        Value::UInt8(_) => TYPE_INFO_UINT_BIT_MASK | 0x1,
+        Value::UInt16(_) => TYPE_INFO_UINT_BIT_MASK | 0x2,
+        Value::UInt32(_) => TYPE_INFO_UINT_BIT_MASK | 0x3,
+        Value::UInt64(_) => TYPE_INFO_UINT_BIT_MASK | 0x4,
+        Value::UInt128(_) => TYPE_INFO_UINT_BIT_MASK | 0x5,
+        Value::Float32(_) => TYPE_INFO_FLOAT_BIT_MASK | 0x3,
+        Value::Float64(_) => TYPE_INFO_FLOAT_BIT_MASK | 0x4,
+        _ => return None,
+    };
+    Some(type_info)
+}
+
 impl From<u32> for Type {
     fn from(value: u32) -> Self {
         let has_var_info = value & TYPE_INFO_VARIABLE_INFO_BIT_MASK == TYPE_INFO_VARIABLE_INFO_BIT_MASK;
@@ -131,6 +325,13 @@ impl From<u32> for Type {
         let type_info = TypeInfo::new(type_len, has_var_info, has_fixed_point, string_coding);
 
         match value {
+            // the array bit is combined with the bit of the element type, so check it first
+            value if value & TYPE_INFO_ARRAY_BIT_MASK == TYPE_INFO_ARRAY_BIT_MASK => {
+                // name, unit and fixed point parameters are given once for the whole array, not
+                // per element
+                let element_type = Type::from(value & !(TYPE_INFO_ARRAY_BIT_MASK | TYPE_INFO_VARIABLE_INFO_BIT_MASK | TYPE_INFO_FIXED_POINT_BIT_MASK));
+                Type::Array(type_info, Box::new(element_type))
+            },
             value if value & TYPE_INFO_BOOL_BIT_MASK == TYPE_INFO_BOOL_BIT_MASK => {
                 Type::Bool(type_info)
             },
@@ -143,9 +344,6 @@ impl From<u32> for Type {
             value if value & TYPE_INFO_FLOAT_BIT_MASK == TYPE_INFO_FLOAT_BIT_MASK => {
                 Type::Float(type_info)
             },
-            value if value & TYPE_INFO_ARRAY_BIT_MASK == TYPE_INFO_ARRAY_BIT_MASK => {
-                Type::Array(type_info)
-            },
             value if value & TYPE_INFO_STRING_BIT_MASK == TYPE_INFO_STRING_BIT_MASK => {
                 Type::String(type_info)
             },
@@ -163,7 +361,7 @@ impl From<u32> for Type {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum Value<'d> {
     Bool(bool),
     SInt8(i8),
@@ -179,8 +377,156 @@ pub enum Value<'d> {
     Float32(f32),
     Float64(f64),
     String(&'d str),
+    /// string argument that is not valid UTF-8, written with replacement characters
+    InvalidString(&'d [u8]),
+    /// ASCII coded string argument with 8 bit characters, read as Latin-1
+    Latin1String(&'d [u8]),
+    /// string argument of an ECU whose profile declares UTF-16 strings
+    Utf16String {
+        data: &'d [u8],
+        is_big_endian: bool,
+    },
+    Raw(&'d [u8]),
+    /// elements of an array, multi-dimensional arrays nest one array per dimension
+    Array(Vec<Value<'d>>),
+    /// entries of a struct, each one a complete argument
+    Struct(Vec<Value<'d>>),
     TraceData(&'d str),
-    NonVerbose(u32, &'d [u8]),
+    /// message id followed by the argument bytes, which can only be decoded with the description
+    /// of the message id
+    NonVerbose {
+        message_id: u32,
+        data: &'d [u8],
+    },
+    /// argument that carries its variable name and, for numbers, its unit
+    Named {
+        name: &'d str,
+        unit: Option<&'d str>,
+        value: Box<Value<'d>>,
+    },
+    /// integer scaled to its physical value: `scaled = raw * quantization + offset`
+    FixedPoint {
+        raw: i128,
+        quantization: f32,
+        offset: i128,
+        scaled: f64,
+    },
+}
+
+impl Display for Value<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Bool(value) => write!(f, "{value}"),
+            Value::SInt8(value) => write!(f, "{value}"),
+            Value::SInt16(value) => write!(f, "{value}"),
+            Value::SInt32(value) => write!(f, "{value}"),
+            Value::SInt64(value) => write!(f, "{value}"),
+            Value::SInt128(value) => write!(f, "{value}"),
+            Value::UInt8(value) => write!(f, "{value}"),
+            Value::UInt16(value) => write!(f, "{value}"),
+            Value::UInt32(value) => write!(f, "{value}"),
+            Value::UInt64(value) => write!(f, "{value}"),
+            Value::UInt128(value) => write!(f, "{value}"),
+            Value::Float32(value) => write!(f, "{value}"),
+            Value::Float64(value) => write!(f, "{value}"),
+            Value::String(string) | Value::TraceData(string) => write!(f, "{string}"),
+            Value::InvalidString(bytes) => write!(f, "{}", String::from_utf8_lossy(bytes)),
+            Value::Latin1String(bytes) => bytes.iter().try_for_each(|byte| write!(f, "{}", char::from(*byte))),
+            Value::Utf16String { data, is_big_endian } => {
+                let units = data.chunks_exact(2)
+                    .map(|unit| if *is_big_endian { u16::from_be_bytes([unit[0], unit[1]]) } else { u16::from_le_bytes([unit[0], unit[1]]) })
+                    .take_while(|unit| *unit != 0);
+                char::decode_utf16(units).try_for_each(|c| write!(f, "{}", c.unwrap_or(char::REPLACEMENT_CHARACTER)))
+            },
+            // raw data is written as hex string
+            Value::Raw(bytes) => bytes.iter().try_for_each(|byte| write!(f, "{byte:02x}")),
+            Value::Array(values) | Value::Struct(values) => {
+                let (open, close) = if matches!(self, Value::Array(_)) { ('[', ']') } else { ('{', '}') };
+                write!(f, "{open}")?;
+                for (idx, value) in values.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{value}")?;
+                }
+                write!(f, "{close}")
+            },
+            Value::NonVerbose { message_id, data } => {
+                write!(f, "[{message_id}] ")?;
+                data.iter().try_for_each(|byte| write!(f, "{byte:02x}"))
+            },
+            Value::Named { name, unit, value } => {
+                write!(f, "{name}: {value}")?;
+                match unit {
+                    Some(unit) => write!(f, " {unit}"),
+                    None => Ok(()),
+                }
+            },
+            // fixed point numbers are written with their physical value
+            Value::FixedPoint { scaled, .. } => write!(f, "{scaled}"),
+        }
+    }
+}
+
+impl<'d> Value<'d> {
+    /// Name of the argument, if it was sent with variable info.
+    pub fn name(&self) -> Option<&'d str> {
+        match self {
+            Value::Named { name, .. } => Some(name),
+            _ => None,
+        }
+    }
+
+    /// Unit of the argument, if it was sent with variable info.
+    pub fn unit(&self) -> Option<&'d str> {
+        match self {
+            Value::Named { unit, .. } => *unit,
+            _ => None,
+        }
+    }
+
+    /// The value itself, without name and unit.
+    pub fn value(&self) -> &Value<'d> {
+        match self {
+            Value::Named { value, .. } => value,
+            _ => self,
+        }
+    }
+
+    fn with_variable_info(self, variable_info: Option<(&'d str, Option<&'d str>)>) -> Value<'d> {
+        match variable_info {
+            Some((name, unit)) => Value::Named { name, unit, value: Box::new(self) },
+            None => self,
+        }
+    }
+
+    fn as_integer(&self) -> Option<i128> {
+        match self {
+            Value::SInt8(value) => Some(*value as i128),
+            Value::SInt16(value) => Some(*value as i128),
+            Value::SInt32(value) => Some(*value as i128),
+            Value::SInt64(value) => Some(*value as i128),
+            Value::SInt128(value) => Some(*value),
+            Value::UInt8(value) => Some(*value as i128),
+            Value::UInt16(value) => Some(*value as i128),
+            Value::UInt32(value) => Some(*value as i128),
+            Value::UInt64(value) => Some(*value as i128),
+            Value::UInt128(value) => Some(*value as i128),
+            _ => None,
+        }
+    }
+
+    fn with_fixed_point(self, fixed_point: Option<(f32, i128)>) -> Value<'d> {
+        match (fixed_point, self.as_integer()) {
+            (Some((quantization, offset)), Some(raw)) => Value::FixedPoint {
+                raw,
+                quantization,
+                offset,
+                scaled: raw as f64 * quantization as f64 + offset as f64,
+            },
+            _ => self,
+        }
+    }
 }
 
 pub struct Payload<'d> {
@@ -190,48 +536,64 @@ pub struct Payload<'d> {
     count: usize,
     is_big_endian : bool,
     is_verbose: bool,
+    string_coding: Option<StringCoding>,
 }
 
 impl<'p,'d:'p> Payload<'d> {
 
     pub fn new_verbose(data: &'d [u8], index: usize, payload_size: usize, is_big_endian: bool, count: usize) -> Payload<'d> {
-        Payload { data, index, payload_size, count, is_big_endian, is_verbose: true }
+        Payload { data, index, payload_size, count, is_big_endian, is_verbose: true, string_coding: None }
     }
 
-    pub fn new_non_verbose<'b>(data: &'d [u8], index: usize, payload_size: usize, is_big_endian: bool) -> Payload<'d> {
-        Payload { data, index, payload_size, count: 0, is_big_endian, is_verbose: false }
+    pub fn new_non_verbose(data: &'d [u8], index: usize, payload_size: usize, is_big_endian: bool) -> Payload<'d> {
+        Payload { data, index, payload_size, count: 0, is_big_endian, is_verbose: false, string_coding: None }
     }
 
-    pub fn read_non_verbose(&'p self) -> Value <'d> {
+    /// Reads all string arguments with the given coding instead of the one of their type info.
+    pub fn with_string_coding(self, string_coding: Option<StringCoding>) -> Payload<'d> {
+        Payload { string_coding, ..self }
+    }
+
+    /// Reads message id and argument bytes, `None` if the payload is too short for the id.
+    pub fn read_non_verbose(&'p self) -> Option<Value <'d>> {
         let mut read_to = self.index + SIZE_MSG_ID;
         let converter = if self.is_big_endian { ByteConverter::FromBigEndian } else { ByteConverter::FromLittleEndian };
-        let message_id = converter.u32_from_bytes(self.data[self.index .. read_to].try_into().unwrap());
+        let message_id = converter.u32_from_bytes(self.data.get(self.index .. read_to)?.try_into().ok()?);
         let index = read_to;
 
-        read_to = index + self.payload_size - SIZE_MSG_ID;
-        let payload = &self.data[index..read_to];
+        read_to = self.index + self.payload_size;
+        let data = self.data.get(index..read_to)?;
 
-        Value::NonVerbose(message_id, payload)
+        Some(Value::NonVerbose { message_id, data })
     }
 
     pub fn iter(&'p self) -> PayloadIter<'d> {
         PayloadIter {
             data : self.data,
             index : self.index,
+            end: self.index + self.payload_size,
             payload_size: self.payload_size,
             count : self.count,
             is_verbose: self.is_verbose,
+            string_coding: self.string_coding,
+            depth: 0,
             converter : if self.is_big_endian { ByteConverter::FromBigEndian } else { ByteConverter::FromLittleEndian }
         }
     }
 }
 
+#[allow(dead_code)]
 pub struct PayloadIter<'d> {
     data: &'d [u8],
     index: usize,
+    /// end of the payload, no argument may reach beyond it
+    end: usize,
     payload_size: usize,
     count: usize,
     is_verbose: bool,
+    string_coding: Option<StringCoding>,
+    /// structs the argument being read is nested in
+    depth: usize,
     converter: ByteConverter,
 }
 
@@ -243,7 +605,7 @@ impl<'d> Iterator for PayloadIter<'d> {
             self.count -= 1;
             self.read_verbose_argument()
         } else {
-            return None
+            None
         }
     }
 }
@@ -258,150 +620,323 @@ impl<'p,'d:'p> IntoIterator for &'p Payload<'d> {
 }
 
 const SIZE_MSG_ID: usize = mem::size_of::<u32>();
+/// Structs nested deeper are taken as malformed, each level is a recursion of the reader.
+const MAX_STRUCT_DEPTH: usize = 32;
 
+/// The readers return `None` if the payload is malformed, i.e. an argument is of unknown type or
+/// reaches beyond the end of the payload.
 impl<'d> PayloadIter<'d> {
 
-    fn read_verbose_argument(&mut self) -> Option<Value <'d>> {
-        let read_to = self.index + mem::size_of::<u32>();
-        let type_info = self.converter.u32_from_bytes(self.data[self.index .. read_to].try_into().unwrap());
+    fn take_slice(&mut self, len: usize) -> Option<&'d [u8]> {
+        let read_to = self.index.checked_add(len).filter(|read_to| *read_to <= self.end)?;
+        let bytes = &self.data[self.index .. read_to];
         self.index = read_to;
+        Some(bytes)
+    }
+
+    fn take<const N: usize>(&mut self) -> Option<[u8; N]> {
+        self.take_slice(N)?.try_into().ok()
+    }
+
+    fn read_verbose_argument(&mut self) -> Option<Value <'d>> {
+        let type_info = self.take_u32()?;
         let arg_type = Type::from(type_info);
 
+        self.read_value(&arg_type)
+    }
+
+    fn read_value(&mut self, arg_type: &Type) -> Option<Value<'d>> {
         match arg_type {
-            Type::Bool(type_info) => self.read_bool(&type_info),
-            Type::Unsigned(type_info) => self.read_unsigned(&type_info),
-            Type::Signed(type_info) => self.read_signed(&type_info),
-            Type::Float(type_info) => self.read_float(&type_info),
-            Type::String(type_info) => self.read_string(&type_info),
-            Type::Raw(type_info) => self.read_rawdata(&type_info),
-            Type::TraceInfo(type_info) => self.read_trace_info(&type_info),
-            Type::Array(type_info) => self.read_array(&type_info),
-            Type::Struct(type_info) => self.read_struct(&type_info),
+            Type::Bool(type_info) => self.read_bool(type_info),
+            Type::Unsigned(type_info) => self.read_unsigned(type_info),
+            Type::Signed(type_info) => self.read_signed(type_info),
+            Type::Float(type_info) => self.read_float(type_info),
+            Type::String(type_info) => self.read_string(type_info),
+            Type::Raw(type_info) => self.read_rawdata(type_info),
+            Type::TraceInfo(type_info) => self.read_trace_info(type_info),
+            Type::Array(type_info, element_type) => self.read_array(type_info, element_type),
+            Type::Struct(type_info) => self.read_struct(type_info),
             Type::Reserved => None,
         }
     }
 
+    /// Reads a name or unit, cut off before the first byte that is not valid UTF-8.
+    fn read_text(&mut self, len: usize) -> Option<&'d str> {
+        let bytes = self.take_slice(len)?;
+        let text: &'d str = match str::from_utf8(bytes) {
+            Ok(text) => text,
+            Err(err) => str::from_utf8(&bytes[..err.valid_up_to()]).unwrap(),
+        };
+        Some(text.trim_matches(char::from(0)))
+    }
+
+    /// Reads name and, for numbers, unit of the argument if the VARI bit is set. Numbers and
+    /// arrays carry both lengths in front of the strings, all other types only the name length.
+    /// The inner option is empty if the argument has no variable info.
+    fn read_variable_info(&mut self, type_info: &TypeInfo, with_unit: bool) -> Option<Option<(&'d str, Option<&'d str>)>> {
+        if !type_info.var_info {
+            return Some(None);
+        }
+
+        let name_len = self.take_u16()? as usize;
+        let unit_len = if with_unit {
+            let unit_len = self.take_u16()? as usize;
+            Some(unit_len)
+        } else {
+            None
+        };
+
+        let name = self.read_text(name_len)?;
+        let unit = match unit_len {
+            Some(unit_len) => Some(self.read_text(unit_len)?).filter(|unit| !unit.is_empty()),
+            None => None,
+        };
+        Some(Some((name, unit)))
+    }
+
     fn read_bool(&mut self, type_info: &TypeInfo) -> Option<Value<'d>> {
+        let variable_info = self.read_variable_info(type_info, false)?;
         match type_info.length {
             TypeLength::Bits8 => {
-                let read_to = self.index + mem::size_of::<u8>();
-                let boolean = self.converter.u8_from_bytes(*&self.data[self.index .. read_to].try_into().unwrap());
-                self.index = read_to;
-                Some(Value::Bool(boolean == 0x1))
+                let boolean = self.take_u8()?;
+                Some(Value::Bool(boolean == 0x1).with_variable_info(variable_info))
             },
             _ => None,
         }
     }
 
+    /// Reads quantization and offset if the argument is a fixed point number. The inner option is
+    /// empty if it is not.
+    fn read_fixed_point(&mut self, type_info: &TypeInfo) -> Option<Option<(f32, i128)>> {
+        if !type_info.fixed_point {
+            return Some(None);
+        }
+
+        let quantization = self.take_f32()?;
+
+        // the offset has the size of the value, but at least 32 bit
+        let offset = match type_info.length {
+            TypeLength::Bits64 => self.take_i64()? as i128,
+            TypeLength::Bits128 => self.take_i128()?,
+            _ => self.take_i32()? as i128,
+        };
+        Some(Some((quantization, offset)))
+    }
+
     fn read_signed(&mut self, type_info: &TypeInfo) -> Option<Value<'d>> {
+        let variable_info = self.read_variable_info(type_info, true)?;
+        let fixed_point = self.read_fixed_point(type_info)?;
+        self.read_signed_value(type_info).map(|value| value.with_fixed_point(fixed_point).with_variable_info(variable_info))
+    }
+
+    fn read_unsigned(&mut self, type_info: &TypeInfo) -> Option<Value<'d>> {
+        let variable_info = self.read_variable_info(type_info, true)?;
+        let fixed_point = self.read_fixed_point(type_info)?;
+        self.read_unsigned_value(type_info).map(|value| value.with_fixed_point(fixed_point).with_variable_info(variable_info))
+    }
+
+    fn read_signed_value(&mut self, type_info: &TypeInfo) -> Option<Value<'d>> {
         match type_info.length {
             TypeLength::Bits8 => {
-                let read_to = self.index + mem::size_of::<i8>();
-                let signed_int = self.converter.i8_from_bytes(*&self.data[self.index .. read_to].try_into().unwrap());
-                self.index = read_to;
+                let signed_int = self.take_i8()?;
                 Some(Value::SInt8(signed_int))
             },
             TypeLength::Bits16 => {
-                let read_to = self.index + mem::size_of::<i16>();
-                let signed_int = self.converter.i16_from_bytes(*&self.data[self.index .. read_to].try_into().unwrap());
-                self.index = read_to;
+                let signed_int = self.take_i16()?;
                 Some(Value::SInt16(signed_int))
             },
             TypeLength::Bits32 => {
-                let read_to = self.index + mem::size_of::<i32>();
-                let signed_int = self.converter.i32_from_bytes(*&self.data[self.index .. read_to].try_into().unwrap());
-                self.index = read_to;
+                let signed_int = self.take_i32()?;
                 Some(Value::SInt32(signed_int))
             },
             TypeLength::Bits64 => {
-                let read_to = self.index + mem::size_of::<i64>();
-                let signed_int = self.converter.i64_from_bytes(*&self.data[self.index .. read_to].try_into().unwrap());
-                self.index = read_to;
+                let signed_int = self.take_i64()?;
                 Some(Value::SInt64(signed_int))
             },
             TypeLength::Bits128 => {
-                let read_to = self.index + mem::size_of::<i128>();
-                let signed_int = self.converter.i128_from_bytes(*&self.data[self.index .. read_to].try_into().unwrap());
-                self.index = read_to;
+                let signed_int = self.take_i128()?;
                 Some(Value::SInt128(signed_int))
             },
             TypeLength::Undefined => None,
         }
     }
 
-    fn read_unsigned(&mut self, type_info: &TypeInfo) -> Option<Value<'d>> {
+    fn read_unsigned_value(&mut self, type_info: &TypeInfo) -> Option<Value<'d>> {
         match type_info.length {
             TypeLength::Bits8 => {
-                let read_to = self.index + mem::size_of::<u8>();
-                let unsigned_int = self.converter.u8_from_bytes(*&self.data[self.index .. read_to].try_into().unwrap());
-                self.index = read_to;
+                let unsigned_int = self.take_u8()?;
                 Some(Value::UInt8(unsigned_int))
             },
             TypeLength::Bits16 => {
-                let read_to = self.index + mem::size_of::<u16>();
-                let unsigned_int = self.converter.u16_from_bytes(*&self.data[self.index .. read_to].try_into().unwrap());
-                self.index = read_to;
+                let unsigned_int = self.take_u16()?;
                 Some(Value::UInt16(unsigned_int))
             },
             TypeLength::Bits32 => {
-                let read_to = self.index + mem::size_of::<u32>();
-                let unsigned_int = self.converter.u32_from_bytes(*&self.data[self.index .. read_to].try_into().unwrap());
-                self.index = read_to;
+                let unsigned_int = self.take_u32()?;
                 Some(Value::UInt32(unsigned_int))
             },
             TypeLength::Bits64 => {
-                let read_to = self.index + mem::size_of::<u64>();
-                let unsigned_int = self.converter.u64_from_bytes(*&self.data[self.index .. read_to].try_into().unwrap());
-                self.index = read_to;
+                let unsigned_int = self.take_u64()?;
                 Some(Value::UInt64(unsigned_int))
             },
             TypeLength::Bits128 => {
-                let read_to = self.index + mem::size_of::<u128>();
-                let unsigned_int = self.converter.u128_from_bytes(*&self.data[self.index .. read_to].try_into().unwrap());
-                self.index = read_to;
+                let unsigned_int = self.take_u128()?;
                 Some(Value::UInt128(unsigned_int))
             },
             TypeLength::Undefined => None,
         }
     }
 
-    fn read_float(&self, type_info: &TypeInfo) -> Option<Value<'d>> {
-        None
+    fn read_float(&mut self, type_info: &TypeInfo) -> Option<Value<'d>> {
+        let variable_info = self.read_variable_info(type_info, true)?;
+        let value = match type_info.length {
+            TypeLength::Bits32 => {
+                let float = self.take_f32()?;
+                Some(Value::Float32(float))
+            },
+            TypeLength::Bits64 => {
+                let float = self.take_f64()?;
+                Some(Value::Float64(float))
+            },
+            // 16 and 128 bit floats have no native representation
+            _ => None,
+        };
+        value.map(|value| value.with_variable_info(variable_info))
+    }
+
+    fn read_array(&mut self, type_info: &TypeInfo, element_type: &Type) -> Option<Value<'d>> {
+        let dimension_count = self.take_u16()? as usize;
+
+        let mut dimensions = Vec::with_capacity(dimension_count);
+        for _ in 0..dimension_count {
+            dimensions.push(self.take_u16()? as usize);
+        }
+
+        let variable_info = self.read_variable_info(type_info, true)?;
+        let fixed_point = self.read_fixed_point(type_info)?;
+
+        if dimensions.contains(&0) {
+            return Some(Value::Array(vec![]).with_variable_info(variable_info));
+        }
+        // each element takes at least one byte, more elements than bytes left mean a corrupt length
+        let element_count = dimensions.iter().try_fold(1usize, |count, size| count.checked_mul(*size))?;
+        if element_count > self.end - self.index {
+            return None;
+        }
+
+        self.read_array_dimensions(&dimensions, element_type, fixed_point)
+            .map(|value| value.with_variable_info(variable_info))
     }
 
-    fn read_array(&self, _type_info: &TypeInfo) -> Option<Value<'d>> {
-        None
+    fn read_array_dimensions(&mut self, dimensions: &[usize], element_type: &Type, fixed_point: Option<(f32, i128)>) -> Option<Value<'d>> {
+        let Some((size, inner_dimensions)) = dimensions.split_first() else {
+            return Some(Value::Array(vec![]));
+        };
+
+        let mut elements = Vec::with_capacity((*size).min(self.end - self.index));
+        for _ in 0..*size {
+            let element = if inner_dimensions.is_empty() {
+                self.read_value(element_type)?.with_fixed_point(fixed_point)
+            } else {
+                self.read_array_dimensions(inner_dimensions, element_type, fixed_point)?
+            };
+            elements.push(element);
+        }
+        Some(Value::Array(elements))
     }
 
     fn read_string(&mut self, type_info: &TypeInfo) -> Option<Value<'d>> {
-        let mut read_to = self.index + mem::size_of::<u16>();
-        let str_len = self.converter.u16_from_bytes(*&self.data[self.index .. read_to].try_into().unwrap()) as usize;
-        self.index = read_to;
+        let str_len = self.take_u16()? as usize;
 
-        read_to = read_to + str_len;
-        let string: &'d str = str::from_utf8(&self.data[self.index .. read_to]).unwrap().trim_matches(char::from(0));
-        self.index = read_to;
+        let variable_info = self.read_variable_info(type_info, false)?;
+        let bytes = self.take_slice(str_len)?;
+        if let Some(string_coding) = self.string_coding {
+            let is_big_endian = matches!(string_coding, StringCoding::Utf16be);
+            return Some(Value::Utf16String { data: bytes, is_big_endian }.with_variable_info(variable_info));
+        }
+        let string = match type_info.string_coding {
+            // 8 bit characters beyond ASCII are common in practice and read as Latin-1
+            TypeInfoStringEncoding::Ascii if !bytes.is_ascii() => Value::Latin1String(bytes.strip_suffix(&[0]).unwrap_or(bytes)),
+            TypeInfoStringEncoding::Ascii | TypeInfoStringEncoding::Utf8 => match str::from_utf8(bytes) {
+                Ok(string) => Value::String(string.trim_matches(char::from(0))),
+                Err(_) if invalid_utf8() == InvalidUtf8::Hex => Value::Raw(bytes),
+                Err(_) => Value::InvalidString(bytes.strip_suffix(&[0]).unwrap_or(bytes)),
+            },
+            // the characters of a reserved coding are unknown, keep the bytes
+            TypeInfoStringEncoding::Reserved => Value::Raw(bytes),
+        };
 
-        Some(Value::String(string))
+        Some(string.with_variable_info(variable_info))
     }
 
     fn read_rawdata(&mut self, type_info: &TypeInfo) -> Option<Value<'d>> {
-        None
+        let raw_len = self.take_u16()? as usize;
+
+        let variable_info = self.read_variable_info(type_info, false)?;
+
+        let raw_data = self.take_slice(raw_len)?;
+
+        Some(Value::Raw(raw_data).with_variable_info(variable_info))
     }
 
-    fn read_trace_info(&mut self, type_info: &TypeInfo) -> Option<Value<'d>> {
-        let mut read_to = self.index + mem::size_of::<u16>();
-        let str_len = self.converter.u16_from_bytes(*&self.data[self.index .. read_to].try_into().unwrap()) as usize;
-        self.index = read_to;
+    fn read_trace_info(&mut self, _type_info: &TypeInfo) -> Option<Value<'d>> {
+        let str_len = self.take_u16()? as usize;
 
-        read_to = read_to + str_len;
-        let trace_data: &'d str = str::from_utf8(&self.data[self.index .. read_to]).unwrap().trim_matches(char::from(0));
-        self.index = read_to;
+        Some(Value::TraceData(self.read_text(str_len)?))
+    }
+
+    fn read_struct(&mut self, type_info: &TypeInfo) -> Option<Value<'d>> {
+        if self.depth >= MAX_STRUCT_DEPTH {
+            return None;
+        }
+        let entry_count = self.take_u16()? as usize;
+
+        let variable_info = self.read_variable_info(type_info, false)?;
+
+        self.depth += 1;
+        let entries: Option<Vec<_>> = (0..entry_count).map(|_| self.read_verbose_argument()).collect();
+        self.depth -= 1;
+        Some(Value::Struct(entries?).with_variable_info(variable_info))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Verbose argument of `depth` structs nested in each other around a 32 bit unsigned integer.
+    fn nested_structs(depth: usize) -> Vec<u8> {
+        let mut data = vec![];
+        for _ in 0..depth {
+            data.extend_from_slice(&TYPE_INFO_STRUCT_BIT_MASK.to_le_bytes());
+            data.extend_from_slice(&1u16.to_le_bytes());
+        }
+        data.extend_from_slice(&(TYPE_INFO_UINT_BIT_MASK | 3).to_le_bytes());
+        data.extend_from_slice(&7u32.to_le_bytes());
+        data
+    }
+
+    fn read_argument(data: &[u8]) -> Option<Value<'_>> {
+        Payload::new_verbose(data, 0, data.len(), false, 1).iter().next()
+    }
+
+    #[test]
+    fn decodes_nested_structs() {
+        let data = nested_structs(2);
+        let value = read_argument(&data).unwrap();
+        assert!(matches!(&value, Value::Struct(outer) if matches!(outer.as_slice(),
+            [Value::Struct(inner)] if matches!(inner.as_slice(), [Value::UInt32(7)]))));
+    }
 
-        Some(Value::TraceData(trace_data))
+    #[test]
+    fn decodes_structs_up_to_the_depth_limit() {
+        let data = nested_structs(MAX_STRUCT_DEPTH);
+        assert!(read_argument(&data).is_some());
     }
 
-    fn read_struct(&mut self, _type_info: &TypeInfo) -> Option<Value<'d>> {
-        None
+    #[test]
+    fn rejects_structs_nested_too_deeply() {
+        let data = nested_structs(MAX_STRUCT_DEPTH + 1);
+        assert!(read_argument(&data).is_none());
     }
 }
@@ -0,0 +1,568 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use crate::dlt::headers::{peek_message_length, peek_standard_header_ecu_id, write_storage_header};
+
+/// Port DLT daemons send and receive messages on, over TCP and UDP.
+pub const DLT_PORT: u16 = 3490;
+
+const PCAP_MAGIC_MICROSECONDS: u32 = 0xA1B2_C3D4;
+const PCAP_MAGIC_NANOSECONDS: u32 = 0xA1B2_3C4D;
+const PCAPNG_SECTION_HEADER_BLOCK: u32 = 0x0A0D_0D0A;
+const PCAPNG_BYTE_ORDER_MAGIC: u32 = 0x1A2B_3C4D;
+const PCAPNG_INTERFACE_DESCRIPTION_BLOCK: u32 = 1;
+const PCAPNG_SIMPLE_PACKET_BLOCK: u32 = 3;
+const PCAPNG_ENHANCED_PACKET_BLOCK: u32 = 6;
+const PCAPNG_OPTION_END: u16 = 0;
+const PCAPNG_OPTION_TIMESTAMP_RESOLUTION: u16 = 9;
+
+const LINK_TYPE_NULL: u32 = 0;
+const LINK_TYPE_ETHERNET: u32 = 1;
+const LINK_TYPE_RAW: u32 = 101;
+const LINK_TYPE_LINUX_SLL: u32 = 113;
+const LINK_TYPE_IPV4: u32 = 228;
+const LINK_TYPE_IPV6: u32 = 229;
+const LINK_TYPE_LINUX_SLL2: u32 = 276;
+
+const ETHER_TYPE_IPV4: u16 = 0x0800;
+const ETHER_TYPE_IPV6: u16 = 0x86DD;
+const ETHER_TYPE_VLAN: [u16; 2] = [0x8100, 0x88A8];
+
+const IP_PROTOCOL_TCP: u8 = 6;
+const IP_PROTOCOL_UDP: u8 = 17;
+const IPV6_EXTENSION_HEADERS: [u8; 3] = [0, 43, 60];
+
+const TCP_FLAG_FIN: u8 = 0x01;
+const TCP_FLAG_SYN: u8 = 0x02;
+const TCP_FLAG_RST: u8 = 0x04;
+/// Segments held back waiting for a lost one, before the gap is given up on.
+const MAX_PENDING_SEGMENTS: usize = 64;
+
+const DLT_SERIAL_HEADER: [u8; 4] = [0x44, 0x4C, 0x53, 0x01];
+
+/// Checks for the magic number of a pcap or pcapng file.
+pub fn is_capture(data: &[u8]) -> bool {
+    match data.get(..4) {
+        Some(magic) => {
+            let magic = u32::from_le_bytes(magic.try_into().unwrap());
+            [PCAP_MAGIC_MICROSECONDS, PCAP_MAGIC_NANOSECONDS, PCAPNG_SECTION_HEADER_BLOCK].iter()
+                .any(|known| magic == *known || magic == known.swap_bytes())
+        },
+        None => false,
+    }
+}
+
+/// Byte order aware reading of the capture file, which is written in the byte order of the
+/// machine that captured it.
+#[derive(Clone, Copy)]
+struct Endian {
+    is_big_endian: bool,
+}
+
+impl Endian {
+    fn u16(&self, data: &[u8], offset: usize) -> Option<u16> {
+        let bytes = data.get(offset..offset + 2)?.try_into().unwrap();
+        Some(if self.is_big_endian { u16::from_be_bytes(bytes) } else { u16::from_le_bytes(bytes) })
+    }
+
+    fn u32(&self, data: &[u8], offset: usize) -> Option<u32> {
+        let bytes = data.get(offset..offset + 4)?.try_into().unwrap();
+        Some(if self.is_big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) })
+    }
+}
+
+fn be_u16(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(data.get(offset..offset + 2)?.try_into().unwrap()))
+}
+
+fn be_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(offset..offset + 4)?.try_into().unwrap()))
+}
+
+/// Frame of the capture with its time in microseconds since the epoch.
+struct Frame<'d> {
+    micros: u64,
+    link_type: u32,
+    data: &'d [u8],
+}
+
+/// Interface of a pcapng section, which the packets refer to by index.
+struct Interface {
+    link_type: u32,
+    /// timestamp units per second
+    resolution: u64,
+}
+
+/// Reads the frames of a pcap file, a truncated last record is reported as warning.
+fn read_pcap<'d>(data: &'d [u8], warnings: &mut Vec<String>) -> Result<Vec<Frame<'d>>, String> {
+    let magic = u32::from_le_bytes(data[..4].try_into().unwrap());
+    let endian = Endian { is_big_endian: magic != PCAP_MAGIC_MICROSECONDS && magic != PCAP_MAGIC_NANOSECONDS };
+    let is_nanoseconds = endian.u32(data, 0) == Some(PCAP_MAGIC_NANOSECONDS);
+    let link_type = endian.u32(data, 20).ok_or("truncated pcap header")? & 0xFFFF;
+
+    let mut frames = vec![];
+    let mut offset = 24;
+    while offset < data.len() {
+        let start = offset + 16;
+        let (Some(seconds), Some(fraction), Some(length)) = (endian.u32(data, offset), endian.u32(data, offset + 4), endian.u32(data, offset + 8)) else {
+            warnings.push(format!("capture ends in a truncated record at offset {offset}"));
+            break;
+        };
+        let Some(frame) = data.get(start..start + length as usize) else {
+            warnings.push(format!("capture ends in a truncated record at offset {offset}"));
+            break;
+        };
+        let micros = if is_nanoseconds { fraction as u64 / 1000 } else { fraction as u64 };
+        frames.push(Frame { micros: seconds as u64 * 1_000_000 + micros, link_type, data: frame });
+        offset = start + length as usize;
+    }
+    Ok(frames)
+}
+
+/// Reads the frames of a pcapng file, a truncated last block is reported as warning.
+fn read_pcapng<'d>(data: &'d [u8], warnings: &mut Vec<String>) -> Result<Vec<Frame<'d>>, String> {
+    let mut frames = vec![];
+    let mut interfaces: Vec<Interface> = vec![];
+    let mut endian = Endian { is_big_endian: false };
+    let mut offset = 0;
+    while offset < data.len() {
+        if data.get(offset..offset + 4) == Some(&PCAPNG_SECTION_HEADER_BLOCK.to_le_bytes()) {
+            // each section may have another byte order, the block type reads the same in both
+            let byte_order_magic = data.get(offset + 8..offset + 12).ok_or("truncated pcapng section header")?;
+            endian.is_big_endian = byte_order_magic == PCAPNG_BYTE_ORDER_MAGIC.to_be_bytes();
+            interfaces.clear();
+        }
+        let (Some(block_type), Some(block_length)) = (endian.u32(data, offset), endian.u32(data, offset + 4)) else {
+            warnings.push(format!("capture ends in a truncated block at offset {offset}"));
+            break;
+        };
+        let block_length = block_length as usize;
+        if block_length < 12 || !block_length.is_multiple_of(4) {
+            return Err(format!("invalid pcapng block length {block_length} at offset {offset}"));
+        }
+        let Some(block) = data.get(offset..offset + block_length) else {
+            warnings.push(format!("capture ends in a truncated block at offset {offset}"));
+            break;
+        };
+        let body = &block[8..block_length - 4];
+
+        match block_type {
+            PCAPNG_INTERFACE_DESCRIPTION_BLOCK => {
+                let link_type = endian.u16(body, 0).ok_or("truncated pcapng interface description")? as u32;
+                interfaces.push(Interface { link_type, resolution: interface_resolution(body, endian) });
+            },
+            PCAPNG_ENHANCED_PACKET_BLOCK => {
+                let (Some(interface), Some(high), Some(low), Some(length)) = (endian.u32(body, 0), endian.u32(body, 4), endian.u32(body, 8), endian.u32(body, 12)) else {
+                    return Err(format!("truncated pcapng packet at offset {offset}"));
+                };
+                let interface = interfaces.get(interface as usize)
+                    .ok_or_else(|| format!("pcapng packet at offset {offset} refers to unknown interface {interface}"))?;
+                let frame = body.get(20..20 + length as usize)
+                    .ok_or_else(|| format!("truncated pcapng packet at offset {offset}"))?;
+                let timestamp = (high as u64) << 32 | low as u64;
+                let micros = (timestamp as u128 * 1_000_000 / interface.resolution as u128) as u64;
+                frames.push(Frame { micros, link_type: interface.link_type, data: frame });
+            },
+            PCAPNG_SIMPLE_PACKET_BLOCK => {
+                let interface = interfaces.first()
+                    .ok_or_else(|| format!("pcapng packet at offset {offset} without interface"))?;
+                // simple packets carry no time, they get the one of the previous frame
+                let micros = frames.last().map_or(0, |frame: &Frame| frame.micros);
+                frames.push(Frame { micros, link_type: interface.link_type, data: &body[4.min(body.len())..] });
+            },
+            _ => {},
+        }
+        offset += block_length;
+    }
+    Ok(frames)
+}
+
+/// Timestamp units per second of the interface, given by the `if_tsresol` option as power of 10,
+/// or of 2 if the most significant bit is set. Microseconds by default.
+fn interface_resolution(body: &[u8], endian: Endian) -> u64 {
+    let mut offset = 8;
+    while let (Some(code), Some(length)) = (endian.u16(body, offset), endian.u16(body, offset + 2)) {
+        if code == PCAPNG_OPTION_END {
+            break;
+        }
+        if code == PCAPNG_OPTION_TIMESTAMP_RESOLUTION {
+            if let Some(resolution) = body.get(offset + 4) {
+                let exponent = (resolution & 0x7F) as u32;
+                let base: u64 = if resolution & 0x80 == 0 { 10 } else { 2 };
+                return base.checked_pow(exponent).unwrap_or(1_000_000);
+            }
+        }
+        offset += 4 + (length as usize).div_ceil(4) * 4;
+    }
+    1_000_000
+}
+
+/// Address and port of one end of a connection.
+type Endpoint = (IpAddr, u16);
+
+/// Transport segment carrying DLT, with the ports of both ends.
+struct Segment<'d> {
+    source: Endpoint,
+    destination: Endpoint,
+    is_tcp: bool,
+    sequence: u32,
+    flags: u8,
+    payload: &'d [u8],
+}
+
+/// Strips the link layer, returning the ether type and the packet.
+fn link_payload(link_type: u32, data: &[u8]) -> Option<(u16, &[u8])> {
+    match link_type {
+        LINK_TYPE_ETHERNET => {
+            let mut offset = 12;
+            let mut ether_type = be_u16(data, offset)?;
+            while ETHER_TYPE_VLAN.contains(&ether_type) {
+                offset += 4;
+                ether_type = be_u16(data, offset)?;
+            }
+            Some((ether_type, data.get(offset + 2..)?))
+        },
+        LINK_TYPE_LINUX_SLL => Some((be_u16(data, 14)?, data.get(16..)?)),
+        LINK_TYPE_LINUX_SLL2 => Some((be_u16(data, 0)?, data.get(20..)?)),
+        LINK_TYPE_NULL => {
+            // address family in the byte order of the capturing machine
+            let family = u32::from_le_bytes(data.get(..4)?.try_into().unwrap()).min(u32::from_be_bytes(data.get(..4)?.try_into().unwrap()));
+            let ether_type = if family == 2 { ETHER_TYPE_IPV4 } else { ETHER_TYPE_IPV6 };
+            Some((ether_type, data.get(4..)?))
+        },
+        LINK_TYPE_RAW => {
+            let ether_type = if data.first()? >> 4 == 4 { ETHER_TYPE_IPV4 } else { ETHER_TYPE_IPV6 };
+            Some((ether_type, data))
+        },
+        LINK_TYPE_IPV4 => Some((ETHER_TYPE_IPV4, data)),
+        LINK_TYPE_IPV6 => Some((ETHER_TYPE_IPV6, data)),
+        _ => None,
+    }
+}
+
+/// Decodes the IP and TCP or UDP headers of the frame. Fragmented IP packets are not
+/// reassembled and return `None` like packets of other protocols.
+fn read_segment<'d>(frame: &Frame<'d>) -> Option<Segment<'d>> {
+    let (ether_type, packet) = link_payload(frame.link_type, frame.data)?;
+    let (source, destination, protocol, transport) = match ether_type {
+        ETHER_TYPE_IPV4 => {
+            let header_length = (*packet.first()? & 0x0F) as usize * 4;
+            let total_length = be_u16(packet, 2)? as usize;
+            let fragment = be_u16(packet, 6)?;
+            // more fragments flag or fragment offset
+            if fragment & 0x3FFF != 0 {
+                return None;
+            }
+            let source: [u8; 4] = packet.get(12..16)?.try_into().unwrap();
+            let destination: [u8; 4] = packet.get(16..20)?.try_into().unwrap();
+            let end = total_length.clamp(header_length, packet.len());
+            (IpAddr::from(source), IpAddr::from(destination), packet[9], packet.get(header_length..end)?)
+        },
+        ETHER_TYPE_IPV6 => {
+            let payload_length = be_u16(packet, 4)? as usize;
+            let mut next_header = *packet.get(6)?;
+            let source: [u8; 16] = packet.get(8..24)?.try_into().unwrap();
+            let destination: [u8; 16] = packet.get(24..40)?.try_into().unwrap();
+            let mut transport = packet.get(40..(40 + payload_length).min(packet.len()))?;
+            while IPV6_EXTENSION_HEADERS.contains(&next_header) {
+                next_header = *transport.first()?;
+                transport = transport.get((*transport.get(1)? as usize + 1) * 8..)?;
+            }
+            (IpAddr::from(source), IpAddr::from(destination), next_header, transport)
+        },
+        _ => return None,
+    };
+
+    let source_port = be_u16(transport, 0)?;
+    let destination_port = be_u16(transport, 2)?;
+    if source_port != DLT_PORT && destination_port != DLT_PORT {
+        return None;
+    }
+    let (is_tcp, sequence, flags, payload) = match protocol {
+        IP_PROTOCOL_TCP => {
+            let data_offset = (*transport.get(12)? >> 4) as usize * 4;
+            (true, be_u32(transport, 4)?, *transport.get(13)?, transport.get(data_offset..)?)
+        },
+        IP_PROTOCOL_UDP => {
+            let length = (be_u16(transport, 4)? as usize).clamp(8, transport.len());
+            (false, 0, 0, &transport[8..length])
+        },
+        _ => return None,
+    };
+    Some(Segment { source: (source, source_port), destination: (destination, destination_port), is_tcp, sequence, flags, payload })
+}
+
+/// One direction of a TCP connection, reassembled in sequence order.
+#[derive(Default)]
+struct Stream {
+    next_sequence: Option<u32>,
+    /// received data not yet split into messages
+    buffer: Vec<u8>,
+    /// segments received ahead of a missing one, with their sequence number
+    pending: Vec<(u32, Vec<u8>)>,
+}
+
+impl Stream {
+    /// Adds the segment, returns the number of bytes lost if a gap had to be skipped.
+    fn add(&mut self, sequence: u32, payload: &[u8]) -> usize {
+        self.pending.push((sequence, payload.to_vec()));
+        let mut next = *self.next_sequence.get_or_insert(sequence);
+        let mut lost = 0;
+        loop {
+            // sequence numbers wrap, distances are taken modulo 2^32
+            match self.pending.iter().position(|(sequence, _)| sequence.wrapping_sub(next) as i32 <= 0) {
+                Some(position) => {
+                    // retransmitted data is skipped
+                    let (sequence, data) = self.pending.swap_remove(position);
+                    let skip = next.wrapping_sub(sequence) as usize;
+                    if skip < data.len() {
+                        self.buffer.extend_from_slice(&data[skip..]);
+                        next = next.wrapping_add((data.len() - skip) as u32);
+                    }
+                },
+                None if self.pending.len() > MAX_PENDING_SEGMENTS => {
+                    // the missing segment was not captured, the stream continues after the gap
+                    let gap = self.pending.iter().map(|(sequence, _)| sequence.wrapping_sub(next)).min().unwrap();
+                    lost += gap as usize + self.buffer.len();
+                    self.buffer.clear();
+                    next = next.wrapping_add(gap);
+                },
+                None => break,
+            }
+        }
+        self.next_sequence = Some(next);
+        lost
+    }
+}
+
+/// Splits the complete messages off the start of `buffer` and stores them with a storage
+/// header taking the capture time. Returns the number of bytes skipped as not being DLT.
+pub(crate) fn store_messages(buffer: &mut Vec<u8>, micros: u64, out: &mut Vec<u8>) -> usize {
+    let mut offset = 0;
+    let mut skipped = 0;
+    loop {
+        if buffer[offset..].starts_with(&DLT_SERIAL_HEADER) {
+            offset += DLT_SERIAL_HEADER.len();
+        }
+        let data = &buffer[offset..];
+        if data.len() < 4 {
+            break;
+        }
+        let Some(length) = peek_message_length(data) else {
+            offset += 1;
+            skipped += 1;
+            continue;
+        };
+        if data.len() < length {
+            break;
+        }
+        let ecu_id = peek_standard_header_ecu_id(data);
+        write_storage_header(out, (micros / 1_000_000) as u32, (micros % 1_000_000) as u32, &ecu_id);
+        out.extend_from_slice(&data[..length]);
+        offset += length;
+    }
+    buffer.drain(..offset);
+    skipped
+}
+
+/// Extracts the DLT messages sent over TCP or UDP on port 3490 from a pcap or pcapng capture
+/// and stores them with storage headers holding the capture time, so the result reads like a
+/// DLT file. TCP streams are reassembled; problems like lost segments are returned as
+/// warnings along the trace.
+pub fn read_capture(data: &[u8]) -> Result<(Vec<u8>, Vec<String>), String> {
+    let is_pcapng = data.get(..4) == Some(&PCAPNG_SECTION_HEADER_BLOCK.to_le_bytes());
+    let mut warnings = vec![];
+    let frames = if is_pcapng { read_pcapng(data, &mut warnings)? } else { read_pcap(data, &mut warnings)? };
+
+    let mut trace = vec![];
+    let mut streams: HashMap<(Endpoint, Endpoint), Stream> = HashMap::new();
+    let mut skipped = 0;
+    let mut lost = 0;
+    for frame in &frames {
+        let Some(segment) = read_segment(frame) else { continue };
+        if !segment.is_tcp {
+            let mut datagram = segment.payload.to_vec();
+            skipped += store_messages(&mut datagram, frame.micros, &mut trace) + datagram.len();
+            continue;
+        }
+
+        let key = (segment.source, segment.destination);
+        if segment.flags & (TCP_FLAG_SYN | TCP_FLAG_RST) != 0 {
+            skipped += streams.remove(&key).map_or(0, |stream| stream.buffer.len());
+            if segment.flags & TCP_FLAG_SYN != 0 {
+                streams.insert(key, Stream { next_sequence: Some(segment.sequence.wrapping_add(1)), ..Stream::default() });
+            }
+            continue;
+        }
+        let stream = streams.entry(key).or_default();
+        lost += stream.add(segment.sequence, segment.payload);
+        skipped += store_messages(&mut stream.buffer, frame.micros, &mut trace);
+        if segment.flags & TCP_FLAG_FIN != 0 && stream.pending.is_empty() {
+            skipped += streams.remove(&key).map_or(0, |stream| stream.buffer.len());
+        }
+    }
+    skipped += streams.values().map(|stream| stream.buffer.len()).sum::<usize>();
+
+    if lost > 0 {
+        warnings.push(format!("{lost} bytes of TCP streams were not captured, messages around the gaps are lost"));
+    }
+    if skipped > 0 {
+        warnings.push(format!("{skipped} bytes on port {DLT_PORT} were skipped as incomplete or not DLT"));
+    }
+    Ok((trace, warnings))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dlt::headers::{write_log_headers, LogHeaders};
+    use crate::dlt::MessageTypeInfoLog;
+
+    const STORAGE_HEADER_LENGTH: usize = 16;
+    const TCP_FLAGS_PSH_ACK: u8 = 0x18;
+
+    /// Reassembles the segments in the order given, returning the data and the bytes lost.
+    fn reassemble(segments: &[(u32, &[u8])]) -> (Vec<u8>, usize) {
+        let mut stream = Stream::default();
+        let lost = segments.iter().map(|(sequence, payload)| stream.add(*sequence, payload)).sum();
+        (stream.buffer, lost)
+    }
+
+    #[test]
+    fn appends_segments_in_order() {
+        assert_eq!(reassemble(&[(100, b"abc"), (103, b"def")]), (b"abcdef".to_vec(), 0));
+    }
+
+    #[test]
+    fn holds_back_segments_ahead_of_a_missing_one() {
+        let mut stream = Stream::default();
+        stream.add(100, b"abc");
+        stream.add(106, b"ghi");
+        assert_eq!(stream.buffer, b"abc");
+        stream.add(103, b"def");
+        assert_eq!(stream.buffer, b"abcdefghi");
+        assert!(stream.pending.is_empty());
+    }
+
+    #[test]
+    fn skips_retransmitted_data() {
+        let segments: [(u32, &[u8]); 4] = [(100, b"abc"), (100, b"abc"), (101, b"bcde"), (105, b"f")];
+        assert_eq!(reassemble(&segments), (b"abcdef".to_vec(), 0));
+    }
+
+    #[test]
+    fn follows_the_sequence_number_across_the_wrap() {
+        let segments: [(u32, &[u8]); 3] = [(u32::MAX - 1, b"ab"), (1, b"d"), (0, b"c")];
+        assert_eq!(reassemble(&segments), (b"abcd".to_vec(), 0));
+    }
+
+    #[test]
+    fn gives_up_on_a_gap_once_too_many_segments_wait() {
+        let mut segments: Vec<(u32, &[u8])> = vec![(0, b"ab")];
+        // the byte at 2 is never captured
+        segments.extend((0..=MAX_PENDING_SEGMENTS as u32).map(|index| (3 + index, b"x".as_slice())));
+        let (data, lost) = reassemble(&segments);
+        // the incomplete data before the gap is dropped along the missing byte
+        assert_eq!(lost, 3);
+        assert_eq!(data, vec![b'x'; MAX_PENDING_SEGMENTS + 1]);
+    }
+
+    /// DLT message as sent over TCP, without storage header.
+    fn dlt_message(counter: u8) -> Vec<u8> {
+        let headers = LogHeaders {
+            seconds: 0,
+            microseconds: 0,
+            ecu_id: "ECU1",
+            counter,
+            session_id: None,
+            timestamp: Some(1234),
+            is_big_endian: false,
+            app_id: "APP",
+            context_id: "CTX",
+            level: MessageTypeInfoLog::Info,
+            verbose: false,
+            num_of_args: 0,
+        };
+        let mut message = vec![];
+        write_log_headers(&mut message, &headers, 8);
+        message.extend_from_slice(&[1, 0, 0, 0, 0xca, 0xfe, 0xca, 0xfe]);
+        message.split_off(STORAGE_HEADER_LENGTH)
+    }
+
+    /// pcap capture of IPv4 packets without link layer, carrying TCP segments to the DLT port.
+    fn capture(segments: &[(u32, u8, &[u8])]) -> Vec<u8> {
+        let mut capture = vec![];
+        capture.extend_from_slice(&PCAP_MAGIC_MICROSECONDS.to_le_bytes());
+        capture.extend_from_slice(&[2, 0, 4, 0]);
+        capture.extend_from_slice(&[0; 8]);
+        capture.extend_from_slice(&65535u32.to_le_bytes());
+        capture.extend_from_slice(&LINK_TYPE_RAW.to_le_bytes());
+
+        for (index, (sequence, flags, payload)) in segments.iter().enumerate() {
+            let mut packet = vec![0x45, 0];
+            packet.extend_from_slice(&((40 + payload.len()) as u16).to_be_bytes());
+            packet.extend_from_slice(&[0, 0, 0x40, 0, 64, IP_PROTOCOL_TCP, 0, 0]);
+            packet.extend_from_slice(&[10, 0, 0, 1, 10, 0, 0, 2]);
+            packet.extend_from_slice(&50000u16.to_be_bytes());
+            packet.extend_from_slice(&DLT_PORT.to_be_bytes());
+            packet.extend_from_slice(&sequence.to_be_bytes());
+            packet.extend_from_slice(&[0, 0, 0, 0, 0x50, *flags, 0xff, 0xff, 0, 0, 0, 0]);
+            packet.extend_from_slice(payload);
+
+            capture.extend_from_slice(&1_700_000_000u32.to_le_bytes());
+            capture.extend_from_slice(&(index as u32).to_le_bytes());
+            capture.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+            capture.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+            capture.extend_from_slice(&packet);
+        }
+        capture
+    }
+
+    /// The messages of a trace written by `read_capture`, without their storage headers.
+    fn messages(mut trace: &[u8]) -> Vec<Vec<u8>> {
+        let mut messages = vec![];
+        while !trace.is_empty() {
+            let length = peek_message_length(&trace[STORAGE_HEADER_LENGTH..]).unwrap();
+            messages.push(trace[STORAGE_HEADER_LENGTH..STORAGE_HEADER_LENGTH + length].to_vec());
+            trace = &trace[STORAGE_HEADER_LENGTH + length..];
+        }
+        messages
+    }
+
+    #[test]
+    fn reassembles_messages_split_across_segments_out_of_order() {
+        let (first, second) = (dlt_message(1), dlt_message(2));
+        let stream = [first.clone(), second.clone()].concat();
+        let (head, tail) = stream.split_at(first.len() + 5);
+        let head_sequence = 1001;
+        let tail_sequence = head_sequence + head.len() as u32;
+        let capture = capture(&[
+            (1000, TCP_FLAG_SYN, b""),
+            (tail_sequence, TCP_FLAGS_PSH_ACK, tail),
+            (head_sequence, TCP_FLAGS_PSH_ACK, head),
+        ]);
+
+        let (trace, warnings) = read_capture(&capture).unwrap();
+        assert!(warnings.is_empty(), "unexpected warnings {warnings:?}");
+        assert_eq!(messages(&trace), vec![first, second]);
+    }
+
+    #[test]
+    fn warns_about_lost_segments() {
+        let message = dlt_message(1);
+        let (head, tail) = message.split_at(6);
+        // the segment after head is not captured, the stream resumes with the next message
+        let mut segments = vec![(1000, TCP_FLAG_SYN, b"".as_slice()), (1001, TCP_FLAGS_PSH_ACK, head)];
+        let resumed = 1001 + message.len() as u32;
+        let next = dlt_message(2);
+        let mut sequence = resumed;
+        for _ in 0..=MAX_PENDING_SEGMENTS {
+            segments.push((sequence, TCP_FLAGS_PSH_ACK, next.as_slice()));
+            sequence += next.len() as u32;
+        }
+        let (trace, warnings) = read_capture(&capture(&segments)).unwrap();
+
+        assert_eq!(messages(&trace).len(), MAX_PENDING_SEGMENTS + 1);
+        let lost = head.len() + tail.len();
+        assert_eq!(warnings, vec![format!("{lost} bytes of TCP streams were not captured, messages around the gaps are lost")]);
+    }
+}
@@ -0,0 +1,410 @@
+use regex::Regex;
+use crate::dlt::MessageTypeInfoLog;
+use crate::dlt::Message;
+
+/// Log levels from the most to the least severe, `level <= warn` selects fatal, error and warn.
+const LEVELS: [&str; 6] = ["fatal", "error", "warn", "info", "debug", "verbose"];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Num(f64),
+    Op(&'static str),
+    Open,
+    Close,
+}
+
+const OPERATORS: [&str; 11] = ["==", "!=", "<=", ">=", "=~", "&&", "||", "<", ">", "!", "="];
+
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let chars: Vec<char> = input.chars().collect();
+    let mut idx = 0;
+
+    while idx < chars.len() {
+        let c = chars[idx];
+        if c.is_whitespace() {
+            idx += 1;
+        } else if c == '(' || c == ')' {
+            tokens.push(if c == '(' { Token::Open } else { Token::Close });
+            idx += 1;
+        } else if c == '"' {
+            let mut string = String::new();
+            idx += 1;
+            loop {
+                match chars.get(idx) {
+                    Some('"') => break,
+                    Some('\\') if idx + 1 < chars.len() => {
+                        string.push(chars[idx + 1]);
+                        idx += 2;
+                    },
+                    Some(c) => {
+                        string.push(*c);
+                        idx += 1;
+                    },
+                    None => return Err("unterminated string".to_string()),
+                }
+            }
+            idx += 1;
+            tokens.push(Token::Str(string));
+        } else if c.is_ascii_digit() || (c == '-' && chars.get(idx + 1).is_some_and(|c| c.is_ascii_digit())) {
+            let start = idx;
+            idx += 1;
+            while idx < chars.len() && (chars[idx].is_ascii_digit() || chars[idx] == '.') {
+                idx += 1;
+            }
+            let number: String = chars[start..idx].iter().collect();
+            tokens.push(Token::Num(number.parse().map_err(|_| format!("invalid number '{number}'"))?));
+        } else if c.is_alphabetic() || c == '_' {
+            let start = idx;
+            while idx < chars.len() && (chars[idx].is_alphanumeric() || chars[idx] == '_' || chars[idx] == ':') {
+                idx += 1;
+            }
+            tokens.push(Token::Ident(chars[start..idx].iter().collect()));
+        } else {
+            let rest: String = chars[idx..].iter().take(2).collect();
+            let op = OPERATORS.iter().find(|op| rest.starts_with(*op))
+                .ok_or_else(|| format!("unexpected character '{c}'"))?;
+            if *op == "=" {
+                return Err("unknown operator '=', use '=='".to_string());
+            }
+            tokens.push(Token::Op(op));
+            idx += op.len();
+        }
+    }
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone, Copy)]
+enum TextField {
+    Ecu,
+    App,
+    Ctx,
+    Type,
+    Payload,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum NumberField {
+    /// uptime in seconds
+    Timestamp,
+    Counter,
+    Session,
+    /// size of the payload in bytes
+    PayloadLen,
+    Noar,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Compare {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Compare {
+    fn from(op: &str) -> Option<Compare> {
+        match op {
+            "==" => Some(Compare::Eq),
+            "!=" => Some(Compare::Ne),
+            "<" => Some(Compare::Lt),
+            "<=" => Some(Compare::Le),
+            ">" => Some(Compare::Gt),
+            ">=" => Some(Compare::Ge),
+            _ => None,
+        }
+    }
+
+    fn apply<T: PartialOrd>(&self, left: T, right: T) -> bool {
+        match self {
+            Compare::Eq => left == right,
+            Compare::Ne => left != right,
+            Compare::Lt => left < right,
+            Compare::Le => left <= right,
+            Compare::Gt => left > right,
+            Compare::Ge => left >= right,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Text(TextField, bool, String),
+    Matches(TextField, Regex),
+    Number(NumberField, Compare, f64),
+    Level(Compare, usize),
+}
+
+/// Expression over the fields of a message, e.g.
+/// `app == "NAV" && level <= warn && timestamp > 120.5`. It is compiled once and then evaluated
+/// for each message.
+///
+/// Text fields `ecu`, `app`, `ctx`, `type` and `payload` support `==`, `!=` and the regex match
+/// `=~`. Number fields `timestamp` (uptime in seconds), `counter`, `session`, `payload_len`
+/// (payload size in bytes) and `noar` (number of arguments) and the `level` support all
+/// comparisons. Comparisons combine with `&&`, `||`, `!` and parentheses. A message
+/// without the compared field, e.g. the level of a non-log message, does not match.
+#[derive(Debug)]
+pub struct Query {
+    expr: Expr,
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    index: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.index)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.index).cloned();
+        self.index += 1;
+        token
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.parse_and()?;
+        while self.peek() == Some(&Token::Op("||")) {
+            self.index += 1;
+            expr = Expr::Or(Box::new(expr), Box::new(self.parse_and()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.parse_unary()?;
+        while self.peek() == Some(&Token::Op("&&")) {
+            self.index += 1;
+            expr = Expr::And(Box::new(expr), Box::new(self.parse_unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Op("!")) => Ok(Expr::Not(Box::new(self.parse_unary()?))),
+            Some(Token::Open) => {
+                let expr = self.parse_or()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(expr),
+                    _ => Err("missing ')'".to_string()),
+                }
+            },
+            Some(Token::Ident(field)) => self.parse_comparison(&field),
+            Some(token) => Err(format!("expected a field, found {token:?}")),
+            None => Err("unexpected end of expression".to_string()),
+        }
+    }
+
+    fn parse_comparison(&mut self, field: &str) -> Result<Expr, String> {
+        let op = match self.next() {
+            Some(Token::Op(op)) => op,
+            _ => return Err(format!("expected an operator after '{field}'")),
+        };
+        let value = match self.next() {
+            Some(Token::Str(value)) | Some(Token::Ident(value)) => Token::Str(value),
+            Some(Token::Num(value)) => Token::Num(value),
+            _ => return Err(format!("expected a value after '{field} {op}'")),
+        };
+
+        let text_field = match field {
+            "ecu" => Some(TextField::Ecu),
+            "app" => Some(TextField::App),
+            "ctx" => Some(TextField::Ctx),
+            "type" => Some(TextField::Type),
+            "payload" => Some(TextField::Payload),
+            _ => None,
+        };
+        let number_field = match field {
+            "timestamp" => Some(NumberField::Timestamp),
+            "counter" => Some(NumberField::Counter),
+            "session" => Some(NumberField::Session),
+            "payload_len" => Some(NumberField::PayloadLen),
+            "noar" => Some(NumberField::Noar),
+            _ => None,
+        };
+
+        match (field, text_field, number_field, op, value) {
+            (_, Some(text_field), _, "=~", Token::Str(pattern)) => {
+                let regex = Regex::new(&pattern).map_err(|err| format!("invalid pattern for '{field}': {err}"))?;
+                Ok(Expr::Matches(text_field, regex))
+            },
+            (_, Some(text_field), _, "==" | "!=", Token::Str(value)) => Ok(Expr::Text(text_field, op == "==", value)),
+            (_, Some(_), _, "==" | "!=" | "=~", Token::Num(value)) => Err(format!("'{field}' is compared with text, not with {value}")),
+            (_, Some(_), _, _, _) => Err(format!("operator '{op}' is not supported for '{field}'")),
+            (_, _, Some(number_field), _, Token::Num(value)) => {
+                let compare = Compare::from(op).ok_or_else(|| format!("operator '{op}' is not supported for '{field}'"))?;
+                Ok(Expr::Number(number_field, compare, value))
+            },
+            (_, _, Some(_), _, _) => Err(format!("'{field}' is compared with a number")),
+            ("level", _, _, _, Token::Str(level)) => {
+                let compare = Compare::from(op).ok_or_else(|| format!("operator '{op}' is not supported for 'level'"))?;
+                let level = LEVELS.iter().position(|name| name.eq_ignore_ascii_case(&level))
+                    .ok_or_else(|| format!("unknown level '{level}', expected one of {}", LEVELS.join(", ")))?;
+                Ok(Expr::Level(compare, level))
+            },
+            ("level", _, _, _, _) => Err(format!("'level' is compared with one of {}", LEVELS.join(", "))),
+            _ => Err(format!("unknown field '{field}'")),
+        }
+    }
+}
+
+fn log_level(msg: &Message) -> Option<usize> {
+    let level = match msg.log_level()? {
+        MessageTypeInfoLog::Fatal => 0,
+        MessageTypeInfoLog::Error => 1,
+        MessageTypeInfoLog::Warn => 2,
+        MessageTypeInfoLog::Info => 3,
+        MessageTypeInfoLog::Debug => 4,
+        MessageTypeInfoLog::Verbose => 5,
+    };
+    Some(level)
+}
+
+fn text(msg: &Message, field: TextField) -> Option<String> {
+    match field {
+        TextField::Ecu => Some(msg.ecu_id().to_string()),
+        TextField::App => msg.extended_header.as_ref().map(|header| header.app_id().clone()),
+        TextField::Ctx => msg.extended_header.as_ref().map(|header| header.context_id().clone()),
+        TextField::Type => msg.extended_header.as_ref().map(|header| header.type_name()),
+        TextField::Payload => Some(msg.payload_text()),
+    }
+}
+
+fn number(msg: &Message, field: NumberField) -> Option<f64> {
+    match field {
+        NumberField::Timestamp => msg.standard_header.timestamp().map(|timestamp| timestamp as f64 / 10_000.0),
+        NumberField::Counter => Some(msg.standard_header.counter() as f64),
+        NumberField::Session => msg.standard_header.session_id().map(|session_id| session_id as f64),
+        NumberField::PayloadLen => Some(msg.payload_len() as f64),
+        NumberField::Noar => msg.number_of_arguments().map(|noar| noar as f64),
+    }
+}
+
+impl Expr {
+    fn eval(&self, msg: &Message) -> bool {
+        match self {
+            Expr::And(left, right) => left.eval(msg) && right.eval(msg),
+            Expr::Or(left, right) => left.eval(msg) || right.eval(msg),
+            Expr::Not(expr) => !expr.eval(msg),
+            Expr::Text(field, equal, value) => text(msg, *field).is_some_and(|text| (text == *value) == *equal),
+            Expr::Matches(field, regex) => text(msg, *field).is_some_and(|text| regex.is_match(&text)),
+            Expr::Number(field, compare, value) => number(msg, *field).is_some_and(|number| compare.apply(number, *value)),
+            Expr::Level(compare, value) => log_level(msg).is_some_and(|level| compare.apply(level, *value)),
+        }
+    }
+}
+
+impl Query {
+    pub fn compile(input: &str) -> Result<Query, String> {
+        let mut parser = Parser { tokens: tokenize(input)?, index: 0 };
+        let expr = parser.parse_or()?;
+        match parser.peek() {
+            None => Ok(Query { expr }),
+            Some(token) => Err(format!("unexpected {token:?} after the expression")),
+        }
+    }
+
+    pub fn matches(&self, msg: &Message) -> bool {
+        self.expr.eval(msg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The expression with every operation in parentheses, so the grouping is visible.
+    fn grouping(expr: &Expr) -> String {
+        match expr {
+            Expr::And(left, right) => format!("({} && {})", grouping(left), grouping(right)),
+            Expr::Or(left, right) => format!("({} || {})", grouping(left), grouping(right)),
+            Expr::Not(expr) => format!("!{}", grouping(expr)),
+            Expr::Text(field, equal, value) => format!("{field:?}{}{value}", if *equal { "==" } else { "!=" }),
+            Expr::Matches(field, regex) => format!("{field:?}=~{regex}"),
+            Expr::Number(field, compare, value) => format!("{field:?} {compare:?} {value}"),
+            Expr::Level(compare, level) => format!("Level {compare:?} {level}"),
+        }
+    }
+
+    fn parse(input: &str) -> String {
+        grouping(&Query::compile(input).unwrap().expr)
+    }
+
+    fn error(input: &str) -> String {
+        Query::compile(input).unwrap_err()
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        assert_eq!(parse("app == A || app == B && ctx == C"), "(App==A || (App==B && Ctx==C))");
+        assert_eq!(parse("app == A && app == B || ctx == C"), "((App==A && App==B) || Ctx==C)");
+    }
+
+    #[test]
+    fn operators_of_equal_precedence_group_from_the_left() {
+        assert_eq!(parse("ecu == E || app == A || ctx == C"), "((Ecu==E || App==A) || Ctx==C)");
+        assert_eq!(parse("ecu == E && app == A && ctx == C"), "((Ecu==E && App==A) && Ctx==C)");
+    }
+
+    #[test]
+    fn not_binds_tighter_than_and() {
+        assert_eq!(parse("!app == A && ctx == C"), "(!App==A && Ctx==C)");
+        assert_eq!(parse("!!app == A"), "!!App==A");
+    }
+
+    #[test]
+    fn parentheses_override_precedence() {
+        assert_eq!(parse("(app == A || app == B) && ctx == C"), "((App==A || App==B) && Ctx==C)");
+        assert_eq!(parse("!(app == A || counter > 3)"), "!(App==A || Counter Gt 3)");
+    }
+
+    #[test]
+    fn parses_the_values_of_each_kind_of_field() {
+        assert_eq!(parse("level <= warn && timestamp > 120.5"), "(Level Le 2 && Timestamp Gt 120.5)");
+        assert_eq!(parse(r#"payload =~ "temp \\d+""#), r"Payload=~temp \d+");
+        assert_eq!(parse("session == -1"), "Session Eq -1");
+        assert_eq!(parse(r#"ctx != "a \"b\"""#), r#"Ctx!=a "b""#);
+    }
+
+    #[test]
+    fn reports_incomplete_expressions() {
+        assert_eq!(error(""), "unexpected end of expression");
+        assert_eq!(error("app == A &&"), "unexpected end of expression");
+        assert_eq!(error("(app == A"), "missing ')'");
+        assert_eq!(error("app =="), "expected a value after 'app =='");
+        assert_eq!(error("app"), "expected an operator after 'app'");
+        assert_eq!(error(r#"payload == "open"#), "unterminated string");
+    }
+
+    #[test]
+    fn reports_tokens_after_the_expression() {
+        assert_eq!(error("app == A)"), "unexpected Close after the expression");
+        assert_eq!(error("app == A ctx == C"), r#"unexpected Ident("ctx") after the expression"#);
+    }
+
+    #[test]
+    fn reports_invalid_operators_and_values() {
+        assert_eq!(error("app = A"), "unknown operator '=', use '=='");
+        assert_eq!(error("app # A"), "unexpected character '#'");
+        assert_eq!(error("app < A"), "operator '<' is not supported for 'app'");
+        assert_eq!(error("app == 3"), "'app' is compared with text, not with 3");
+        assert_eq!(error("counter == x"), "'counter' is compared with a number");
+        assert_eq!(error("counter =~ 3"), "operator '=~' is not supported for 'counter'");
+        assert_eq!(error("level == loud"), "unknown level 'loud', expected one of fatal, error, warn, info, debug, verbose");
+        assert_eq!(error("level == 3"), "'level' is compared with one of fatal, error, warn, info, debug, verbose");
+        assert_eq!(error("size > 3"), "unknown field 'size'");
+        assert_eq!(error("&& app == A"), r#"expected a field, found Op("&&")"#);
+        assert!(error(r#"payload =~ "(""#).starts_with("invalid pattern for 'payload'"));
+    }
+}
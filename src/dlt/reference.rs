@@ -0,0 +1,59 @@
+use std::fs;
+use std::path::Path;
+use crate::config::WallClock;
+
+/// Events recorded outside the trace, e.g. CAN trigger timestamps exported from a BLF
+/// measurement, that messages are annotated with by storage time.
+///
+/// Read from a CSV file with the time of the event in the first column, as seconds since the
+/// epoch or RFC 3339 time, and its name in the second. A header line and lines starting with `#`
+/// are skipped.
+#[derive(Debug)]
+pub struct ReferenceEvents {
+    /// seconds since the epoch and name, sorted by time
+    events: Vec<(f64, String)>,
+}
+
+impl ReferenceEvents {
+    pub fn load(file_path: &Path) -> Result<ReferenceEvents, String> {
+        let content = fs::read_to_string(file_path)
+            .map_err(|err| format!("error on reading reference events {file_path:?}: {err}"))?;
+        let mut events = vec![];
+        for (line_idx, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (time, name) = line.split_once(',').unwrap_or((line, ""));
+            let time = match WallClock::parse(time.trim()) {
+                Ok(time) => time.as_secs_f64(),
+                // the column names
+                Err(_) if events.is_empty() && line_idx == 0 => continue,
+                Err(err) => return Err(format!("{file_path:?}, line {}: {err}", line_idx + 1)),
+            };
+            let name = name.trim().trim_matches('"');
+            let name = if name.is_empty() { format!("line {}", line_idx + 1) } else { name.to_string() };
+            events.push((time, name));
+        }
+        if events.is_empty() {
+            return Err(format!("no reference events in {file_path:?}"));
+        }
+        events.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+        Ok(ReferenceEvents { events })
+    }
+
+    /// The event closest to the time and how many seconds the time is after it, negative if
+    /// the time is before the event.
+    pub fn nearest(&self, time: f64) -> Option<(&str, f64)> {
+        let next = self.events.partition_point(|(event_time, _)| *event_time < time);
+        let before = next.checked_sub(1).map(|idx| &self.events[idx]);
+        let after = self.events.get(next);
+        let (event_time, name) = match (before, after) {
+            (Some(before), Some(after)) if time - before.0 <= after.0 - time => before,
+            (_, Some(after)) => after,
+            (Some(before), None) => before,
+            (None, None) => return None,
+        };
+        Some((name, time - event_time))
+    }
+}
@@ -0,0 +1,43 @@
+use std::collections::HashMap;
+use crate::dlt::control::ControlMessage;
+use crate::dlt::Message;
+
+/// Descriptions of the applications and contexts per ECU, harvested from the GetLogInfo
+/// responses in a trace.
+#[derive(Default, Debug)]
+pub struct Registry {
+    apps: HashMap<(String, String), String>,
+    contexts: HashMap<(String, String, String), String>,
+}
+
+impl Registry {
+    pub fn new() -> Registry {
+        Registry::default()
+    }
+
+    pub fn add(&mut self, msg: &Message) {
+        let Some(ControlMessage::LogInfo { apps, .. }) = ControlMessage::from_message(msg) else {
+            return;
+        };
+
+        let ecu_id = msg.ecu_id();
+        for app in apps {
+            if let Some(description) = app.description.filter(|description| !description.is_empty()) {
+                self.apps.insert((ecu_id.to_string(), app.app_id.to_string()), description.to_string());
+            }
+            for context in app.contexts {
+                if let Some(description) = context.description.filter(|description| !description.is_empty()) {
+                    self.contexts.insert((ecu_id.to_string(), app.app_id.to_string(), context.context_id.to_string()), description.to_string());
+                }
+            }
+        }
+    }
+
+    pub fn app_description(&self, ecu_id: &str, app_id: &str) -> Option<&str> {
+        self.apps.get(&(ecu_id.to_string(), app_id.to_string())).map(String::as_str)
+    }
+
+    pub fn context_description(&self, ecu_id: &str, app_id: &str, context_id: &str) -> Option<&str> {
+        self.contexts.get(&(ecu_id.to_string(), app_id.to_string(), context_id.to_string())).map(String::as_str)
+    }
+}
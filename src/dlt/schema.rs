@@ -0,0 +1,109 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use serde_derive::Serialize;
+use crate::dlt::Message;
+use crate::dlt::counters::CounterKey;
+use crate::dlt::payload::Value;
+
+/// Deviating messages listed per signature, the rest is only counted.
+const MAX_EXAMPLES: usize = 5;
+
+/// Argument signatures of the verbose messages of each ECU/app/context, e.g. `string,u32`. The
+/// most frequent one is taken as the typical signature of the context, messages with another one
+/// are flagged, as they can point at a serializer that changed between software versions.
+#[derive(Default)]
+pub struct Schemas {
+    contexts: BTreeMap<String, BTreeMap<String, Signature>>,
+}
+
+#[derive(Serialize)]
+struct Signature {
+    messages: usize,
+    /// "file:index" of the first messages with the signature
+    examples: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct ContextSchema<'s> {
+    context: &'s str,
+    messages: usize,
+    typical: &'s str,
+    /// signatures other than the typical one
+    deviations: BTreeMap<&'s str, &'s Signature>,
+}
+
+impl Schemas {
+    pub fn new() -> Schemas {
+        Schemas::default()
+    }
+
+    pub fn add(&mut self, file_path: &Path, index: usize, msg: &Message) {
+        if !msg.extended_header.as_ref().is_some_and(|header| header.is_verbose()) {
+            return;
+        }
+        let signature = msg.payload.iter().map(type_name).collect::<Vec<_>>().join(",");
+        let signatures = self.contexts.entry(CounterKey::of(msg).to_string()).or_default();
+        let signature = signatures.entry(signature).or_insert(Signature { messages: 0, examples: vec![] });
+        signature.messages += 1;
+        if signature.examples.len() < MAX_EXAMPLES {
+            signature.examples.push(format!("{}:{index}", file_path.display()));
+        }
+    }
+
+    /// The typical signature and the deviations of each context.
+    pub fn report(&self) -> Vec<ContextSchema<'_>> {
+        self.contexts.iter().filter_map(|(context, signatures)| {
+            // ties go to the signature first in order, so the report is stable
+            let (typical, _) = signatures.iter()
+                .max_by(|(a_name, a), (b_name, b)| a.messages.cmp(&b.messages).then(b_name.cmp(a_name)))?;
+            Some(ContextSchema {
+                context,
+                messages: signatures.values().map(|signature| signature.messages).sum(),
+                typical,
+                deviations: signatures.iter()
+                    .filter(|(name, _)| *name != typical)
+                    .map(|(name, signature)| (name.as_str(), signature))
+                    .collect(),
+            })
+        }).collect()
+    }
+}
+
+impl Display for ContextSchema<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let typical_messages = self.messages - self.deviations.values().map(|signature| signature.messages).sum::<usize>();
+        writeln!(f, "{}: ({}) in {typical_messages} of {} messages", self.context, self.typical, self.messages)?;
+        for (name, signature) in &self.deviations {
+            writeln!(f, "  deviating ({name}) in {} messages, e.g. {}", signature.messages, signature.examples.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Short name of the type of the argument, nested for arrays and structs.
+fn type_name(value: &Value) -> String {
+    match value {
+        Value::Bool(_) => "bool".to_string(),
+        Value::SInt8(_) => "i8".to_string(),
+        Value::SInt16(_) => "i16".to_string(),
+        Value::SInt32(_) => "i32".to_string(),
+        Value::SInt64(_) => "i64".to_string(),
+        Value::SInt128(_) => "i128".to_string(),
+        Value::UInt8(_) => "u8".to_string(),
+        Value::UInt16(_) => "u16".to_string(),
+        Value::UInt32(_) => "u32".to_string(),
+        Value::UInt64(_) => "u64".to_string(),
+        Value::UInt128(_) => "u128".to_string(),
+        Value::Float32(_) => "f32".to_string(),
+        Value::Float64(_) => "f64".to_string(),
+        Value::String(_) | Value::InvalidString(_) | Value::Latin1String(_) | Value::Utf16String { .. } => "string".to_string(),
+        Value::Raw(_) => "raw".to_string(),
+        Value::Array(values) => format!("[{}]", values.first().map(type_name).unwrap_or_default()),
+        Value::Struct(values) => format!("{{{}}}", values.iter().map(type_name).collect::<Vec<_>>().join(",")),
+        Value::TraceData(_) => "trace".to_string(),
+        Value::NonVerbose { .. } => "non-verbose".to_string(),
+        Value::Named { value, .. } => type_name(value),
+        Value::FixedPoint { .. } => "fixed".to_string(),
+    }
+}
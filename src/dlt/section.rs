@@ -0,0 +1,36 @@
+use std::collections::HashSet;
+use regex::Regex;
+use crate::dlt::Message;
+use crate::dlt::counters::CounterKey;
+
+/// Cuts out the messages of each ECU/app/context from a message matching the start pattern to
+/// the next one matching the stop pattern, both included, e.g. a single use case of a drive log.
+pub struct Sections {
+    start: Regex,
+    stop: Regex,
+    open: HashSet<CounterKey>,
+}
+
+impl Sections {
+    pub fn new(start: &Regex, stop: &Regex) -> Sections {
+        Sections { start: start.clone(), stop: stop.clone(), open: HashSet::new() }
+    }
+
+    /// Whether the message is within a section of its context. Has to see every message, not
+    /// only the ones passing the other filters, so a section opens and closes regardless of them.
+    pub fn contains(&mut self, msg: &Message) -> bool {
+        let key = CounterKey::of(msg);
+        let text = msg.payload_text();
+        if self.open.contains(&key) {
+            if self.stop.is_match(&text) {
+                self.open.remove(&key);
+            }
+            true
+        } else if self.start.is_match(&text) {
+            self.open.insert(key);
+            true
+        } else {
+            false
+        }
+    }
+}
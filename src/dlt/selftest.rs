@@ -0,0 +1,185 @@
+use std::process;
+use crate::dlt::headers::{write_log_headers, LogHeaders};
+use crate::dlt::payload::{write_value, Value};
+use crate::dlt::{MessageTypeInfoLog, TraceData};
+
+const ECU_ID: &str = "TEST";
+const APP_ID: &str = "SELF";
+const CONTEXT_ID: &str = "TEST";
+const NON_VERBOSE_MESSAGE_ID: u32 = 0x0102_0304;
+const LEVELS: [MessageTypeInfoLog; 6] = [
+    MessageTypeInfoLog::Fatal,
+    MessageTypeInfoLog::Error,
+    MessageTypeInfoLog::Warn,
+    MessageTypeInfoLog::Info,
+    MessageTypeInfoLog::Debug,
+    MessageTypeInfoLog::Verbose,
+];
+
+/// Message of the synthetic trace, with the values it has to be read back with.
+struct Expected {
+    seconds: u32,
+    microseconds: u32,
+    counter: u8,
+    session_id: Option<u32>,
+    timestamp: Option<u32>,
+    is_big_endian: bool,
+    level: MessageTypeInfoLog,
+    payload: Vec<Value<'static>>,
+    verbose: bool,
+}
+
+/// Arguments of the verbose messages, one message per entry, covering every argument type the
+/// parser decodes.
+fn argument_sets() -> Vec<Vec<Value<'static>>> {
+    let named = |name, unit, value| Value::Named { name, unit, value: Box::new(value) };
+    vec![
+        vec![],
+        vec![Value::Bool(true), Value::Bool(false)],
+        vec![Value::SInt8(i8::MIN), Value::SInt16(-1234), Value::SInt32(i32::MIN), Value::SInt64(-1), Value::SInt128(i128::MIN)],
+        vec![Value::UInt8(u8::MAX), Value::UInt16(0x1234), Value::UInt32(0xDEAD_BEEF), Value::UInt64(u64::MAX), Value::UInt128(1 << 100)],
+        vec![Value::Float32(-1.5), Value::Float64(std::f64::consts::PI)],
+        vec![Value::String("ascii text"), Value::String("UTF-8: äöü ✓"), Value::String("")],
+        vec![Value::Raw(&[0xde, 0xad, 0xbe, 0xef]), Value::Raw(&[])],
+        vec![Value::TraceData("trace info")],
+        vec![
+            named("speed", Some("km/h"), Value::UInt16(120)),
+            named("temperature", None, Value::Float32(21.5)),
+            named("enabled", None, Value::Bool(true)),
+            named("label", None, Value::String("named")),
+            named("blob", None, Value::Raw(&[1, 2, 3])),
+        ],
+        vec![
+            Value::Array(vec![Value::SInt32(1), Value::SInt32(-2), Value::SInt32(3)]),
+            Value::Array(vec![
+                Value::Array(vec![Value::UInt8(1), Value::UInt8(2)]),
+                Value::Array(vec![Value::UInt8(3), Value::UInt8(4)]),
+            ]),
+            named("samples", Some("V"), Value::Array(vec![Value::Float64(0.5), Value::Float64(1.5)])),
+        ],
+        vec![Value::Struct(vec![Value::UInt32(7), Value::String("entry"), named("flag", None, Value::Bool(false))])],
+    ]
+}
+
+/// Encodes every message of the synthetic trace, returning the trace and what has to be read
+/// back from it.
+fn encode() -> (Vec<u8>, Vec<Expected>) {
+    let mut trace = vec![];
+    let mut expected = vec![];
+
+    let mut variants = vec![];
+    for is_big_endian in [false, true] {
+        for session_id in [None, Some(0x00C0_FFEE)] {
+            for timestamp in [None, Some(123_456)] {
+                for payload in argument_sets() {
+                    variants.push((is_big_endian, session_id, timestamp, payload, true));
+                }
+                variants.push((is_big_endian, session_id, timestamp, vec![], false));
+            }
+        }
+    }
+
+    for (index, (is_big_endian, session_id, timestamp, payload, verbose)) in variants.into_iter().enumerate() {
+        let mut message = Expected {
+            seconds: 1_700_000_000 + index as u32,
+            microseconds: (index as u32 * 1_001) % 1_000_000,
+            counter: index as u8,
+            session_id,
+            timestamp,
+            is_big_endian,
+            level: LEVELS[index % LEVELS.len()],
+            payload,
+            verbose,
+        };
+
+        let mut payload = vec![];
+        if verbose {
+            for value in &message.payload {
+                if !write_value(&mut payload, value, is_big_endian) {
+                    eprintln!("selftest: cannot encode {value:?}");
+                    process::exit(1);
+                }
+            }
+        } else {
+            let id_bytes = if is_big_endian { NON_VERBOSE_MESSAGE_ID.to_be_bytes() } else { NON_VERBOSE_MESSAGE_ID.to_le_bytes() };
+            payload.extend_from_slice(&id_bytes);
+            payload.extend_from_slice(&[0xca, 0xfe]);
+            message.payload = vec![Value::NonVerbose { message_id: NON_VERBOSE_MESSAGE_ID, data: &[0xca, 0xfe] }];
+        }
+
+        let headers = LogHeaders {
+            seconds: message.seconds,
+            microseconds: message.microseconds,
+            ecu_id: ECU_ID,
+            counter: message.counter,
+            session_id,
+            timestamp,
+            is_big_endian,
+            app_id: APP_ID,
+            context_id: CONTEXT_ID,
+            level: message.level,
+            verbose,
+            num_of_args: if verbose { message.payload.len() as u8 } else { 0 },
+        };
+        write_log_headers(&mut trace, &headers, payload.len());
+        trace.extend_from_slice(&payload);
+        expected.push(message);
+    }
+    (trace, expected)
+}
+
+/// Generates a trace covering all argument types and header flags, reads it back and compares
+/// every message with what was written, as a smoke check of the parser on this machine. Exits
+/// with 1 if any message differs.
+pub fn run_selftest() {
+    let (trace, expected) = encode();
+    let trace_data = TraceData::new(&trace, 0);
+    let mut messages = trace_data.iter();
+    let mut failures = 0;
+    let mut count = 0;
+
+    for (index, written) in expected.iter().enumerate() {
+        let Some(msg) = messages.next() else {
+            eprintln!("message {index}: missing, the trace ended early");
+            failures += expected.len() - index;
+            break;
+        };
+        count += 1;
+        let header = msg.extended_header.as_ref();
+        let checks = [
+            ("storage time", msg.storage_header.seconds() == written.seconds && msg.storage_header.microseconds() == written.microseconds),
+            ("ecu id", msg.ecu_id() == ECU_ID && msg.storage_header.ecu_id() == ECU_ID),
+            ("counter", msg.standard_header.counter() == written.counter as usize),
+            ("session id", msg.session_id() == written.session_id),
+            ("timestamp", msg.standard_header.timestamp() == written.timestamp),
+            ("byte order", msg.standard_header.is_big_endian() == written.is_big_endian),
+            ("app/context id", header.is_some_and(|header| header.app_id() == APP_ID && header.context_id() == CONTEXT_ID)),
+            ("verbose", header.is_some_and(|header| header.is_verbose() == written.verbose)),
+            ("log level", msg.log_level() == Some(written.level)),
+            ("malformed", !msg.is_malformed()),
+            ("payload", msg.payload == written.payload),
+        ];
+        let failed: Vec<_> = checks.iter().filter(|(_, passed)| !passed).map(|(name, _)| *name).collect();
+        if !failed.is_empty() {
+            eprintln!("message {index}: {} differ\n  written: {:?}\n  read:    {msg:?}", failed.join(", "), written.payload);
+            failures += 1;
+        }
+    }
+    if messages.next().is_some() {
+        eprintln!("more messages read than written");
+        failures += 1;
+    }
+    if let Some(error) = messages.error() {
+        eprintln!("error on reading the trace: {error}");
+        failures += 1;
+    }
+    for warning in messages.warnings() {
+        eprintln!("unexpected warning at offset {}: {}", warning.offset, warning.message);
+        failures += 1;
+    }
+
+    println!("selftest: {count} of {} messages read back, {failures} failures", expected.len());
+    if failures > 0 {
+        process::exit(1);
+    }
+}
@@ -0,0 +1,176 @@
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use serde_json::{json, Value};
+use crate::dlt::stats::Stats;
+use crate::dlt::{map_file, TraceData};
+
+/// Messages between two progress notifications.
+const PROGRESS_INTERVAL: usize = 10_000;
+const PAUSE_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+const ERROR_PARSE: i64 = -32700;
+const ERROR_METHOD_NOT_FOUND: i64 = -32601;
+const ERROR_INVALID_PARAMS: i64 = -32602;
+const ERROR_CANCELLED: i64 = -32800;
+const ERROR_BUSY: i64 = -32000;
+
+/// Writes the JSON-RPC responses and notifications to stdout, one per line.
+#[derive(Clone)]
+struct Channel {
+    stdout: Arc<Mutex<io::Stdout>>,
+}
+
+impl Channel {
+    fn send(&self, message: Value) {
+        let mut stdout = self.stdout.lock().unwrap();
+        // the front-end went away if stdout is closed, stdin ends then as well
+        let _ = writeln!(stdout, "{message}").and_then(|_| stdout.flush());
+    }
+
+    fn result(&self, id: &Value, result: Value) {
+        self.send(json!({ "jsonrpc": "2.0", "id": id, "result": result }));
+    }
+
+    fn error(&self, id: &Value, code: i64, message: &str) {
+        self.send(json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } }));
+    }
+
+    fn notify(&self, method: &str, params: Value) {
+        self.send(json!({ "jsonrpc": "2.0", "method": method, "params": params }));
+    }
+}
+
+#[derive(Default)]
+struct JobControl {
+    running: AtomicBool,
+    paused: AtomicBool,
+    cancelled: AtomicBool,
+}
+
+/// Serves JSON-RPC 2.0 on stdin/stdout, one message per line, so a front-end can use the parser
+/// as engine. `stats` with `{"files": [...]}` parses the files, reporting `progress`
+/// notifications, and answers with the statistics. While it runs, `pause`, `resume` and `cancel`
+/// control it. The server stops at the end of stdin or on `shutdown`.
+pub fn run_server() {
+    let channel = Channel { stdout: Arc::new(Mutex::new(io::stdout())) };
+    let control = Arc::new(JobControl::default());
+    let jobs = read_requests(channel.clone(), control.clone());
+
+    for (id, files) in jobs {
+        match run_stats_job(&files, &channel, &control) {
+            Some(stats) => channel.result(&id, serde_json::to_value(&stats).unwrap()),
+            None => channel.error(&id, ERROR_CANCELLED, "cancelled"),
+        }
+        control.running.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Reads the requests on a thread of its own, so control requests are handled while a job runs.
+/// Returns the jobs to run in order.
+fn read_requests(channel: Channel, control: Arc<JobControl>) -> Receiver<(Value, Vec<PathBuf>)> {
+    let (jobs, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            let Ok(line) = line else { break };
+            if line.trim().is_empty() {
+                continue;
+            }
+            let request: Value = match serde_json::from_str(&line) {
+                Ok(request) => request,
+                Err(err) => {
+                    channel.error(&Value::Null, ERROR_PARSE, &err.to_string());
+                    continue;
+                },
+            };
+            let id = request.get("id").cloned().unwrap_or(Value::Null);
+            let method = request.get("method").and_then(Value::as_str).unwrap_or_default();
+
+            let flag = match method {
+                "stats" => {
+                    let files: Option<Vec<PathBuf>> = request.pointer("/params/files")
+                        .and_then(|files| serde_json::from_value(files.clone()).ok());
+                    match files {
+                        Some(_) if control.running.load(Ordering::SeqCst) => channel.error(&id, ERROR_BUSY, "a job is running"),
+                        Some(files) => {
+                            // a pause or cancel arriving after this belongs to the new job
+                            control.cancelled.store(false, Ordering::SeqCst);
+                            control.paused.store(false, Ordering::SeqCst);
+                            control.running.store(true, Ordering::SeqCst);
+                            if jobs.send((id, files)).is_err() {
+                                break;
+                            }
+                        },
+                        None => channel.error(&id, ERROR_INVALID_PARAMS, "expected params {\"files\": [...]}"),
+                    }
+                    continue;
+                },
+                "shutdown" => {
+                    channel.result(&id, Value::Bool(true));
+                    control.cancelled.store(true, Ordering::SeqCst);
+                    break;
+                },
+                "pause" => (&control.paused, true),
+                "resume" => (&control.paused, false),
+                "cancel" => (&control.cancelled, true),
+                _ => {
+                    channel.error(&id, ERROR_METHOD_NOT_FOUND, &format!("unknown method '{method}'"));
+                    continue;
+                },
+            };
+            flag.0.store(flag.1, Ordering::SeqCst);
+            // notifications without id get no response
+            if !id.is_null() {
+                channel.result(&id, Value::Bool(control.running.load(Ordering::SeqCst)));
+            }
+        }
+    });
+    receiver
+}
+
+/// Gathers the statistics of the files, `None` if the job got cancelled.
+fn run_stats_job(files: &[PathBuf], channel: &Channel, control: &JobControl) -> Option<Stats> {
+    let mut stats = Stats::new();
+    let mut failures = vec![];
+
+    for file in files {
+        let mmap = match map_file(file) {
+            Ok(mmap) => mmap,
+            Err(error) => {
+                failures.push(json!({ "file": file, "error": error }));
+                continue;
+            },
+        };
+        let trace = TraceData::new(&mmap, 0);
+        let mut messages = trace.iter();
+        let mut count = 0;
+        while let Some(msg) = messages.next() {
+            stats.add(&msg);
+            count += 1;
+            if count % PROGRESS_INTERVAL == 0 {
+                channel.notify("progress", json!({ "file": file, "offset": messages.index, "size": mmap.len(), "messages": count }));
+            }
+            while control.paused.load(Ordering::SeqCst) && !control.cancelled.load(Ordering::SeqCst) {
+                thread::sleep(PAUSE_POLL_INTERVAL);
+            }
+            if control.cancelled.load(Ordering::SeqCst) {
+                return None;
+            }
+        }
+        channel.notify("progress", json!({ "file": file, "offset": mmap.len(), "size": mmap.len(), "messages": count }));
+        if let Some(error) = messages.error() {
+            failures.push(json!({ "file": file, "error": error }));
+        }
+        for warning in messages.warnings() {
+            channel.notify("warning", json!({ "file": file, "warning": warning }));
+        }
+    }
+    if !failures.is_empty() {
+        channel.notify("failures", Value::Array(failures));
+    }
+    Some(stats)
+}
@@ -0,0 +1,129 @@
+use std::fmt::{Display, Formatter};
+use crate::dlt::headers::{MessageType, MessageTypeInfoNetworkTrace};
+use crate::dlt::Message;
+use crate::dlt::payload::Value;
+
+const HEADER_SIZE: usize = 16;
+const MESSAGE_TYPE_TP_FLAG: u8 = 0x20;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SomeIpMessageType {
+    Request,
+    RequestNoReturn,
+    Notification,
+    Response,
+    Error,
+    Unknown(u8),
+}
+
+impl From<u8> for SomeIpMessageType {
+    fn from(value: u8) -> Self {
+        match value & !MESSAGE_TYPE_TP_FLAG {
+            0x00 => SomeIpMessageType::Request,
+            0x01 => SomeIpMessageType::RequestNoReturn,
+            0x02 => SomeIpMessageType::Notification,
+            0x80 => SomeIpMessageType::Response,
+            0x81 => SomeIpMessageType::Error,
+            _ => SomeIpMessageType::Unknown(value),
+        }
+    }
+}
+
+impl Display for SomeIpMessageType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SomeIpMessageType::Request => write!(f, "request"),
+            SomeIpMessageType::RequestNoReturn => write!(f, "request_no_return"),
+            SomeIpMessageType::Notification => write!(f, "notification"),
+            SomeIpMessageType::Response => write!(f, "response"),
+            SomeIpMessageType::Error => write!(f, "error"),
+            SomeIpMessageType::Unknown(value) => write!(f, "type 0x{value:02x}"),
+        }
+    }
+}
+
+fn return_code_name(return_code: u8) -> Option<&'static str> {
+    match return_code {
+        0x00 => Some("E_OK"),
+        0x01 => Some("E_NOT_OK"),
+        0x02 => Some("E_UNKNOWN_SERVICE"),
+        0x03 => Some("E_UNKNOWN_METHOD"),
+        0x04 => Some("E_NOT_READY"),
+        0x05 => Some("E_NOT_REACHABLE"),
+        0x06 => Some("E_TIMEOUT"),
+        0x07 => Some("E_WRONG_PROTOCOL_VERSION"),
+        0x08 => Some("E_WRONG_INTERFACE_VERSION"),
+        0x09 => Some("E_MALFORMED_MESSAGE"),
+        0x0A => Some("E_WRONG_MESSAGE_TYPE"),
+        _ => None,
+    }
+}
+
+/// SOME/IP message carried by a network trace message.
+#[derive(Debug)]
+pub struct SomeIp<'d> {
+    pub service_id: u16,
+    pub method_id: u16,
+    pub client_id: u16,
+    pub session_id: u16,
+    pub protocol_version: u8,
+    pub interface_version: u8,
+    pub message_type: SomeIpMessageType,
+    /// the message is a segment of a larger one, sent with SOME/IP-TP
+    pub is_segment: bool,
+    pub return_code: u8,
+    pub payload: &'d [u8],
+}
+
+impl<'d> SomeIp<'d> {
+    /// Decodes the SOME/IP network trace messages. The SOME/IP header, in big endian, is taken
+    /// from the end of the first raw argument, so a prefix like the addresses of the connection
+    /// is skipped, and the payload from the second one. If the message has a single raw
+    /// argument, it holds the header followed by the payload.
+    pub fn from_message(msg: &Message<'d>) -> Option<SomeIp<'d>> {
+        let header = msg.extended_header.as_ref()?;
+        if !matches!(header.msg_type(), MessageType::NetworkTrace) || !matches!(header.msg_type_info_network_trace(), Some(MessageTypeInfoNetworkTrace::SomeIp)) {
+            return None;
+        }
+
+        let mut raw_arguments = msg.payload.iter().filter_map(|value| match value.value() {
+            Value::Raw(data) => Some(*data),
+            _ => None,
+        });
+        let (header, payload) = match (raw_arguments.next()?, raw_arguments.next()) {
+            (header, Some(payload)) => (header.get(header.len().checked_sub(HEADER_SIZE)?..)?, payload),
+            (data, None) => (data.get(..HEADER_SIZE)?, &data[HEADER_SIZE..]),
+        };
+
+        let u16_at = |index: usize| u16::from_be_bytes([header[index], header[index + 1]]);
+        Some(SomeIp {
+            service_id: u16_at(0),
+            method_id: u16_at(2),
+            // the length at 4..8 covers the rest of the header and the payload, which the
+            // trace may carry truncated, so the payload is taken as is
+            client_id: u16_at(8),
+            session_id: u16_at(10),
+            protocol_version: header[12],
+            interface_version: header[13],
+            message_type: SomeIpMessageType::from(header[14]),
+            is_segment: header[14] & MESSAGE_TYPE_TP_FLAG != 0,
+            return_code: header[15],
+            payload,
+        })
+    }
+}
+
+impl Display for SomeIp<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "SOME/IP service 0x{:04x} method 0x{:04x} {}", self.service_id, self.method_id, self.message_type)?;
+        if self.is_segment {
+            write!(f, " segment")?;
+        }
+        match return_code_name(self.return_code) {
+            Some(name) => write!(f, " {name}")?,
+            None => write!(f, " return code 0x{:02x}", self.return_code)?,
+        }
+        write!(f, " client 0x{:04x} session 0x{:04x} payload ", self.client_id, self.session_id)?;
+        self.payload.iter().try_for_each(|byte| write!(f, "{byte:02x}"))
+    }
+}
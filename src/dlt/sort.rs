@@ -0,0 +1,243 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use crate::dlt::Message;
+use crate::dlt::lifecycle::Lifecycles;
+
+/// Bytes of bookkeeping per buffered message on top of its own bytes.
+const ENTRY_OVERHEAD: usize = 48;
+/// Size of the read buffer of each run during the merge.
+const RUN_BUFFER: usize = 64 * 1024;
+
+/// Lifecycle, uptime in 0.1 ms and position in the input: messages of a boot are ordered by
+/// their uptime, the boots in the order they start in the input, equal uptimes keep their order.
+type SortKey = (u64, u32, u64);
+
+/// Orders the messages of a trace by device time with bounded memory. The messages are buffered
+/// up to `max_memory` bytes, then sorted and spilled to a temporary run file, the runs are merged
+/// in the end. Messages without uptime take the one of the message before them in their boot.
+pub struct ExternalSort {
+    lifecycles: Lifecycles,
+    last_timestamps: HashMap<usize, u32>,
+    sequence: u64,
+    buffer: Vec<(SortKey, Vec<u8>)>,
+    buffered_bytes: usize,
+    max_memory: usize,
+    temp_dir: PathBuf,
+    runs: Vec<PathBuf>,
+}
+
+impl ExternalSort {
+    pub fn new(max_memory: usize, temp_dir: &Path) -> ExternalSort {
+        ExternalSort {
+            lifecycles: Lifecycles::new(),
+            last_timestamps: HashMap::new(),
+            sequence: 0,
+            buffer: vec![],
+            buffered_bytes: 0,
+            max_memory,
+            temp_dir: temp_dir.to_path_buf(),
+            runs: vec![],
+        }
+    }
+
+    pub fn add(&mut self, msg: &Message) -> io::Result<()> {
+        let lifecycle = self.lifecycles.add(msg);
+        let last_timestamp = self.last_timestamps.entry(lifecycle).or_insert(0);
+        if let Some(timestamp) = msg.standard_header.timestamp() {
+            *last_timestamp = timestamp;
+        }
+        let key = (lifecycle as u64, *last_timestamp, self.sequence);
+        self.sequence += 1;
+
+        self.buffered_bytes += msg.bytes.len() + ENTRY_OVERHEAD;
+        self.buffer.push((key, msg.bytes.to_vec()));
+        if self.buffered_bytes >= self.max_memory {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    /// Number of run files spilled so far.
+    pub fn runs(&self) -> usize {
+        self.runs.len()
+    }
+
+    /// Writes the messages in order and removes the run files.
+    pub fn finish(mut self, output: &mut impl Write, mut progress: impl FnMut(u64, u64)) -> io::Result<()> {
+        let total = self.sequence;
+        if self.runs.is_empty() {
+            self.buffer.sort_unstable_by_key(|(key, _)| *key);
+            for (written, (_, bytes)) in self.buffer.iter().enumerate() {
+                output.write_all(bytes)?;
+                progress(written as u64 + 1, total);
+            }
+            return Ok(());
+        }
+        if !self.buffer.is_empty() {
+            self.spill()?;
+        }
+
+        let mut runs = self.runs.iter()
+            .map(|path| File::open(path).map(|file| BufReader::with_capacity(RUN_BUFFER, file)))
+            .collect::<io::Result<Vec<_>>>()?;
+        let mut heads = BinaryHeap::new();
+        for (run, reader) in runs.iter_mut().enumerate() {
+            if let Some((key, bytes)) = read_entry(reader)? {
+                heads.push(Reverse((key, run, bytes)));
+            }
+        }
+        let mut written = 0;
+        while let Some(Reverse((_, run, bytes))) = heads.pop() {
+            output.write_all(&bytes)?;
+            written += 1;
+            progress(written, total);
+            if let Some((key, bytes)) = read_entry(&mut runs[run])? {
+                heads.push(Reverse((key, run, bytes)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Sorts the buffered messages and writes them to a new run file.
+    fn spill(&mut self) -> io::Result<()> {
+        self.buffer.sort_unstable_by_key(|(key, _)| *key);
+        let path = self.temp_dir.join(format!("dlt-kraken-sort-{}-{}.run", process::id(), self.runs.len()));
+        // registered before it is written, so a failed run is removed as well
+        self.runs.push(path.clone());
+        let mut writer = BufWriter::new(File::create(&path)?);
+        for ((lifecycle, timestamp, sequence), bytes) in self.buffer.drain(..) {
+            writer.write_all(&lifecycle.to_le_bytes())?;
+            writer.write_all(&timestamp.to_le_bytes())?;
+            writer.write_all(&sequence.to_le_bytes())?;
+            writer.write_all(&(bytes.len() as u32).to_le_bytes())?;
+            writer.write_all(&bytes)?;
+        }
+        writer.flush()?;
+        self.buffered_bytes = 0;
+        Ok(())
+    }
+}
+
+impl Drop for ExternalSort {
+    fn drop(&mut self) {
+        for run in &self.runs {
+            let _ = fs::remove_file(run);
+        }
+    }
+}
+
+fn read_entry(reader: &mut impl Read) -> io::Result<Option<(SortKey, Vec<u8>)>> {
+    let mut header = [0u8; 24];
+    match reader.read_exact(&mut header) {
+        Ok(()) => {},
+        Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err),
+    }
+    let lifecycle = u64::from_le_bytes(header[0..8].try_into().unwrap());
+    let timestamp = u32::from_le_bytes(header[8..12].try_into().unwrap());
+    let sequence = u64::from_le_bytes(header[12..20].try_into().unwrap());
+    let length = u32::from_le_bytes(header[20..24].try_into().unwrap()) as usize;
+    let mut bytes = vec![0u8; length];
+    reader.read_exact(&mut bytes)?;
+    Ok(Some(((lifecycle, timestamp, sequence), bytes)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dlt::headers::{write_log_headers, LogHeaders};
+    use crate::dlt::{MessageTypeInfoLog, TraceData};
+
+    /// Trace of header only messages of one ECU with the given uptimes, numbered by their counter.
+    fn trace(timestamps: &[Option<u32>]) -> Vec<u8> {
+        let mut trace = vec![];
+        for (counter, timestamp) in timestamps.iter().enumerate() {
+            let headers = LogHeaders {
+                seconds: 1_700_000_000,
+                microseconds: 0,
+                ecu_id: "ECU1",
+                counter: counter as u8,
+                session_id: None,
+                timestamp: *timestamp,
+                is_big_endian: false,
+                app_id: "APP",
+                context_id: "CTX",
+                level: MessageTypeInfoLog::Info,
+                verbose: true,
+                num_of_args: 0,
+            };
+            write_log_headers(&mut trace, &headers, 0);
+        }
+        trace
+    }
+
+    /// Sorts the trace in a temporary directory of its own, returning the counters of the sorted
+    /// messages and the number of run files spilled.
+    fn sort(name: &str, timestamps: &[Option<u32>], max_memory: usize) -> (Vec<usize>, usize) {
+        let temp_dir = std::env::temp_dir().join(format!("dlt-kraken-sort-test-{}-{name}", process::id()));
+        fs::create_dir_all(&temp_dir).unwrap();
+        let trace = trace(timestamps);
+        let mut sort = ExternalSort::new(max_memory, &temp_dir);
+        for msg in &TraceData::new(&trace, 0) {
+            sort.add(&msg).unwrap();
+        }
+        let runs = sort.runs();
+        let mut output = vec![];
+        sort.finish(&mut output, |_, _| {}).unwrap();
+
+        let is_cleaned_up = fs::read_dir(&temp_dir).unwrap().next().is_none();
+        fs::remove_dir_all(&temp_dir).unwrap();
+        assert!(is_cleaned_up, "run files left in {temp_dir:?}");
+        let counters = TraceData::new(&output, 0).into_iter().map(|msg| msg.standard_header.counter()).collect();
+        (counters, runs)
+    }
+
+    #[test]
+    fn sorts_in_memory_without_runs() {
+        let (counters, runs) = sort("memory", &[Some(30), Some(10), Some(20)], usize::MAX);
+        assert_eq!(runs, 0);
+        assert_eq!(counters, vec![1, 2, 0]);
+    }
+
+    #[test]
+    fn merges_runs_in_uptime_order() {
+        let timestamps = [Some(50), Some(10), Some(40), Some(20), Some(60), Some(30), Some(0)];
+        let (counters, runs) = sort("merge", &timestamps, 200);
+        assert!(runs > 1, "expected several runs, got {runs}");
+        assert_eq!(counters, vec![6, 1, 3, 5, 2, 0, 4]);
+    }
+
+    #[test]
+    fn merges_runs_of_single_messages() {
+        let timestamps = [Some(3), Some(1), Some(2), Some(0)];
+        let (counters, runs) = sort("single", &timestamps, 1);
+        assert_eq!(runs, timestamps.len());
+        assert_eq!(counters, vec![3, 1, 2, 0]);
+    }
+
+    #[test]
+    fn keeps_the_input_order_of_equal_uptimes_across_runs() {
+        let timestamps = [Some(7), Some(5), Some(7), Some(5), Some(7)];
+        let (counters, _) = sort("stable", &timestamps, 1);
+        assert_eq!(counters, vec![1, 3, 0, 2, 4]);
+    }
+
+    #[test]
+    fn orders_boots_as_they_start_in_the_input() {
+        // the uptime jumps back by more than the reboot tolerance after the second message
+        let timestamps = [Some(50_000), Some(40_000), Some(100), Some(50)];
+        let (counters, _) = sort("boots", &timestamps, 1);
+        assert_eq!(counters, vec![1, 0, 3, 2]);
+    }
+
+    #[test]
+    fn takes_the_uptime_of_the_message_before_if_missing() {
+        let timestamps = [Some(30), None, Some(10), None];
+        let (counters, _) = sort("missing", &timestamps, 1);
+        assert_eq!(counters, vec![2, 3, 0, 1]);
+    }
+}
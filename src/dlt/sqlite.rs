@@ -0,0 +1,80 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::Duration;
+use rusqlite::{ffi, params_from_iter, Connection};
+use crate::dlt::chaos;
+use crate::{OutputField, Sqlite};
+
+/// Rows inserted in one transaction, unless flushed before.
+const TRANSACTION_ROWS: usize = 4096;
+
+/// Writes the rows of the sqlite output into a table of the database, one text column per field.
+/// A table of the same name is replaced. Outputs into the same database share its connection, so
+/// each filter can write its own table of it.
+pub struct SqliteWriter {
+    connection: Rc<Connection>,
+    insert: String,
+    rows: usize,
+    /// bytes of the values inserted, for the faults of `--chaos`
+    written: u64,
+}
+
+impl SqliteWriter {
+    /// Creates the table, `databases` are the connections opened by the writers created before.
+    pub fn create(sqlite: &Sqlite, fields: &[OutputField], databases: &mut HashMap<PathBuf, Rc<Connection>>) -> rusqlite::Result<SqliteWriter> {
+        let connection = match databases.get(&sqlite.file_path) {
+            Some(connection) => connection.clone(),
+            None => {
+                let connection = Rc::new(Connection::open(&sqlite.file_path)?);
+                // a reader may be looking at the database of the last run
+                connection.busy_timeout(Duration::from_secs(5))?;
+                databases.insert(sqlite.file_path.clone(), connection.clone());
+                connection
+            },
+        };
+        let table = quote(&sqlite.table);
+        let columns: Vec<String> = fields.iter().map(|field| quote(field.name())).collect();
+        let definitions: Vec<String> = columns.iter().map(|column| format!("{column} TEXT")).collect();
+        connection.execute_batch(&format!("DROP TABLE IF EXISTS {table}; CREATE TABLE {table} ({});", definitions.join(", ")))?;
+        let insert = format!("INSERT INTO {table} ({}) VALUES ({})", columns.join(", "), vec!["?"; columns.len()].join(", "));
+        Ok(SqliteWriter { connection, insert, rows: 0, written: 0 })
+    }
+
+    /// Inserts the row, a value per field. The rows are committed in batches, unless `flush` is
+    /// set.
+    pub fn write(&mut self, values: &[String], flush: bool) -> rusqlite::Result<()> {
+        let len = values.iter().map(String::len).sum();
+        chaos::write_row(self.written, len)
+            .map_err(|err| rusqlite::Error::SqliteFailure(ffi::Error::new(ffi::SQLITE_FULL), Some(err.to_string())))?;
+        self.written += len as u64;
+        if self.connection.is_autocommit() {
+            self.connection.execute_batch("BEGIN")?;
+        }
+        self.connection.prepare_cached(&self.insert)?.execute(params_from_iter(values))?;
+        self.rows += 1;
+        if flush || self.rows >= TRANSACTION_ROWS {
+            self.commit()?;
+        }
+        Ok(())
+    }
+
+    /// Commits the remaining rows.
+    pub fn finish(mut self) -> rusqlite::Result<()> {
+        self.commit()
+    }
+
+    fn commit(&mut self) -> rusqlite::Result<()> {
+        self.rows = 0;
+        // the transaction may have been committed by another writer of the database
+        if self.connection.is_autocommit() {
+            return Ok(());
+        }
+        self.connection.execute_batch("COMMIT")
+    }
+}
+
+/// Quotes the name of a table or column, so it may be any text.
+fn quote(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
@@ -0,0 +1,242 @@
+use std::collections::{BTreeMap, HashMap};
+use std::collections::hash_map::Entry;
+use std::fmt::{Display, Formatter};
+use serde_derive::Serialize;
+use crate::dlt::control::BufferOverflow;
+use crate::dlt::counters::{counter_gap, CounterKey};
+use crate::dlt::Message;
+
+#[derive(Serialize, Debug)]
+pub struct TimeSpan {
+    start: f64,
+    end: f64,
+}
+
+impl TimeSpan {
+    fn update(span: &mut Option<TimeSpan>, value: f64) {
+        match span {
+            Some(span) => {
+                span.start = span.start.min(value);
+                span.end = span.end.max(value);
+            },
+            None => *span = Some(TimeSpan { start: value, end: value }),
+        }
+    }
+
+    fn merge(span: &mut Option<TimeSpan>, other: Option<TimeSpan>) {
+        if let Some(other) = other {
+            TimeSpan::update(span, other.start);
+            TimeSpan::update(span, other.end);
+        }
+    }
+
+    pub fn start(&self) -> f64 {
+        self.start
+    }
+
+    pub fn end(&self) -> f64 {
+        self.end
+    }
+
+    pub fn duration(&self) -> f64 {
+        self.end - self.start
+    }
+}
+
+impl Display for TimeSpan {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:.6}s - {:.6}s ({:.6}s)", self.start, self.end, self.duration())
+    }
+}
+
+/// Statistics gathered over all messages of one or more traces. Messages are fed in with `add`,
+/// the statistics of consecutive parts of the traces, e.g. gathered by parallel workers, are
+/// combined with `merge`.
+#[derive(Serialize, Debug, Default)]
+pub struct Stats {
+    messages: usize,
+    ecus: BTreeMap<String, usize>,
+    apps: BTreeMap<String, usize>,
+    contexts: BTreeMap<String, usize>,
+    levels: BTreeMap<String, usize>,
+    storage_time: Option<TimeSpan>,
+    uptime: Option<TimeSpan>,
+    drops: usize,
+    /// messages lost by "ECU/APP/CONTEXT"
+    context_drops: BTreeMap<String, usize>,
+    overflow_drops: u64,
+    malformed: usize,
+    /// messages without payload
+    header_only: usize,
+    /// first and last message counter of each ECU/app/context
+    #[serde(skip)]
+    counters: HashMap<CounterKey, (usize, usize)>,
+}
+
+impl Stats {
+    pub fn new() -> Stats {
+        Stats::default()
+    }
+
+    pub fn add(&mut self, msg: &Message) {
+        self.messages += 1;
+        if msg.is_malformed() {
+            self.malformed += 1;
+        }
+        if msg.is_header_only() {
+            self.header_only += 1;
+        }
+
+        if let Some(overflow) = BufferOverflow::from_message(msg) {
+            self.overflow_drops += overflow.lost() as u64;
+        }
+
+        *self.ecus.entry(msg.ecu_id().to_string()).or_default() += 1;
+
+        let storage_time = msg.storage_header.time();
+        TimeSpan::update(&mut self.storage_time, storage_time);
+        if let Some(timestamp) = msg.standard_header.timestamp() {
+            TimeSpan::update(&mut self.uptime, timestamp as f64 / 10_000.0);
+        }
+
+        if let Some(level) = msg.log_level() {
+            *self.levels.entry(level.to_string().to_lowercase()).or_default() += 1;
+        }
+
+        if let Some(header) = &msg.extended_header {
+            *self.apps.entry(header.app_id().clone()).or_default() += 1;
+            *self.contexts.entry(format!("{}/{}", header.app_id(), header.context_id())).or_default() += 1;
+        }
+
+        let counter = msg.standard_header.counter();
+        match self.counters.entry(CounterKey::of(msg)) {
+            Entry::Occupied(mut entry) => {
+                let lost = counter_gap(entry.get().1, counter);
+                Stats::add_drops(&mut self.drops, &mut self.context_drops, entry.key(), lost);
+                entry.get_mut().1 = counter;
+            },
+            Entry::Vacant(entry) => {
+                entry.insert((counter, counter));
+            },
+        }
+    }
+
+    /// Adds the statistics of the messages following the ones of these statistics. Messages lost
+    /// between both parts are counted as drops.
+    pub fn merge(&mut self, other: Stats) {
+        self.messages += other.messages;
+        for (counts, other_counts) in [(&mut self.ecus, other.ecus), (&mut self.apps, other.apps), (&mut self.contexts, other.contexts), (&mut self.levels, other.levels)] {
+            for (name, count) in other_counts {
+                *counts.entry(name).or_default() += count;
+            }
+        }
+        TimeSpan::merge(&mut self.storage_time, other.storage_time);
+        TimeSpan::merge(&mut self.uptime, other.uptime);
+        self.drops += other.drops;
+        for (context, lost) in other.context_drops {
+            *self.context_drops.entry(context).or_default() += lost;
+        }
+        self.overflow_drops += other.overflow_drops;
+        self.malformed += other.malformed;
+        self.header_only += other.header_only;
+
+        for (key, (first, last)) in other.counters {
+            match self.counters.entry(key) {
+                Entry::Occupied(mut entry) => {
+                    let lost = counter_gap(entry.get().1, first);
+                    Stats::add_drops(&mut self.drops, &mut self.context_drops, entry.key(), lost);
+                    entry.get_mut().1 = last;
+                },
+                Entry::Vacant(entry) => {
+                    entry.insert((first, last));
+                },
+            }
+        }
+    }
+
+    fn add_drops(drops: &mut usize, context_drops: &mut BTreeMap<String, usize>, key: &CounterKey, lost: usize) {
+        if lost > 0 {
+            *drops += lost;
+            *context_drops.entry(key.to_string()).or_default() += lost;
+        }
+    }
+
+    pub fn messages(&self) -> usize {
+        self.messages
+    }
+
+    pub fn ecus(&self) -> &BTreeMap<String, usize> {
+        &self.ecus
+    }
+
+    pub fn apps(&self) -> &BTreeMap<String, usize> {
+        &self.apps
+    }
+
+    /// Message counts by "APP/CONTEXT".
+    pub fn contexts(&self) -> &BTreeMap<String, usize> {
+        &self.contexts
+    }
+
+    /// Message counts by log level.
+    pub fn levels(&self) -> &BTreeMap<String, usize> {
+        &self.levels
+    }
+
+    pub fn storage_time(&self) -> &Option<TimeSpan> {
+        &self.storage_time
+    }
+
+    pub fn uptime(&self) -> &Option<TimeSpan> {
+        &self.uptime
+    }
+
+    /// Messages lost according to gaps in the message counters.
+    pub fn drops(&self) -> usize {
+        self.drops
+    }
+
+    /// Messages lost according to gaps in the message counters, by "ECU/APP/CONTEXT".
+    pub fn context_drops(&self) -> &BTreeMap<String, usize> {
+        &self.context_drops
+    }
+
+    /// Messages lost in buffer overflows, as reported by the ECUs.
+    pub fn overflow_drops(&self) -> u64 {
+        self.overflow_drops
+    }
+
+    pub fn malformed(&self) -> usize {
+        self.malformed
+    }
+
+    pub fn header_only(&self) -> usize {
+        self.header_only
+    }
+}
+
+impl Display for Stats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "messages: {}", self.messages)?;
+        writeln!(f, "dropped: {}", self.drops)?;
+        for (context, lost) in &self.context_drops {
+            writeln!(f, "  {context}: {lost}")?;
+        }
+        writeln!(f, "dropped in buffer overflows: {}", self.overflow_drops)?;
+        writeln!(f, "malformed: {}", self.malformed)?;
+        writeln!(f, "header only: {}", self.header_only)?;
+        if let Some(span) = &self.storage_time {
+            writeln!(f, "storage time: {span}")?;
+        }
+        if let Some(span) = &self.uptime {
+            writeln!(f, "uptime: {span}")?;
+        }
+        for (title, counts) in [("ecus", &self.ecus), ("apps", &self.apps), ("contexts", &self.contexts), ("levels", &self.levels)] {
+            writeln!(f, "{title}:")?;
+            for (name, count) in counts {
+                writeln!(f, "  {name}: {count}")?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+use regex::Regex;
+use serde_derive::Serialize;
+use crate::config::Span;
+use crate::dlt::apptrace::CallTracker;
+use crate::dlt::Message;
+use crate::dlt::payload::Value;
+
+/// Event in the Chrome `trace_event` format, which is also understood by Perfetto.
+#[derive(Serialize, Debug)]
+struct TraceEvent {
+    name: String,
+    ph: &'static str,
+    /// microseconds
+    ts: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dur: Option<f64>,
+    pid: usize,
+    tid: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    args: Option<HashMap<&'static str, String>>,
+}
+
+impl TraceEvent {
+    fn complete(name: String, start: f64, end: f64, pid: usize, tid: usize) -> TraceEvent {
+        TraceEvent { name, ph: "X", ts: start, dur: Some(end - start), pid, tid, args: None }
+    }
+
+    fn metadata(kind: &str, name: &str, pid: usize, tid: usize) -> TraceEvent {
+        TraceEvent {
+            name: kind.to_string(),
+            ph: "M",
+            ts: 0.0,
+            dur: None,
+            pid,
+            tid,
+            args: Some(HashMap::from([("name", name.to_string())])),
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct TraceFile {
+    #[serde(rename = "traceEvents")]
+    trace_events: Vec<TraceEvent>,
+    #[serde(rename = "displayTimeUnit")]
+    display_time_unit: &'static str,
+}
+
+struct SpanMatcher {
+    name: String,
+    app_id: Option<String>,
+    context_id: Option<String>,
+    start: Regex,
+    stop: Regex,
+}
+
+impl SpanMatcher {
+    fn from(span: &Span) -> Result<SpanMatcher, String> {
+        let compile = |pattern: &str| Regex::new(pattern)
+            .map_err(|err| format!("invalid pattern in span '{}': {err}", span.name()));
+        Ok(SpanMatcher {
+            name: span.name().clone(),
+            app_id: span.app_id().clone(),
+            context_id: span.context_id().clone(),
+            start: compile(span.start_pattern())?,
+            stop: compile(span.stop_pattern())?,
+        })
+    }
+}
+
+/// Collects spans between start/stop pattern matches and between FunctionIn/FunctionOut
+/// app-trace messages. Each ECU becomes a process and each app/context a thread of the timeline.
+pub struct Timeline {
+    spans: Vec<SpanMatcher>,
+    events: Vec<TraceEvent>,
+    processes: HashMap<String, usize>,
+    threads: HashMap<(usize, String), usize>,
+    open_spans: HashMap<(usize, usize), f64>,
+    calls: CallTracker,
+}
+
+impl Timeline {
+    pub fn new(spans: &[Span]) -> Result<Timeline, String> {
+        Ok(Timeline {
+            spans: spans.iter().map(SpanMatcher::from).collect::<Result<_, _>>()?,
+            events: vec![],
+            processes: HashMap::new(),
+            threads: HashMap::new(),
+            open_spans: HashMap::new(),
+            calls: CallTracker::new(),
+        })
+    }
+
+    fn thread_of(&mut self, ecu_id: &str, thread_name: String) -> (usize, usize) {
+        let next_pid = self.processes.len() + 1;
+        let pid = *self.processes.entry(ecu_id.to_string()).or_insert(next_pid);
+        let next_tid = self.threads.len() + 1;
+        let tid = *self.threads.entry((pid, thread_name)).or_insert(next_tid);
+        (pid, tid)
+    }
+
+    pub fn add(&mut self, msg: &Message) {
+        let Some(header) = &msg.extended_header else {
+            return;
+        };
+        let time = msg.time();
+        let (pid, tid) = self.thread_of(msg.storage_header.ecu_id(), format!("{}/{}", header.app_id(), header.context_id()));
+
+        if let Some(call) = self.calls.add(msg) {
+            self.events.push(TraceEvent::complete(call.name, call.start, call.end, pid, tid));
+        }
+
+        let strings : Vec<_> = msg.payload.iter().filter_map(|value| match value.value() {
+            Value::String(string) => Some(*string),
+            _ => None,
+        }).collect();
+        for (idx, span) in self.spans.iter().enumerate() {
+            let is_app_id_matching = span.app_id.as_ref().is_none_or(|id| id == header.app_id());
+            let is_context_id_matching = span.context_id.as_ref().is_none_or(|id| id == header.context_id());
+            if !is_app_id_matching || !is_context_id_matching {
+                continue;
+            }
+
+            if strings.iter().any(|string| span.stop.is_match(string)) {
+                if let Some(start) = self.open_spans.remove(&(idx, tid)) {
+                    self.events.push(TraceEvent::complete(span.name.clone(), start, time, pid, tid));
+                }
+            } else if strings.iter().any(|string| span.start.is_match(string)) {
+                self.open_spans.insert((idx, tid), time);
+            }
+        }
+    }
+
+    pub fn finish(mut self) -> TraceFile {
+        for (ecu_id, pid) in &self.processes {
+            self.events.push(TraceEvent::metadata("process_name", ecu_id, *pid, 0));
+        }
+        for ((pid, thread_name), tid) in &self.threads {
+            self.events.push(TraceEvent::metadata("thread_name", thread_name, *pid, *tid));
+        }
+        TraceFile { trace_events: self.events, display_time_unit: "ms" }
+    }
+}
@@ -0,0 +1,59 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+use serde_derive::Serialize;
+
+#[derive(Serialize, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum WarningKind {
+    /// corrupt data was skipped up to the next message
+    Resync,
+    /// the payload of the message could not be decoded completely
+    MalformedPayload,
+}
+
+/// Problem in a trace that does not stop processing it.
+#[derive(Serialize, Debug, Clone)]
+pub struct Warning {
+    /// byte offset in the input file
+    pub offset: usize,
+    pub kind: WarningKind,
+    pub message: String,
+}
+
+#[derive(Serialize)]
+struct Record<'w> {
+    file: &'w Path,
+    #[serde(flatten)]
+    warning: &'w Warning,
+}
+
+static WARNINGS_FILE: OnceLock<Mutex<BufWriter<File>>> = OnceLock::new();
+
+/// Writes the warnings to the given file instead of stderr.
+pub fn set_warnings_file(file_path: &Path) -> io::Result<()> {
+    let file = File::create(file_path)?;
+    let _ = WARNINGS_FILE.set(Mutex::new(BufWriter::new(file)));
+    Ok(())
+}
+
+/// Writes the warnings of the input file as JSON lines, separate from the data output.
+pub fn report(file: &Path, warnings: &[Warning]) {
+    let result = match WARNINGS_FILE.get() {
+        Some(warnings_file) => write_records(&mut *warnings_file.lock().unwrap(), file, warnings),
+        None => write_records(&mut io::stderr().lock(), file, warnings),
+    };
+    if let Err(err) = result {
+        eprintln!("error on writing warnings: {err}");
+    }
+}
+
+/// Writes the warnings of the input file as JSON lines to the output.
+pub fn write_records(out: &mut dyn Write, file: &Path, warnings: &[Warning]) -> io::Result<()> {
+    for warning in warnings {
+        serde_json::to_writer(&mut *out, &Record { file, warning })?;
+        writeln!(out)?;
+    }
+    out.flush()
+}
@@ -0,0 +1,158 @@
+use std::cell::RefCell;
+use std::fmt::{Debug, Formatter};
+use std::fs;
+use std::path::Path;
+use serde_json::{json, Value as JsonValue};
+use wasmi::{Config, Engine, Linker, Memory, Module, Store, TypedFunc};
+use crate::dlt::Message;
+
+/// Instructions a call of the module may execute before it is aborted, so a module stuck in a
+/// loop cannot stall the run.
+const FUEL_PER_CALL: u64 = 10_000_000;
+
+/// User supplied WebAssembly module deciding which messages pass the filter and extracting values
+/// from them, run sandboxed without access to the host.
+///
+/// The module exports its `memory` and `alloc(len: i32) -> i32`, which returns the address of
+/// `len` bytes the message is written to as JSON object (`ecu`, `app`, `ctx`, `type`, `level`,
+/// `counter`, `session`, `timestamp`, `time`, `args` and `payload`), and at least one of:
+/// * `filter(ptr: i32, len: i32) -> i32`: the message passes if the result is not 0
+/// * `transform(ptr: i32, len: i32) -> i64`: returns the address in the upper and the length in
+///   the lower 32 bits of a JSON object, whose values are output as captures of their key; 0
+///   for none
+pub struct WasmModule {
+    runtime: RefCell<Runtime>,
+    has_filter: bool,
+    has_transform: bool,
+}
+
+struct Runtime {
+    store: Store<()>,
+    memory: Memory,
+    alloc: TypedFunc<i32, i32>,
+    filter: Option<TypedFunc<(i32, i32), i32>>,
+    transform: Option<TypedFunc<(i32, i32), i64>>,
+}
+
+impl Debug for WasmModule {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "WasmModule {{ filter: {}, transform: {} }}", self.has_filter, self.has_transform)
+    }
+}
+
+impl WasmModule {
+    pub fn load(module_path: &Path) -> Result<WasmModule, String> {
+        let error = |err: &dyn std::fmt::Display| format!("error on loading WASM module {module_path:?}: {err}");
+        let wasm = fs::read(module_path).map_err(|err| error(&err))?;
+
+        let mut config = Config::default();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config);
+        let module = Module::new(&engine, &wasm[..]).map_err(|err| error(&err))?;
+        let mut store = Store::new(&engine, ());
+        // no host functions are linked, the module has no way to reach the outside
+        let linker = Linker::<()>::new(&engine);
+        store.set_fuel(FUEL_PER_CALL).map_err(|err| error(&err))?;
+        let instance = linker.instantiate(&mut store, &module)
+            .and_then(|instance| instance.start(&mut store))
+            .map_err(|err| error(&err))?;
+
+        let memory = instance.get_memory(&store, "memory").ok_or_else(|| error(&"no exported memory"))?;
+        let alloc = instance.get_typed_func::<i32, i32>(&store, "alloc").map_err(|err| error(&err))?;
+        let filter = instance.get_typed_func::<(i32, i32), i32>(&store, "filter").ok();
+        let transform = instance.get_typed_func::<(i32, i32), i64>(&store, "transform").ok();
+        if filter.is_none() && transform.is_none() {
+            return Err(error(&"exports neither filter nor transform"));
+        }
+        Ok(WasmModule {
+            has_filter: filter.is_some(),
+            has_transform: transform.is_some(),
+            runtime: RefCell::new(Runtime { store, memory, alloc, filter, transform }),
+        })
+    }
+
+    /// Whether the message passes the `filter` of the module, every message passes if it has
+    /// none. Messages the module fails on do not pass.
+    pub fn filter(&self, msg: &Message) -> bool {
+        if !self.has_filter {
+            return true;
+        }
+        let result = self.runtime.borrow_mut().call_filter(&message_json(msg));
+        result.unwrap_or_else(|err| {
+            eprintln!("error in WASM filter: {err}");
+            false
+        })
+    }
+
+    /// Values the `transform` of the module extracts from the message, by name.
+    pub fn transform(&self, msg: &Message) -> Vec<(String, String)> {
+        if !self.has_transform {
+            return vec![];
+        }
+        let result = self.runtime.borrow_mut().call_transform(&message_json(msg));
+        result.unwrap_or_else(|err| {
+            eprintln!("error in WASM transform: {err}");
+            vec![]
+        })
+    }
+}
+
+impl Runtime {
+    /// Copies the message into the memory of the module, returns its address and length.
+    fn write_message(&mut self, message: &str) -> Result<(i32, i32), String> {
+        self.store.set_fuel(FUEL_PER_CALL).map_err(|err| err.to_string())?;
+        let len = i32::try_from(message.len()).map_err(|err| err.to_string())?;
+        let ptr = self.alloc.call(&mut self.store, len).map_err(|err| err.to_string())?;
+        self.memory.write(&mut self.store, ptr as u32 as usize, message.as_bytes()).map_err(|err| err.to_string())?;
+        Ok((ptr, len))
+    }
+
+    fn call_filter(&mut self, message: &str) -> Result<bool, String> {
+        let Some(filter) = self.filter else { return Ok(true) };
+        let (ptr, len) = self.write_message(message)?;
+        let result = filter.call(&mut self.store, (ptr, len)).map_err(|err| err.to_string())?;
+        Ok(result != 0)
+    }
+
+    fn call_transform(&mut self, message: &str) -> Result<Vec<(String, String)>, String> {
+        let Some(transform) = self.transform else { return Ok(vec![]) };
+        let (ptr, len) = self.write_message(message)?;
+        let result = transform.call(&mut self.store, (ptr, len)).map_err(|err| err.to_string())? as u64;
+        if result == 0 {
+            return Ok(vec![]);
+        }
+
+        let mut output = vec![0; (result & 0xFFFF_FFFF) as usize];
+        self.memory.read(&self.store, (result >> 32) as usize, &mut output).map_err(|err| err.to_string())?;
+        let output: JsonValue = serde_json::from_slice(&output).map_err(|err| format!("invalid transform result: {err}"))?;
+        let JsonValue::Object(values) = output else {
+            return Err("transform result is not a JSON object".to_string());
+        };
+        Ok(values.into_iter()
+            .filter(|(_, value)| !value.is_null())
+            .map(|(name, value)| match value {
+                JsonValue::String(value) => (name, value),
+                value => (name, value.to_string()),
+            })
+            .collect())
+    }
+}
+
+/// The message as JSON object handed to the module.
+fn message_json(msg: &Message) -> String {
+    let header = msg.extended_header.as_ref();
+    let args: Vec<_> = msg.payload.iter().map(|value| value.to_string()).collect();
+    json!({
+        "ecu": msg.ecu_id(),
+        "app": header.map(|header| header.app_id()),
+        "ctx": header.map(|header| header.context_id()),
+        "type": header.map(|header| header.type_name()),
+        "level": msg.log_level().map(|level| level.to_string().to_lowercase()),
+        "counter": msg.standard_header.counter(),
+        "session": msg.session_id(),
+        "timestamp": msg.standard_header.timestamp().map(|timestamp| timestamp as f64 / 10_000.0),
+        "time": msg.storage_header.rfc3339(),
+        "args": args,
+        "payload": msg.payload_text(),
+    }).to_string()
+}
@@ -0,0 +1,88 @@
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::time::Duration;
+use notify::{Config, Event, EventKind, PollWatcher, RecommendedWatcher, RecursiveMode, Watcher};
+
+/// Interval of the polling fallback, and the longest a change goes unnoticed if events get lost.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Waits for changes of a few files, e.g. the trace followed and the config, with the native
+/// mechanism of the OS (inotify, FSEvents, ReadDirectoryChangesW) or by polling, which also works
+/// on network shares where the native one sees no changes. The directories of the files are
+/// watched, not the files themselves, so a file replaced by rotation is still seen.
+pub struct FileWatcher {
+    // only kept alive, it sends the events to `events`
+    _watcher: Box<dyn Watcher>,
+    events: Receiver<notify::Result<Event>>,
+    paths: Vec<PathBuf>,
+}
+
+impl FileWatcher {
+    pub fn new(paths: &[&Path], poll: bool) -> notify::Result<FileWatcher> {
+        let (sender, events) = mpsc::channel();
+        let mut watcher: Box<dyn Watcher> = if poll {
+            Box::new(PollWatcher::new(sender, Config::default().with_poll_interval(POLL_INTERVAL))?)
+        } else {
+            Box::new(RecommendedWatcher::new(sender, Config::default())?)
+        };
+        let paths: Vec<PathBuf> = paths.iter().map(|path| path.canonicalize()).collect::<Result<_, _>>()?;
+        let mut directories: Vec<&Path> = paths.iter().filter_map(|path| path.parent()).collect();
+        directories.sort();
+        directories.dedup();
+        for directory in directories {
+            watcher.watch(directory, RecursiveMode::NonRecursive)?;
+        }
+        Ok(FileWatcher { _watcher: watcher, events, paths })
+    }
+
+    /// Waits up to `timeout` for changes and returns the watched files that changed, empty if
+    /// none did. Errors of the watcher are reported and otherwise ignored, the caller looks at
+    /// the files again after the timeout anyway.
+    pub fn wait(&self, timeout: Duration) -> Vec<&Path> {
+        let mut changed = vec![];
+        let mut next = self.events.recv_timeout(timeout).ok();
+        while let Some(event) = next {
+            match event {
+                Ok(event) if !matches!(event.kind, EventKind::Access(_)) => {
+                    for path in &self.paths {
+                        if event.paths.contains(path) && !changed.contains(&path.as_path()) {
+                            changed.push(path.as_path());
+                        }
+                    }
+                },
+                Ok(_) => {},
+                Err(err) => eprintln!("error on watching files: {err}"),
+            }
+            next = self.events.try_recv().ok();
+        }
+        changed
+    }
+}
+
+/// Whether the file opened is not the one at the path anymore, as after a rotation, or was
+/// truncated below the position read up to.
+pub fn is_replaced(file: &File, path: &Path, position: u64) -> bool {
+    let Ok(current) = fs::metadata(path) else {
+        // moved away and not yet created again, keep reading the old one
+        return false;
+    };
+    if current.len() < position {
+        return true;
+    }
+    is_other_file(file, &current)
+}
+
+#[cfg(unix)]
+fn is_other_file(file: &File, current: &fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    file.metadata().is_ok_and(|opened| (opened.dev(), opened.ino()) != (current.dev(), current.ino()))
+}
+
+#[cfg(not(unix))]
+fn is_other_file(file: &File, current: &fs::Metadata) -> bool {
+    // without inode numbers a new file shows by a creation time other than the one opened
+    file.metadata().and_then(|opened| opened.created()).ok()
+        .zip(current.created().ok())
+        .is_some_and(|(opened, current)| opened != current)
+}
@@ -1,69 +1,300 @@
-use std::fmt::Error;
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 use std::process;
-use regex::{Regex};
-use crate::cli::Cli;
+use std::time::Duration;
+use crate::cli::{Cli, Command, ConfigCommand};
 use clap::Parser;
-use crate::config::Filter;
+use regex::Regex;
+use crate::config::{Annotation, Config, Filter, LineEnding, NonVerbose, ShardKey};
+pub use crate::config::FilterSpec;
+use crate::dlt::can::Dbc;
 use crate::dlt::filter::{FilterId, FilterType, Pattern};
+use crate::dlt::query::Query;
+use crate::dlt::reference::ReferenceEvents;
+use crate::dlt::wasm::WasmModule;
+use crate::manifest::Manifest;
+use crate::provenance::Provenance;
 
 pub mod dlt;
 pub mod config;
 pub mod cli;
+pub mod provenance;
+pub mod manifest;
 
 #[derive(Debug)]
 pub enum OutputField {
     Ecu,
     App,
     Ctx,
+    /// storage time as ISO 8601 timestamp in UTC
     Time,
     Timestamp,
+    /// session id of the standard header, usually the process id of the logging application
+    Session,
+    Type,
+    /// log level of log messages, e.g. `Info`
+    Level,
     Payload,
+    /// size of the payload in bytes
+    PayloadLen,
+    /// number of arguments of the extended header
+    Noar,
+    /// name of the reference event closest to the storage time
+    RefEvent,
+    /// seconds from the closest reference event to the storage time
+    RefDelta,
+    /// position of the message in the input, counting from 0
+    Index,
+    /// boot of the ECU the message was logged in, counting from 1
+    Lifecycle,
+    /// description of the application from the GetLogInfo responses in the trace
+    AppDescription,
+    /// description of the context from the GetLogInfo responses in the trace
+    ContextDescription,
     Capture(String),
+    /// literal value of a column declared in the output, the same in every row
+    Static(String, String),
+    /// value the manifest lists for the input file under the key
+    Metadata(String),
 }
 
 impl OutputField {
-    fn from(input: &str) -> Option<OutputField> {
-        println!("transform {input}");
+    fn builtin(input: &str) -> Option<OutputField> {
         match input {
             "ecu" => Some(OutputField::Ecu),
             "app" => Some(OutputField::App),
             "ctx" => Some(OutputField::Ctx),
             "time" => Some(OutputField::Time),
             "timestamp" => Some(OutputField::Timestamp),
+            "session" => Some(OutputField::Session),
+            "type" => Some(OutputField::Type),
+            "level" => Some(OutputField::Level),
             "payload" => Some(OutputField::Payload),
+            "payload_len" => Some(OutputField::PayloadLen),
+            "noar" => Some(OutputField::Noar),
+            "ref_event" => Some(OutputField::RefEvent),
+            "ref_delta" => Some(OutputField::RefDelta),
+            "index" => Some(OutputField::Index),
+            "lifecycle" => Some(OutputField::Lifecycle),
+            "app_desc" => Some(OutputField::AppDescription),
+            "ctx_desc" => Some(OutputField::ContextDescription),
             x if x.starts_with('<') && x.ends_with('>') => {
                 Some(OutputField::Capture(x[1..x.len()-1].to_string()))
             },
-            _ => {
-                eprintln!("invalid field name: {input}");
-                None
-            },
+            _ => None,
+        }
+    }
+
+    /// Name of the field as in the format, captures without the angle brackets.
+    pub fn name(&self) -> &str {
+        match self {
+            OutputField::Ecu => "ecu",
+            OutputField::App => "app",
+            OutputField::Ctx => "ctx",
+            OutputField::Time => "time",
+            OutputField::Timestamp => "timestamp",
+            OutputField::Session => "session",
+            OutputField::Type => "type",
+            OutputField::Level => "level",
+            OutputField::Payload => "payload",
+            OutputField::PayloadLen => "payload_len",
+            OutputField::Noar => "noar",
+            OutputField::RefEvent => "ref_event",
+            OutputField::RefDelta => "ref_delta",
+            OutputField::Index => "index",
+            OutputField::Lifecycle => "lifecycle",
+            OutputField::AppDescription => "app_desc",
+            OutputField::ContextDescription => "ctx_desc",
+            OutputField::Capture(name) => name,
+            OutputField::Static(name, _) => name,
+            OutputField::Metadata(name) => name,
+        }
+    }
+}
+
+/// Line format with named placeholders for the fields, e.g. `{time} [{app}.{ctx}] {level}:
+/// {payload}` or `{<speed>}` for a capture. Literal braces are written as `{{` and `}}`.
+#[derive(Debug)]
+pub struct Template {
+    /// text before each placeholder and after the last one
+    literals: Vec<String>,
+}
+
+impl Template {
+    /// Whether the format is a template rather than fields separated by the delimiter.
+    pub fn is_template(format: &str) -> bool {
+        format.contains('{')
+    }
+
+    /// Parses the template, returns it with the names of its placeholders in order.
+    pub fn parse(template: &str) -> Result<(Template, Vec<String>), String> {
+        let mut literals = vec![];
+        let mut names = vec![];
+        let mut literal = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                },
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                },
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some('{') | None => return Err(format!("unclosed placeholder in template '{template}'")),
+                            Some(c) => name.push(c),
+                        }
+                    }
+                    if name.trim().is_empty() {
+                        return Err(format!("empty placeholder in template '{template}'"));
+                    }
+                    literals.push(std::mem::take(&mut literal));
+                    names.push(name.trim().to_string());
+                },
+                '}' => return Err(format!("unmatched '}}' in template '{template}'")),
+                _ => literal.push(c),
+            }
         }
+        literals.push(literal);
+        Ok((Template { literals }, names))
     }
+
+    /// Fills the values of the placeholders, in their order, into the template.
+    pub fn render(&self, values: impl IntoIterator<Item = String>) -> String {
+        let mut line = String::new();
+        let mut literals = self.literals.iter();
+        line.push_str(literals.next().map_or("", String::as_str));
+        for (value, literal) in values.into_iter().zip(literals) {
+            line.push_str(&value);
+            line.push_str(literal);
+        }
+        line
+    }
+}
+
+/// Written for values a message lacks, unless the output sets another placeholder.
+pub const DEFAULT_MISSING: &str = "none";
+
+const DEFAULT_CSV_FIELDS: [&str; 5] = ["timestamp", "ecu", "app", "ctx", "payload"];
+/// of the outputs of tables, sqlite and parquet, followed by the captures of the filter
+const DEFAULT_TABLE_FIELDS: [&str; 9] = ["index", "lifecycle", "time", "timestamp", "ecu", "app", "ctx", "type", "payload"];
+
+fn default_fields() -> Vec<OutputField> {
+    DEFAULT_CSV_FIELDS.iter().filter_map(|field| OutputField::builtin(field)).collect()
 }
 
 #[derive(Debug)]
 pub enum OutputType {
     Csv(Csv),
     Stdout(Stdout),
+    Dlt(Dlt),
+    Arrow(Arrow),
+    Sqlite(Sqlite),
+    Parquet(Parquet),
 }
 
 #[derive(Debug)]
 pub struct Csv {
     delimiter: char,
     file_path: PathBuf,
+    shard_by: Option<ShardKey>,
+    line_ending: LineEnding,
+    /// rows per file, if the output is split into chunks
+    chunk_rows: Option<usize>,
+    /// whether each file starts with a row of the column names
+    header: bool,
+}
+
+impl Csv {
+    pub fn shard_by(&self) -> Option<ShardKey> {
+        self.shard_by
+    }
+
+    pub fn line_ending(&self) -> &'static str {
+        self.line_ending.as_str()
+    }
+
+    pub fn header(&self) -> bool {
+        self.header
+    }
+
+    /// Returns the file the row should be written to, substituting the shard value into the
+    /// path template if the output is sharded.
+    pub fn file_path(&self, shard_value: &str) -> PathBuf {
+        match self.shard_by {
+            Some(shard_by) => {
+                let shard_value : String = shard_value.chars()
+                    .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+                    .collect();
+                let template = self.file_path.to_string_lossy();
+                PathBuf::from(template.replace(shard_by.placeholder(), &shard_value))
+            },
+            None => self.file_path.clone(),
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct Stdout {
     delimiter: char,
+    /// written before and after each formatted line
+    prefix: String,
+    suffix: String,
+}
+
+#[derive(Debug)]
+pub struct Dlt {
+    file_path: PathBuf,
+    annotation: Option<Annotation>,
+    non_verbose: Option<NonVerbose>,
+    /// captures re-encoded as signals of the non-verbose message
+    capture_names: Vec<String>,
+    /// messages held back to write them in storage time order
+    reorder_window: usize,
+}
+
+#[derive(Debug)]
+pub struct Arrow {
+    /// stdout if not given
+    pub file_path: Option<PathBuf>,
+}
+
+#[derive(Debug)]
+pub struct Sqlite {
+    pub file_path: PathBuf,
+    pub table: String,
 }
 
+#[derive(Debug)]
+pub struct Parquet {
+    pub file_path: PathBuf,
+}
+
+/// Fields of a format, its template if it is one and the names in it that are no field.
+type FormatFields = (Vec<OutputField>, Option<Template>, Vec<String>);
+
 #[derive(Debug)]
 pub struct Output {
     out_type: OutputType,
     fields: Vec<OutputField>,
+    /// write a marker line before a message if messages of its context were lost
+    lost_markers: bool,
+    /// events the messages are annotated with by the `ref_event` and `ref_delta` fields
+    references: Option<ReferenceEvents>,
+    /// placeholder for values a message lacks
+    missing: String,
+    /// units of the captures known from the FIBEX description
+    units: BTreeMap<String, String>,
+    /// lines of stdout and csv are filled into it instead of joining the fields
+    template: Option<Template>,
+    /// names in the format that are no field, they are left out
+    unknown_fields: Vec<String>,
 }
 
 impl Output {
@@ -75,18 +306,109 @@ impl Output {
         &self.fields
     }
 
-    fn validate_captures(filter : &Filter, fields: &Vec<OutputField>) -> Result<(), String> {
-        let field_verifier = fields.iter().filter(|field| match field {
-            OutputField::Capture(_) => true,
-            _ => false,
-        });
-        let capture_names = filter.patterns().as_ref().map_or_else(|| None, |patterns| Pattern::capture_names(patterns));
+    pub fn unknown_fields(&self) -> &Vec<String> {
+        &self.unknown_fields
+    }
+
+    pub fn lost_markers(&self) -> bool {
+        self.lost_markers
+    }
+
+    pub fn missing(&self) -> &str {
+        &self.missing
+    }
+
+    pub fn units(&self) -> &BTreeMap<String, String> {
+        &self.units
+    }
+
+    pub fn template(&self) -> &Option<Template> {
+        &self.template
+    }
+
+    /// Names of the columns, with the unit of captures whose unit is known, e.g. `speed [km/h]`.
+    /// The unit comes from the FIBEX description or from the `variables`, the names and units
+    /// of the arguments a message sent with variable info, see [`dlt::Message::variables`].
+    pub fn column_names(&self, variables: &[(&str, Option<&str>)]) -> Vec<String> {
+        self.fields.iter().map(|field| {
+            let unit = match field {
+                OutputField::Capture(name) => self.units.get(name).map(String::as_str)
+                    .or_else(|| variables.iter().find(|(variable, _)| variable == name).and_then(|(_, unit)| *unit)),
+                _ => None,
+            };
+            match unit {
+                Some(unit) => format!("{} [{unit}]", field.name()),
+                None => field.name().to_string(),
+            }
+        }).collect()
+    }
+
+    /// Loads the reference events of the output, required if the fields refer to them.
+    fn references(filter: &Filter, output: &config::Output, fields: &[OutputField]) -> Result<Option<ReferenceEvents>, String> {
+        let Some(file_path) = output.reference_events().as_ref() else {
+            if fields.iter().any(|field| matches!(field, OutputField::RefEvent | OutputField::RefDelta)) {
+                return Err(format!("fields ref_event and ref_delta require reference_events in the output of filter '{}'", filter.name()));
+            }
+            return Ok(None);
+        };
+        ReferenceEvents::load(file_path).map(Some)
+    }
+
+    /// Fields of the format, the static columns of the output and then the metadata of the
+    /// manifest not named in it come first. A template names all the fields it writes, it is
+    /// returned along, followed by the names of a plain format that are no field and are left
+    /// out.
+    fn parse_fields(filter: &Filter, signal_names: &[String], format: &str, delimiter: char, columns: &BTreeMap<String, String>, metadata_keys: &[String]) -> Result<FormatFields, String> {
+        if let Some(name) = columns.keys().chain(metadata_keys).find(|name| OutputField::builtin(name).is_some()) {
+            return Err(format!("column '{name}' of filter '{}' has the name of a field", filter.name()));
+        }
+        if let Some(name) = metadata_keys.iter().find(|key| columns.contains_key(*key)) {
+            return Err(format!("column '{name}' of filter '{}' is also a key of the manifest", filter.name()));
+        }
+        let (template, inputs) = if Template::is_template(format) {
+            let (template, names) = Template::parse(format).map_err(|err| format!("{err} of filter '{}'", filter.name()))?;
+            (Some(template), names)
+        } else {
+            (None, format.split(delimiter).map(str::to_string).collect())
+        };
+        let mut format_fields = vec![];
+        let mut unknown_fields = vec![];
+        for input in &inputs {
+            let field = match columns.get(input) {
+                Some(value) => OutputField::Static(input.to_string(), value.clone()),
+                None if metadata_keys.contains(input) => OutputField::Metadata(input.to_string()),
+                None => match OutputField::builtin(input) {
+                    Some(field) => field,
+                    None if template.is_some() => return Err(format!("invalid placeholder '{input}' in the template of filter '{}'", filter.name())),
+                    None => {
+                        unknown_fields.push(input.clone());
+                        continue;
+                    },
+                },
+            };
+            format_fields.push(field);
+        }
+        let is_named = |name: &str| template.is_some() || format_fields.iter().any(|field| field.name() == name);
+        let mut fields: Vec<_> = columns.iter()
+            .filter(|(name, _)| !is_named(name))
+            .map(|(name, value)| OutputField::Static(name.clone(), value.clone()))
+            .chain(metadata_keys.iter().filter(|key| !is_named(key)).map(|key| OutputField::Metadata(key.clone())))
+            .collect();
+        fields.extend(format_fields);
+
+        Output::validate_captures(filter, signal_names, &fields)?;
+        Ok((fields, template, unknown_fields))
+    }
+
+    fn validate_captures(filter : &Filter, signal_names: &[String], fields: &[OutputField]) -> Result<(), String> {
+        let field_verifier = fields.iter().filter(|field| matches!(field, OutputField::Capture(_)));
+        let capture_names = Output::capture_names(filter, signal_names);
         // validate output fields for captures
         for field in field_verifier {
             match field {
                 OutputField::Capture(name) => {
                     if let Some(capture_names) = &capture_names {
-                        if capture_names.iter().find(|capture_name| *capture_name == name) == None {
+                        if !capture_names.iter().any(|capture_name| capture_name == name) {
                             return Err::<(),String>(format!("no capture defined for stdout field '{name}' in filter '{}'", filter.name()));
                         }
                     } else {
@@ -99,100 +421,632 @@ impl Output {
         Ok(())
     }
 
-    pub fn from_filter(filter: &Filter) -> Option<Output> {
-        match filter.output() {
-            Some(output) => {
-                if let Some(stdout) = output.stdout() {
-                    if stdout.is_enabled() {
-                        let fields : Vec<_>= stdout.format_string().split(stdout.delimiter()).collect();
-                        let fields : Vec<_> = fields.iter().filter_map(|field_name| OutputField::from(field_name)).collect();
-
-                        match Output::validate_captures(filter, &fields) {
-                            Ok(_) => Some(Output {
-                                out_type: OutputType::Stdout(Stdout { delimiter: stdout.delimiter() }),
-                                fields: fields,
-                            }),
-                            Err(err) => {
-                                eprintln!("{err}");
-                                process::exit(1);
-                            }
-                        }
-                    } else {
-                        None
+    /// Names of the captures of the patterns and of the signals of the non-verbose messages.
+    fn capture_names(filter: &Filter, signal_names: &[String]) -> Option<Vec<String>> {
+        let mut names = filter.patterns().as_ref().and_then(Pattern::capture_names).unwrap_or_default();
+        for name in signal_names {
+            if !names.contains(name) {
+                names.push(name.clone());
+            }
+        }
+        (!names.is_empty()).then_some(names)
+    }
+
+    /// Default format of the outputs of tables, the header fields, the payload and the captures.
+    fn table_format(filter: &Filter, signal_names: &[String]) -> String {
+        let mut format = DEFAULT_TABLE_FIELDS.join(",");
+        for name in Output::capture_names(filter, signal_names).unwrap_or_default() {
+            format.push_str(&format!(",<{name}>"));
+        }
+        format
+    }
+
+    /// Outputs of the filter, one per sink it configures, e.g. brief lines on stdout while all
+    /// fields are written to a CSV file. A filter without sinks writes its matches to stdout,
+    /// see [`Output::default_stdout`]. `signal_names` are the names of the non-verbose
+    /// arguments it extracts, `metadata_keys` the keys of the manifest of the inputs.
+    pub fn from_filter(filter: &Filter, signal_names: &[String], metadata_keys: &[String]) -> Result<Vec<Output>, String> {
+        let mut outputs = match filter.output() {
+            Some(output) => Output::sinks(filter, output, signal_names, metadata_keys)?,
+            None => vec![],
+        };
+        if outputs.is_empty() {
+            let capture_names = Output::capture_names(filter, signal_names).unwrap_or_default();
+            outputs.push(Output::default_stdout(&capture_names, metadata_keys));
+        }
+        Ok(outputs)
+    }
+
+    /// Output of a filter without sinks: the metadata of the manifest, the default fields and
+    /// the captures, separated by spaces on stdout.
+    pub fn default_stdout(capture_names: &[String], metadata_keys: &[String]) -> Output {
+        let fields = metadata_keys.iter().map(|key| OutputField::Metadata(key.clone()))
+            .chain(default_fields())
+            .chain(capture_names.iter().map(|name| OutputField::Capture(name.clone())))
+            .collect();
+        Output {
+            out_type: OutputType::Stdout(Stdout {
+                delimiter: ' ',
+                prefix: String::new(),
+                suffix: String::new(),
+            }),
+            fields,
+            lost_markers: false,
+            references: None,
+            missing: DEFAULT_MISSING.to_string(),
+            units: BTreeMap::new(),
+            template: None,
+            unknown_fields: vec![],
+        }
+    }
+
+    fn sinks(filter: &Filter, output: &config::Output, signal_names: &[String], metadata_keys: &[String]) -> Result<Vec<Output>, String> {
+        let mut outputs = vec![];
+        let missing = output.missing().clone().unwrap_or(DEFAULT_MISSING.to_string());
+        let columns = output.columns().clone().unwrap_or_default();
+
+        if let Some(stdout) = output.stdout().as_ref().filter(|stdout| stdout.is_enabled()) {
+            let (fields, template, unknown_fields) = Output::parse_fields(filter, signal_names, stdout.format_string(), stdout.delimiter(), &columns, metadata_keys)?;
+            let references = Output::references(filter, output, &fields)?;
+            outputs.push(Output {
+                out_type: OutputType::Stdout(Stdout {
+                    delimiter: stdout.delimiter(),
+                    prefix: stdout.prefix().clone().unwrap_or_default(),
+                    suffix: stdout.suffix().clone().unwrap_or_default(),
+                }),
+                fields,
+                lost_markers: output.lost_markers(),
+                references,
+                missing: missing.clone(),
+                units: BTreeMap::new(),
+                template,
+                unknown_fields,
+            });
+        }
+
+        if let Some(csv) = output.csv() {
+            let default_format = DEFAULT_CSV_FIELDS.join(&csv.delimiter().to_string());
+            let format = csv.format_string().as_deref().unwrap_or(&default_format);
+            let (fields, template, unknown_fields) = Output::parse_fields(filter, signal_names, format, csv.delimiter(), &columns, metadata_keys)?;
+            let references = Output::references(filter, output, &fields)?;
+            outputs.push(Output {
+                out_type: OutputType::Csv(Csv {
+                    delimiter: csv.delimiter(),
+                    file_path: csv.file_path().clone(),
+                    shard_by: csv.shard_by(),
+                    line_ending: csv.line_ending(),
+                    chunk_rows: csv.chunk_rows(),
+                    header: csv.header(),
+                }),
+                fields,
+                lost_markers: output.lost_markers(),
+                references,
+                missing: missing.clone(),
+                units: BTreeMap::new(),
+                template,
+                unknown_fields,
+            });
+        }
+
+        if let Some(arrow) = output.arrow() {
+            let default_format = DEFAULT_CSV_FIELDS.join(",");
+            let format = arrow.format_string().as_deref().unwrap_or(&default_format);
+            let (fields, template, unknown_fields) = Output::parse_fields(filter, signal_names, format, ',', &columns, metadata_keys)?;
+            let references = Output::references(filter, output, &fields)?;
+            outputs.push(Output {
+                out_type: OutputType::Arrow(Arrow {
+                    file_path: arrow.file_path().clone(),
+                }),
+                fields,
+                lost_markers: false,
+                references,
+                missing: missing.clone(),
+                units: BTreeMap::new(),
+                template,
+                unknown_fields,
+            });
+        }
+
+        if let Some(sqlite) = output.sqlite() {
+            let default_format = Output::table_format(filter, signal_names);
+            let format = sqlite.format_string().as_deref().unwrap_or(&default_format);
+            let (fields, template, unknown_fields) = Output::parse_fields(filter, signal_names, format, ',', &columns, metadata_keys)?;
+            let references = Output::references(filter, output, &fields)?;
+            outputs.push(Output {
+                out_type: OutputType::Sqlite(Sqlite {
+                    file_path: sqlite.file_path().clone(),
+                    table: sqlite.table().clone().unwrap_or_else(|| filter.name().clone()),
+                }),
+                fields,
+                lost_markers: false,
+                references,
+                missing: missing.clone(),
+                units: BTreeMap::new(),
+                template,
+                unknown_fields,
+            });
+        }
+
+        if let Some(parquet) = output.parquet() {
+            let default_format = Output::table_format(filter, signal_names);
+            let format = parquet.format_string().as_deref().unwrap_or(&default_format);
+            let (fields, template, unknown_fields) = Output::parse_fields(filter, signal_names, format, ',', &columns, metadata_keys)?;
+            let references = Output::references(filter, output, &fields)?;
+            outputs.push(Output {
+                out_type: OutputType::Parquet(Parquet {
+                    file_path: parquet.file_path().clone(),
+                }),
+                fields,
+                lost_markers: false,
+                references,
+                missing: missing.clone(),
+                units: BTreeMap::new(),
+                template,
+                unknown_fields,
+            });
+        }
+
+        // the messages are exported as they are or as non-verbose messages, there are no fields
+        // to format
+        if let Some(dlt) = output.dlt() {
+            let mut capture_names = vec![];
+            if dlt.non_verbose().is_some() {
+                for name in Output::capture_names(filter, signal_names).unwrap_or_default() {
+                    if !capture_names.contains(&name) {
+                        capture_names.push(name);
                     }
-                } else {
-                    None
+                }
+                if capture_names.is_empty() {
+                    return Err(format!("non-verbose dlt output of filter '{}' requires patterns with captures or described non-verbose messages", filter.name()));
                 }
             }
-            _ => None,
+            outputs.push(Output {
+                out_type: OutputType::Dlt(Dlt {
+                    file_path: dlt.file_path().clone(),
+                    annotation: dlt.annotation().clone(),
+                    non_verbose: dlt.non_verbose().clone(),
+                    capture_names,
+                    reorder_window: dlt.reorder_window(),
+                }),
+                fields: vec![],
+                // the exported trace has the same gaps in its counters
+                lost_markers: false,
+                references: None,
+                missing: missing.clone(),
+                units: BTreeMap::new(),
+                template: None,
+                unknown_fields: vec![],
+            });
         }
+        Ok(outputs)
     }
 }
 
-pub fn run() {
-    let args : Cli = Cli::parse();
-    println!("cli {args:?}");
+fn read_config_or_exit(config_path: &Path) -> Config {
+    config::read_config(config_path).unwrap_or_else(|err| {
+        println!("error in reading config: {err}");
+        process::exit(1);
+    })
+}
+
+fn run_config_tests(config: Option<Config>) {
+    let Some(config) = config else {
+        eprintln!("config tests require a config file (--config)");
+        process::exit(1);
+    };
+
+    let mut passed = 0;
+    let mut failed = 0;
+    for cfg_filter in config.filters().iter().flatten() {
+        let (Some(patterns), Some(tests)) = (cfg_filter.patterns(), cfg_filter.tests()) else {
+            continue;
+        };
+        // validated with the config
+        let Ok(pattern) = Pattern::with_case(patterns, cfg_filter.ignore_case()) else {
+            continue;
+        };
+
+        for (idx, test) in tests.iter().enumerate() {
+            match test.run(&pattern) {
+                Ok(_) => {
+                    println!("test '{}' #{idx}: ok", cfg_filter.name());
+                    passed += 1;
+                },
+                Err(err) => {
+                    println!("test '{}' #{idx}: FAILED: {err}", cfg_filter.name());
+                    failed += 1;
+                },
+            }
+        }
+    }
+
+    println!("{passed} passed, {failed} failed");
+    if failed > 0 {
+        process::exit(1);
+    }
+}
+
+fn run_eval(args: &Cli, config: Option<Config>, filter_name: &str, payload: &str) {
+    let (Some(config_path), Some(config)) = (args.config(), config) else {
+        eprintln!("eval requires a config file (--config)");
+        process::exit(1);
+    };
+    let Some(cfg_filter) = config.filters().iter().flatten().find(|cfg_filter| cfg_filter.name() == filter_name) else {
+        let names: Vec<_> = config.filters().iter().flatten().map(|cfg_filter| cfg_filter.name().as_str()).collect();
+        eprintln!("no filter '{filter_name}' in {config_path:?}, filters: {}", names.join(", "));
+        process::exit(1);
+    };
+    // values of the WASM transform and of named arguments may be output, they stay empty here
+    let mut signal_names = cfg_filter.wasm().as_ref().map(|wasm| wasm.fields().clone()).unwrap_or_default();
+    signal_names.extend(cfg_filter.variables().iter().flatten().cloned());
+    let outputs = Output::from_filter(cfg_filter, &signal_names, &[]).unwrap_or_else(|err| {
+        eprintln!("{err}");
+        process::exit(1);
+    });
+    dlt::eval::run_eval(cfg_filter, &outputs, payload);
+}
+
+/// Start and end of a time window, open ended on the side not given.
+fn window(from: Option<Duration>, to: Option<Duration>) -> Option<(Duration, Duration)> {
+    if from.is_none() && to.is_none() {
+        return None;
+    }
+    Some((from.unwrap_or(Duration::ZERO), to.unwrap_or(Duration::MAX)))
+}
+
+/// Builds the filters and the outputs of a filter definition, the way each filter of the config
+/// is run. Lets services embedding the crate run filters they got e.g. as JSON, see
+/// [`FilterSpec::from_json`].
+pub fn build_route(spec: &FilterSpec, metadata_keys: &[String]) -> Result<(dlt::filter::Filter, Vec<Output>), String> {
     let mut filters = dlt::filter::Filter::new();
-    let mut output : Option<Output> = None;
-    if let Some(config_path) = args.config.as_deref() {
-        println!("config file: {config_path:?}");
-        let config = config::read_config(config_path).unwrap_or_else(|err| {
-            println!("error in reading config: {err}");
-            process::exit(1);
-        });
+    filters.set_ecu_source(spec.ecu_source());
+    if let Some(ecu_ids) = spec.ecu_id() {
+        filters.add(FilterId::EcuId, FilterType::EcuId(ecu_ids.to_vec()));
+    }
+    if let Some(app_ids) = spec.app_id() {
+        filters.add(FilterId::AppId, FilterType::AppId(app_ids.to_vec()));
+    }
+    if let Some(context_ids) = spec.context_id() {
+        filters.add(FilterId::ContextId, FilterType::ContextId(context_ids.to_vec()));
+    }
+    let exclusions = [
+        (FilterId::ExcludeEcuId, spec.exclude_ecu_id()),
+        (FilterId::ExcludeAppId, spec.exclude_app_id()),
+        (FilterId::ExcludeContextId, spec.exclude_context_id()),
+    ];
+    for (key, ids) in exclusions {
+        if let Some(ids) = ids {
+            filters.add(key, FilterType::Exclude(ids.to_vec()));
+        }
+    }
+    if let Some(patterns) = spec.exclude_patterns() {
+        let patterns = Pattern::with_case(patterns, spec.ignore_case()).map_err(|err| format!("exclude_patterns: {err}"))?;
+        filters.add(FilterId::ExcludePatterns, FilterType::ExcludePatterns(patterns));
+    }
+    if let Some(patterns) = spec.patterns() {
+        let patterns = Pattern::with_case(patterns, spec.ignore_case()).map_err(|err| format!("patterns: {err}"))?;
+        filters.add(FilterId::Patterns, FilterType::Patterns(patterns));
+    }
+    if let (Some(start), Some(stop)) = (spec.start_pattern(), spec.stop_pattern()) {
+        let case = if spec.ignore_case() { "(?i)" } else { "" };
+        let start = Regex::new(&format!("{case}{start}")).map_err(|err| format!("start_pattern: {err}"))?;
+        let stop = Regex::new(&format!("{case}{stop}")).map_err(|err| format!("stop_pattern: {err}"))?;
+        filters.add(FilterId::Section, FilterType::Section(start, stop));
+    }
+    if let Some(message_ids) = spec.message_ids() {
+        filters.add(FilterId::MessageIds, FilterType::MessageIds(message_ids.clone()));
+    }
+    if let Some(header_only) = spec.header_only() {
+        filters.add(FilterId::HeaderOnly, FilterType::HeaderOnly(header_only));
+    }
+    if spec.buffer_overflow() {
+        filters.add(FilterId::BufferOverflow, FilterType::BufferOverflow);
+    }
+    if let Some(app_trace) = spec.app_trace() {
+        filters.add(FilterId::AppTrace, FilterType::AppTrace(app_trace));
+    }
+    if let Some(level) = spec.level() {
+        filters.add(FilterId::LogLevel, FilterType::LogLevel(level));
+    }
+    // validated with the config
+    let time_from = spec.time_from().as_ref().and_then(|time| time.since_epoch().ok());
+    let time_to = spec.time_to().as_ref().and_then(|time| time.since_epoch().ok());
+    if let Some((from, to)) = window(time_from, time_to) {
+        filters.add(FilterId::Time, FilterType::Time(from, to));
+    }
+    if let Some((from, to)) = window(spec.uptime_from(), spec.uptime_to()) {
+        filters.add(FilterId::Uptime, FilterType::Uptime(from, to));
+    }
+    if let Some(interval) = spec.keep_one_per() {
+        filters.add(FilterId::KeepOnePer, FilterType::KeepOnePer(interval));
+    }
+    if let Some(first_per) = spec.first_per() {
+        filters.add(FilterId::FirstPer, FilterType::FirstPer(first_per));
+    }
+    if spec.dedup() {
+        filters.add(FilterId::Dedup, FilterType::Dedup);
+    }
+    if spec.before().is_some() || spec.after().is_some() {
+        filters.add(FilterId::Context, FilterType::Context(spec.before().unwrap_or(0), spec.after().unwrap_or(0)));
+    }
+
+    let mut signal_names = vec![];
+    let mut units = BTreeMap::new();
+    if let Some(non_verbose) = spec.non_verbose() {
+        let fibex = non_verbose.fibex()?;
+        signal_names = fibex.signal_names(non_verbose.message_ids());
+        units.extend(fibex.signal_units(non_verbose.message_ids()));
+        filters.add(FilterId::NonVerbose, FilterType::NonVerbose(fibex, non_verbose.message_ids().clone()));
+    }
+    if let Some(variables) = spec.variables() {
+        signal_names.extend(variables.iter().cloned());
+        filters.add(FilterId::Variables, FilterType::Variables(variables.clone()));
+    }
+    if let Some(wasm) = spec.wasm() {
+        let module = WasmModule::load(wasm.module_path())?;
+        signal_names.extend(wasm.fields().iter().cloned());
+        filters.add(FilterId::Wasm, FilterType::Wasm(Box::new(module)));
+    }
+
+    let mut outputs = Output::from_filter(spec, &signal_names, metadata_keys)?;
+    for output in &mut outputs {
+        output.units = units.clone();
+    }
+    Ok((filters, outputs))
+}
+
+fn run_filters(args: &Cli, config: Option<Config>) {
+    let manifest = args.manifest().as_deref().map(|manifest_path| Manifest::read(manifest_path).unwrap_or_else(|err| {
+        eprintln!("{err}");
+        process::exit(1);
+    }));
+    let inputs = manifest.as_ref().map_or(args.input(), Manifest::files);
+    let metadata_keys = manifest.as_ref().map_or(&[][..], |manifest| manifest.keys());
+    // each filter of the config is evaluated on its own and written to its own output
+    let mut routes: Vec<(dlt::filter::Filter, Vec<Output>)> = vec![];
+    let mut callstacks = vec![];
+    if let Some(mut config) = config {
+        if let Some(output_root) = args.output_root() {
+            if let Err(err) = config.confine_outputs(output_root) {
+                eprintln!("{err}");
+                process::exit(1);
+            }
+        }
+        if args.strict_ids() {
+            let unconventional = config.unconventional_ids();
+            for id in &unconventional {
+                eprintln!("{id} is not 1 to 4 uppercase letters or digits");
+            }
+            if !unconventional.is_empty() {
+                process::exit(1);
+            }
+        }
+        callstacks = config.callstacks().clone().unwrap_or_default();
         if let Some(cfg_filters) = config.filters() {
             for cfg_filter in cfg_filters {
-                match cfg_filter.ecu_id() {
-                    Some(ecu_id) => {
-                        filters.add(FilterId::EcuId, FilterType::EcuId(ecu_id.to_string()));
-                    },
-                    _ => (),
-                }
-                match cfg_filter.app_id() {
-                    Some(app_id) => {
-                        filters.add(FilterId::AppId, FilterType::AppId(app_id.to_string()));
-                    },
-                    _ => (),
+                let route = build_route(cfg_filter, metadata_keys).unwrap_or_else(|err| {
+                    eprintln!("{err}");
+                    process::exit(1);
+                });
+                for name in route.1.iter().flat_map(Output::unknown_fields) {
+                    eprintln!("invalid field name: {name}");
                 }
-                match cfg_filter.context_id() {
-                    Some(context_id) => {
-                        filters.add(FilterId::ContextId, FilterType::ContextId(context_id.to_string()));
-                    },
-                    _ => (),
-                }
-                let mut capture_names : Option<Vec<String>> = None;
-                match cfg_filter.patterns() {
-                    Some(patterns) => {
-                        let regex = Regex::new("<(?P<name>[a-z]+)>").unwrap();
-                        let mut names: Vec<String> = vec![];
-
-                        for pattern in patterns {
-                            let captures : Vec<_>= regex.captures_iter(pattern).collect();
-                            for capture in captures {
-                                if let Some(name) = capture.name("name") {
-                                    names.push(name.as_str().to_string());
-                                }
-                            }
-                        }
+                routes.push(route);
+            }
+        }
+    }
 
-                        if names.is_empty() {
-                            capture_names = None;
-                        } else {
-                            capture_names = Some(names);
-                        }
+    if args.strict_ids() {
+        let cli_ids = [("--ecu", args.ecu_id()), ("--app", args.app_id()), ("--ctx", args.context_id())];
+        for (name, id) in cli_ids.iter().flat_map(|(name, ids)| ids.iter().map(move |id| (name, id))) {
+            if !config::is_conventional_id(id) {
+                eprintln!("{name} {id:?} is not 1 to 4 uppercase letters or digits");
+                process::exit(1);
+            }
+        }
+    }
+    if routes.is_empty() {
+        routes.push((dlt::filter::Filter::new(), vec![Output::default_stdout(&[], metadata_keys)]));
+    }
+    for (filters, _) in routes.iter_mut() {
+        add_cli_filters(args, filters);
+    }
 
-                        let patterns= Pattern::from(&*patterns);
-                        filters.add(FilterId::Patterns, FilterType::Patterns(patterns));
-                    },
-                    _ => ()
-                }
+    let provenance = args.provenance().then(|| {
+        Provenance::new(args.config().as_deref(), inputs).unwrap_or_else(|err| {
+            eprintln!("{err}");
+            process::exit(1);
+        })
+    });
 
-                output = Output::from_filter(&cfg_filter);
-            }
+    dlt::run_dlt(inputs, &routes, args.index_range(), args.line_buffered(), &callstacks, &provenance, &manifest)
+}
+
+/// Adds the filters given on the command line, they apply to each filter of the config.
+fn add_cli_filters(args: &Cli, filters: &mut dlt::filter::Filter) {
+    filters.set_strict_ids(args.strict_ids());
+    if !args.ecu_id().is_empty() {
+        filters.add(FilterId::EcuId, FilterType::EcuId(args.ecu_id().to_vec()));
+    }
+    if !args.app_id().is_empty() {
+        filters.add(FilterId::AppId, FilterType::AppId(args.app_id().to_vec()));
+    }
+    if !args.context_id().is_empty() {
+        filters.add(FilterId::ContextId, FilterType::ContextId(args.context_id().to_vec()));
+    }
+    if args.buffer_overflow() {
+        filters.add(FilterId::BufferOverflow, FilterType::BufferOverflow);
+    }
+    if let Some(app_trace) = args.app_trace() {
+        filters.add(FilterId::AppTrace, FilterType::AppTrace(app_trace));
+    }
+    if let Some(level) = args.level() {
+        filters.add(FilterId::LogLevel, FilterType::LogLevel(level));
+    }
+    if let Some((from, to)) = window(args.time_from(), args.time_to()) {
+        filters.add(FilterId::Time, FilterType::Time(from, to));
+    }
+    if let Some((from, to)) = window(args.uptime_from(), args.uptime_to()) {
+        filters.add(FilterId::Uptime, FilterType::Uptime(from, to));
+    }
+    if args.before().is_some() || args.after().is_some() {
+        let (before, after) = filters.context();
+        filters.add(FilterId::Context, FilterType::Context(args.before().unwrap_or(before), args.after().unwrap_or(after)));
+    }
+    if let Some(query) = args.query() {
+        let query = Query::compile(query).unwrap_or_else(|err| {
+            eprintln!("invalid --where expression: {err}");
+            process::exit(1);
+        });
+        filters.add(FilterId::Query, FilterType::Query(query));
+    }
+}
+
+pub fn run() {
+    let args : Cli = Cli::parse();
+    dlt::set_invalid_utf8(args.invalid_utf8());
+    if let Some(format) = args.format() {
+        dlt::set_input_format(format);
+    }
+    if let Some(chaos) = args.chaos() {
+        dlt::chaos::set_chaos(chaos);
+    }
+    if let Some(warnings_path) = args.warnings() {
+        if let Err(err) = dlt::warning::set_warnings_file(warnings_path) {
+            eprintln!("error on creating warnings file {warnings_path:?}: {err}");
+            process::exit(1);
+        }
+    }
+    if let Some(dbc_path) = args.dbc() {
+        match Dbc::read(dbc_path) {
+            Ok(dbc) => dlt::set_dbc(dbc),
+            Err(err) => {
+                eprintln!("{err}");
+                process::exit(1);
+            },
         }
-        println!("config: {config:?}");
     }
+    // read once, the profiles apply to every command
+    let config = args.config().as_deref().map(read_config_or_exit);
+    if let Some(config) = &config {
+        dlt::set_profiles(config.profiles().as_deref().unwrap_or_default());
+    }
+    match &args.command {
+        Some(Command::Config { action: ConfigCommand::Test }) => run_config_tests(config),
+        Some(Command::Compile { source, output }) => config::compile_config(source, output).unwrap_or_else(|err| {
+            eprintln!("error in compiling config: {err}");
+            process::exit(1);
+        }),
+        Some(Command::Cat { input, head, tail, from, to, pretty }) => {
+            let range = from.unwrap_or(0)..=to.unwrap_or(usize::MAX);
+            dlt::run_cat(input, &default_fields(), range, *head, *tail, *pretty)
+        },
+        Some(Command::Stats { input, json }) => dlt::run_stats(input, *json),
+        Some(Command::Trim { input, from, to, output }) => dlt::run_trim(input, *from, *to, output),
+        Some(Command::Bundle { input, from, to, redact, output }) => {
+            let redactions: Vec<regex::bytes::Regex> = redact.iter().map(|pattern| regex::bytes::Regex::new(pattern).unwrap_or_else(|err| {
+                eprintln!("invalid redaction {pattern:?}: {err}");
+                process::exit(1);
+            })).collect();
+            dlt::bundle::run_bundle(input, args.config().as_deref(), *from, *to, &redactions, output)
+        },
+        Some(Command::Sort { input, output, max_memory, temp_dir }) => {
+            let temp_dir = temp_dir.clone().unwrap_or_else(std::env::temp_dir);
+            dlt::run_sort(input, output, max_memory * 1024 * 1024, &temp_dir)
+        },
+        Some(Command::ExtractFiles { input, output_dir }) => dlt::run_extract_files(input, output_dir),
+        Some(Command::Calls { input }) => dlt::run_calls(input),
+        Some(Command::Schema { input, json }) => dlt::run_schema(input, *json),
+        Some(Command::Eval { filter, payload }) => run_eval(&args, config, filter, payload),
+        Some(Command::Selftest) => dlt::selftest::run_selftest(),
+        Some(Command::Serve) => dlt::server::run_server(),
+        Some(Command::Alert { input, follow, poll, summary_interval, summary_output }) => {
+            dlt::alert::run_alerts(input, args.config().as_deref(), *follow, *poll, Duration::from_secs(*summary_interval), summary_output.as_deref())
+        },
+        Some(Command::Timeline { input, output }) => {
+            let spans = config.and_then(|config| config.spans().clone()).unwrap_or_default();
+            dlt::run_timeline(input, &spans, output)
+        },
+        None => run_filters(&args, config),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dlt::headers::{write_log_headers, LogHeaders};
+    use crate::dlt::payload::{write_value, Value};
+    use crate::dlt::{MessageTypeInfoLog, TraceData};
 
-    println!("lib filter: {filters:?}");
-    dlt::run_dlt(&args.input()[0], &filters, &output)
+    /// Verbose info message of the app logging its id.
+    fn message(app_id: &str) -> Vec<u8> {
+        let mut argument = vec![];
+        assert!(write_value(&mut argument, &Value::String(app_id), false));
+        let headers = LogHeaders {
+            seconds: 1_700_000_000,
+            microseconds: 0,
+            ecu_id: "ECU1",
+            counter: 0,
+            session_id: None,
+            timestamp: None,
+            is_big_endian: false,
+            app_id,
+            context_id: "CTX",
+            level: MessageTypeInfoLog::Info,
+            verbose: true,
+            num_of_args: 1,
+        };
+        let mut message = vec![];
+        write_log_headers(&mut message, &headers, argument.len());
+        message.extend_from_slice(&argument);
+        message
+    }
+
+    /// The apps of the messages passing the filters given on the command line.
+    fn passing_cli_filters(cli_args: &[&str], app_ids: &[&str]) -> Vec<String> {
+        let args = Cli::try_parse_from([&["dlt-kraken", "-i", "trace.dlt"], cli_args].concat()).unwrap();
+        let mut filters = dlt::filter::Filter::new();
+        add_cli_filters(&args, &mut filters);
+        let trace: Vec<u8> = app_ids.iter().flat_map(|app_id| message(app_id)).collect();
+        TraceData::new(&trace, 0).into_iter()
+            .filter(|msg| filters.matches(msg))
+            .map(|msg| msg.payload_text())
+            .collect()
+    }
+
+    #[test]
+    fn filters_by_the_ids_given_on_the_command_line() {
+        assert_eq!(passing_cli_filters(&["--app", "APP1"], &["APP1", "APP2", "SYS"]), vec!["APP1"]);
+        assert_eq!(passing_cli_filters(&["--app", "APP1,SYS"], &["APP1", "APP2", "SYS"]), vec!["APP1", "SYS"]);
+        assert_eq!(passing_cli_filters(&["--app", "APP*"], &["APP1", "APP2", "SYS"]), vec!["APP1", "APP2"]);
+        assert_eq!(passing_cli_filters(&[], &["APP1", "SYS"]), vec!["APP1", "SYS"]);
+    }
+
+    fn route(json: &str) -> Result<(dlt::filter::Filter, Vec<Output>), String> {
+        build_route(&FilterSpec::from_json(json).unwrap(), &[])
+    }
+
+    #[test]
+    fn builds_the_outputs_of_a_filter_spec() {
+        let (_, outputs) = route(r#"{"name": "speed", "patterns": ["speed (?P<val>[0-9]+)"],
+            "output": {"stdout": {"enabled": true, "delimiter": ",", "format": "ecu,<val>"}}}"#).unwrap();
+        assert_eq!(outputs.len(), 1);
+        let names: Vec<&str> = outputs[0].fields().iter().map(OutputField::name).collect();
+        assert_eq!(names, vec!["ecu", "val"]);
+    }
+
+    #[test]
+    fn returns_the_errors_of_a_filter_spec_instead_of_exiting() {
+        let unknown_capture = route(r#"{"name": "speed", "patterns": ["speed (?P<val>[0-9]+)"],
+            "output": {"stdout": {"enabled": true, "delimiter": ",", "format": "ecu,<other>"}}}"#);
+        assert_eq!(unknown_capture.err().unwrap(), "no capture defined for stdout field 'other' in filter 'speed'");
+
+        let bad_placeholder = route(r#"{"name": "speed",
+            "output": {"stdout": {"enabled": true, "delimiter": ",", "format": "{ecu} {bogus}"}}}"#);
+        assert_eq!(bad_placeholder.err().unwrap(), "invalid placeholder 'bogus' in the template of filter 'speed'");
+
+        let missing_references = route(r#"{"name": "speed",
+            "output": {"stdout": {"enabled": true, "delimiter": ",", "format": "ecu,ref_event"}}}"#);
+        assert!(missing_references.err().unwrap().starts_with("fields ref_event and ref_delta require reference_events"));
+    }
 }
@@ -0,0 +1,117 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde_json::Value;
+
+/// Column of the manifest holding the path of the input file.
+const FILE_COLUMN: &str = "file";
+
+/// Input files of a batch run with metadata about each one, e.g. vehicle, software version and
+/// test id. The metadata is written along the messages of the file, in the columns named like
+/// its keys. The manifest is a CSV file with a header row and a `file` column, or a JSON array
+/// of objects with a `file` key. Relative paths are taken relative to the manifest.
+#[derive(Debug)]
+pub struct Manifest {
+    files: Vec<PathBuf>,
+    metadata: Vec<BTreeMap<String, String>>,
+    /// metadata keys in the order of the CSV header, sorted for JSON
+    keys: Vec<String>,
+}
+
+impl Manifest {
+    pub fn read(manifest_path: &Path) -> Result<Manifest, String> {
+        let contents = fs::read_to_string(manifest_path).map_err(|err| format!("error on reading manifest {manifest_path:?}: {err}"))?;
+        let rows = if manifest_path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("json")) {
+            json_rows(&contents)
+        } else {
+            csv_rows(&contents)
+        }.map_err(|err| format!("manifest {manifest_path:?}: {err}"))?;
+
+        let directory = manifest_path.parent().unwrap_or(Path::new(""));
+        let mut manifest = Manifest { files: vec![], metadata: vec![], keys: vec![] };
+        for (row, values) in rows.into_iter().enumerate() {
+            let mut file = None;
+            let mut metadata = BTreeMap::new();
+            for (key, value) in values {
+                if key == FILE_COLUMN {
+                    file = Some(value).filter(|file| !file.is_empty());
+                    continue;
+                }
+                if !manifest.keys.contains(&key) {
+                    manifest.keys.push(key.clone());
+                }
+                metadata.insert(key, value);
+            }
+            let Some(file) = file else {
+                return Err(format!("manifest {manifest_path:?}: entry {} has no {FILE_COLUMN}", row + 1));
+            };
+            manifest.files.push(directory.join(file));
+            manifest.metadata.push(metadata);
+        }
+        if manifest.files.is_empty() {
+            return Err(format!("manifest {manifest_path:?} lists no files"));
+        }
+        Ok(manifest)
+    }
+
+    pub fn files(&self) -> &Vec<PathBuf> {
+        &self.files
+    }
+
+    pub fn keys(&self) -> &Vec<String> {
+        &self.keys
+    }
+
+    /// Metadata of the input file, `None` if the manifest does not list it.
+    pub fn metadata(&self, file_path: &Path) -> Option<&BTreeMap<String, String>> {
+        self.files.iter().position(|file| file == file_path).map(|row| &self.metadata[row])
+    }
+}
+
+/// Keys and values of each entry.
+type Rows = Vec<Vec<(String, String)>>;
+
+fn json_rows(contents: &str) -> Result<Rows, String> {
+    let entries: Vec<BTreeMap<String, Value>> = serde_json::from_str(contents).map_err(|err| err.to_string())?;
+    Ok(entries.into_iter().map(|entry| entry.into_iter().map(|(key, value)| {
+        let value = match value {
+            Value::String(text) => text,
+            Value::Null => String::new(),
+            other => other.to_string(),
+        };
+        (key, value)
+    }).collect()).collect())
+}
+
+/// Rows of a CSV file with header row, values may be quoted with `"` to contain commas.
+fn csv_rows(contents: &str) -> Result<Rows, String> {
+    let mut lines = contents.lines().filter(|line| !line.trim().is_empty());
+    let header = split_csv_line(lines.next().ok_or("empty file")?);
+    lines.enumerate().map(|(row, line)| {
+        let values = split_csv_line(line);
+        if values.len() != header.len() {
+            return Err(format!("row {} has {} values, the header {}", row + 1, values.len(), header.len()));
+        }
+        Ok(header.iter().cloned().zip(values).collect())
+    }).collect()
+}
+
+fn split_csv_line(line: &str) -> Vec<String> {
+    let mut values = vec![];
+    let mut value = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                value.push('"');
+                chars.next();
+            },
+            '"' => quoted = !quoted,
+            ',' if !quoted => values.push(std::mem::take(&mut value).trim().to_string()),
+            _ => value.push(c),
+        }
+    }
+    values.push(value.trim().to_string());
+    values
+}
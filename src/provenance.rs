@@ -0,0 +1,56 @@
+use std::env;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use sha2::{Digest, Sha256};
+
+/// Describes how an output was produced, so extracted artifacts can be traced back to the tool
+/// version, configuration and input files.
+#[derive(Debug)]
+pub struct Provenance {
+    version: &'static str,
+    command_line: String,
+    config: Option<(PathBuf, String)>,
+    inputs: Vec<(PathBuf, String)>,
+}
+
+fn hash_file(file_path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(file_path)?, &mut hasher)?;
+    let mut hash = String::new();
+    for byte in hasher.finalize() {
+        let _ = write!(hash, "{byte:02x}");
+    }
+    Ok(hash)
+}
+
+impl Provenance {
+    pub fn new(config_path: Option<&Path>, input_paths: &[PathBuf]) -> Result<Provenance, String> {
+        let hash = |file_path: &Path| hash_file(file_path)
+            .map(|hash| (file_path.to_path_buf(), hash))
+            .map_err(|err| format!("error on hashing {file_path:?}: {err}"));
+
+        Ok(Provenance {
+            version: env!("CARGO_PKG_VERSION"),
+            command_line: env::args().collect::<Vec<_>>().join(" "),
+            config: config_path.map(hash).transpose()?,
+            inputs: input_paths.iter().map(|input_path| hash(input_path)).collect::<Result<_, _>>()?,
+        })
+    }
+
+    /// Lines of the provenance, each one `key: value`.
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!("dlt-kraken: {}", self.version),
+            format!("command line: {}", self.command_line),
+        ];
+        if let Some((config_path, hash)) = &self.config {
+            lines.push(format!("config: {} sha256:{hash}", config_path.display()));
+        }
+        for (input_path, hash) in &self.inputs {
+            lines.push(format!("input: {} sha256:{hash}", input_path.display()));
+        }
+        lines
+    }
+}